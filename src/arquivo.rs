@@ -0,0 +1,1073 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cabecalho::{self, Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::indice::{IndiceParcial, construir_indice_parcial};
+use crate::indice_secundario::{self, caminho_pendencias};
+use crate::arvore_b::{construir_arvore, Ponteiro, ORDEM_MAXIMA, ORDEM_MINIMA, ORDEM_PADRAO};
+use crate::indice_hash::{construir_hash, CAPACIDADE_MAXIMA, CAPACIDADE_MINIMA, CAPACIDADE_PADRAO};
+use crate::indice_overflow::construir_indice_overflow;
+use crate::manifesto::{self, caminho_temporario};
+use crate::wal::{ArquivoAlvo, Escrita, Wal};
+use crate::ordenacao_externa::{OrdenadorExterno, MEMORIA_ORDENACAO_PADRAO};
+use crate::registro::{bytes_removido, Registro};
+use crate::textos::{self, textos_do_arquivo, FormatoTextos, Textos};
+use crate::erro::{Erro, Resultado};
+
+// Critério: reconstruir quando overflow > 10% do principal
+pub const LIMITE_OVERFLOW: f64 = 0.1;
+
+// O que a recuperação encontrou pendente
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recuperacao {
+    pub reconstrucao: Option<manifesto::Estado>,
+    pub operacoes_reaplicadas: usize,
+    // A árvore B+ estava no meio de uma alteração e foi refeita a partir dos dados
+    pub arvore_refeita: bool,
+    // Idem para o índice hash
+    pub hash_refeito: bool,
+}
+
+// O que fazer ao inserir uma chave que já existe no principal ou no overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoliticaDuplicatas {
+    // Recusa a inserção com Erro::ChaveDuplicada
+    #[default]
+    Rejeitar,
+    // Regrava o registro existente no lugar (upsert)
+    Substituir,
+}
+
+impl PoliticaDuplicatas {
+    pub fn from_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "rejeitar" => Some(PoliticaDuplicatas::Rejeitar),
+            "substituir" => Some(PoliticaDuplicatas::Substituir),
+            _ => None,
+        }
+    }
+}
+
+// Como terminou uma inserção
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Insercao {
+    // Acrescentado no overflow
+    Inserido,
+    // A chave já existia e o registro foi regravado no lugar (PoliticaDuplicatas::Substituir)
+    Substituido,
+    // Acrescentado no overflow, que passou do limite e disparou a reconstrução
+    Reconstruido,
+}
+
+// Quantas chaves descartadas aparecem ao mostrar o relatório
+pub const MAX_DUPLICATAS_NA_TELA: usize = 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct RelatorioReconstrucao {
+    pub gravados: usize,
+    // Registros ilegíveis movidos para a quarentena (só no reparo)
+    pub quarentenados: u64,
+    // Chave de cada registro descartado por ter a mesma chave de um mais recente
+    pub duplicatas_descartadas: Vec<i64>,
+}
+
+impl fmt::Display for RelatorioReconstrucao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Registros no arquivo principal: {}", self.gravados)?;
+        if self.quarentenados > 0 {
+            write!(f, "\nRegistros corrompidos movidos para a quarentena: {}", self.quarentenados)?;
+        }
+        if !self.duplicatas_descartadas.is_empty() {
+            write!(f, "\nDuplicatas descartadas (ficou a versão mais recente): {}", self.duplicatas_descartadas.len())?;
+            let chaves: Vec<String> = self
+                .duplicatas_descartadas
+                .iter()
+                .take(MAX_DUPLICATAS_NA_TELA)
+                .map(|c| c.to_string())
+                .collect();
+            write!(f, "\n   chaves: {}", chaves.join(", "))?;
+            if self.duplicatas_descartadas.len() > MAX_DUPLICATAS_NA_TELA {
+                write!(f, " ... e mais {}", self.duplicatas_descartadas.len() - MAX_DUPLICATAS_NA_TELA)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Arquivo principal ordenado pela chave + área de overflow não ordenada,
+// com as mesmas operações para qualquer tipo de registro
+#[derive(Debug, Clone)]
+pub struct ArquivoSequencial<R: Registro> {
+    pub caminho_principal: String,
+    pub caminho_overflow: String,
+    // Índice que a reconstrução troca junto com os dados (se informado)
+    pub caminho_indice: Option<String>,
+    // Memória (em bytes) que a reconstrução pode usar antes de ordenar em disco
+    pub memoria_ordenacao: usize,
+    pub politica_duplicatas: PoliticaDuplicatas,
+    // Campos com índice secundário mantido nas escritas e na reconstrução
+    pub indices_secundarios: Vec<String>,
+    // Ordem da árvore B+ usada nas consultas no lugar do índice parcial (None = índice parcial)
+    pub ordem_arvore: Option<usize>,
+    // Capacidade dos baldes do índice hash usado nas consultas (tem preferência sobre a árvore)
+    pub capacidade_hash: Option<usize>,
+    // Formato dos textos nos arquivos que esta estrutura cria (overflow novo, reconstrução)
+    pub formato_textos: FormatoTextos,
+    _registro: PhantomData<R>,
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn novo(caminho_principal: &str, caminho_overflow: &str) -> Self {
+        ArquivoSequencial {
+            caminho_principal: caminho_principal.to_string(),
+            caminho_overflow: caminho_overflow.to_string(),
+            caminho_indice: None,
+            memoria_ordenacao: MEMORIA_ORDENACAO_PADRAO,
+            politica_duplicatas: PoliticaDuplicatas::default(),
+            indices_secundarios: Vec::new(),
+            ordem_arvore: None,
+            capacidade_hash: None,
+            formato_textos: FormatoTextos::default(),
+            _registro: PhantomData,
+        }
+    }
+
+    pub fn com_indice(mut self, caminho_indice: &str) -> Self {
+        self.caminho_indice = Some(caminho_indice.to_string());
+        self
+    }
+
+    pub fn com_memoria_ordenacao(mut self, memoria: usize) -> Self {
+        self.memoria_ordenacao = memoria;
+        self
+    }
+
+    pub fn com_politica_duplicatas(mut self, politica: PoliticaDuplicatas) -> Self {
+        self.politica_duplicatas = politica;
+        self
+    }
+
+    pub fn com_indices_secundarios(mut self, campos: &[String]) -> Self {
+        self.indices_secundarios = campos.to_vec();
+        self
+    }
+
+    pub fn com_arvore_b(mut self, ordem: usize) -> Self {
+        self.ordem_arvore = Some(ordem);
+        self
+    }
+
+    pub fn com_hash(mut self, capacidade: usize) -> Self {
+        self.capacidade_hash = Some(capacidade);
+        self
+    }
+
+    pub fn com_formato_textos(mut self, formato: FormatoTextos) -> Self {
+        self.formato_textos = formato;
+        self
+    }
+
+    fn caminho_do_alvo(&self, alvo: ArquivoAlvo) -> &str {
+        match alvo {
+            ArquivoAlvo::Principal => &self.caminho_principal,
+            ArquivoAlvo::Overflow => &self.caminho_overflow,
+        }
+    }
+
+    // Textos do arquivo onde o registro vai ser gravado (overflow ainda vazio: o formato configurado)
+    fn textos_do_alvo(&self, alvo: ArquivoAlvo) -> Resultado<Textos> {
+        textos_do_arquivo(self.caminho_do_alvo(alvo), R::LAYOUT, self.formato_textos)
+    }
+
+    // Bytes do registro para gravar no alvo, com os textos que não cabem nele já no disco
+    fn bytes_para(&self, textos: &mut Textos, registro: &R) -> Resultado<Vec<u8>> {
+        let bytes = registro.to_bytes(textos)?;
+        textos.sincronizar()?;
+        Ok(bytes)
+    }
+
+    // Busca pelo índice em disco configurado para as consultas (hash ou árvore
+    // B+), se ele já foi construído. Uma falta não é definitiva: registros
+    // gravados enquanto o índice não estava aberto (por outro processo ou
+    // antes de configurá-lo) só aparecem no índice parcial e no overflow, então
+    // None devolve a consulta para eles.
+    fn localizar_em_disco(&self, chave: i64) -> Resultado<Option<(ArquivoAlvo, u64, R)>> {
+        if self.capacidade_hash.is_some()
+            && let Some(mut hash) = self.abrir_hash()?
+            && let Some(encontrado) = self.localizar_no_hash(&mut hash, chave)?
+        {
+            return Ok(Some(encontrado));
+        }
+        if self.ordem_arvore.is_some()
+            && let Some(mut arvore) = self.abrir_arvore()?
+            && let Some(encontrado) = self.localizar_na_arvore(&mut arvore, chave)?
+        {
+            return Ok(Some(encontrado));
+        }
+        Ok(None)
+    }
+
+    // Árvore e hash existentes recebem toda inserção (antes da escrita dos
+    // dados: um ponteiro sem registro é descartado na conferência) e toda
+    // remoção (depois da escrita)
+    fn indices_em_disco_inserir(&self, chave: i64, ponteiro: Ponteiro) -> Resultado<()> {
+        if let Some(mut arvore) = self.abrir_arvore()? {
+            arvore.inserir(chave, ponteiro)?;
+        }
+        if let Some(mut hash) = self.abrir_hash()? {
+            hash.inserir(chave, ponteiro)?;
+        }
+        Ok(())
+    }
+
+    fn indices_em_disco_remover(&self, chave: i64) -> Resultado<()> {
+        if let Some(mut arvore) = self.abrir_arvore()? {
+            arvore.remover(chave)?;
+        }
+        if let Some(mut hash) = self.abrir_hash()? {
+            hash.remover(chave)?;
+        }
+        Ok(())
+    }
+
+    // Busca binária no principal e, se não achar, no overflow
+    pub fn buscar(&self, chave: i64) -> Resultado<Option<R>> {
+        if let Some(registro) = busca_binaria(&self.caminho_principal, chave)? {
+            return Ok(Some(registro));
+        }
+        self.buscar_no_overflow(chave)
+    }
+
+    // Busca pelo hash ou pela árvore B+, quando configurados, e depois via
+    // índice parcial no principal e, se não achar, no overflow
+    pub fn consultar(&self, indice: &IndiceParcial, chave: i64) -> Resultado<Option<R>> {
+        if let Some((_, _, registro)) = self.localizar_em_disco(chave)? {
+            return Ok(Some(registro));
+        }
+        if let Some(registro) = consultar_com_indice(&self.caminho_principal, indice, chave)? {
+            return Ok(Some(registro));
+        }
+        self.buscar_no_overflow(chave)
+    }
+
+    // Pelo índice ordenado do overflow (produtos_overflow.dat.idx) ou, sem ele, sequencial
+    pub fn buscar_no_overflow(&self, chave: i64) -> Resultado<Option<R>> {
+        Ok(self.localizar_no_overflow(chave)?.map(|(_, registro)| registro))
+    }
+
+    // Registros no overflow segundo o cabeçalho dele (0 se ainda não existe)
+    pub(crate) fn num_registros_overflow(&self) -> Resultado<u64> {
+        self.num_registros_em(ArquivoAlvo::Overflow)
+    }
+
+    // Registros no principal ou no overflow segundo o cabeçalho (0 se o arquivo
+    // ainda não existe ou está vazio)
+    fn num_registros_em(&self, alvo: ArquivoAlvo) -> Resultado<u64> {
+        let caminho = self.caminho_do_alvo(alvo);
+        if std::fs::metadata(caminho).map(|m| m.len() == 0).unwrap_or(true) {
+            return Ok(0);
+        }
+        let mut arquivo = File::open(caminho)?;
+        Ok(cabecalho::ler_cabecalho_dados(&mut arquivo, R::LAYOUT)?.num_registros)
+    }
+
+    pub fn wal(&self) -> Wal {
+        Wal::novo(&self.caminho_principal, &self.caminho_overflow)
+    }
+
+    // Remoção lógica: localiza o registro como a consulta (hash ou árvore B+,
+    // índice parcial no principal e overflow) e o marca como removido. Com o
+    // índice parcial vazio, o principal é percorrido do início.
+    pub fn remover(&self, indice: &IndiceParcial, chave: i64) -> Resultado<()> {
+        let encontrado = match self.localizar(indice, chave)? {
+            None if indice.entradas.is_empty() && Path::new(&self.caminho_principal).exists() => {
+                match localizar_sequencial::<R>(&self.caminho_principal, chave)? {
+                    Some(posicao) => {
+                        let bytes = ler_bytes_em::<R>(&self.caminho_principal, posicao)?;
+                        let mut textos = self.textos_do_alvo(ArquivoAlvo::Principal)?;
+                        Some((ArquivoAlvo::Principal, posicao, R::ler_em(&bytes, posicao, &mut textos)?))
+                    }
+                    None => None,
+                }
+            }
+            encontrado => encontrado,
+        };
+        let Some((alvo, posicao, registro)) = encontrado else {
+            return Err(Erro::ChaveNaoEncontrada(chave));
+        };
+        self.remover_em(alvo, posicao, &registro)
+    }
+
+    pub fn remover_do_overflow(&self, chave: i64) -> Resultado<bool> {
+        let Some((posicao, registro)) = self.localizar_no_overflow(chave)? else {
+            return Ok(false);
+        };
+        self.remover_em(ArquivoAlvo::Overflow, posicao, &registro)?;
+        Ok(true)
+    }
+
+    // Remoção lógica registrada no WAL
+    fn remover_em(&self, alvo: ArquivoAlvo, posicao: u64, registro: &R) -> Resultado<()> {
+        // O registro inteiro é reescrito para o CRC continuar valendo
+        let bytes = bytes_removido(R::LAYOUT, &ler_bytes_em::<R>(self.caminho_do_alvo(alvo), posicao)?);
+        self.wal().executar(&[Escrita { alvo, posicao, bytes }])?;
+        self.indices_em_disco_remover(registro.chave())?;
+        if alvo == ArquivoAlvo::Overflow {
+            self.indice_overflow_depois_da_remocao(registro.chave(), posicao)?;
+        }
+        self.secundarios_depois_da_escrita(Some(registro), None)?;
+        Ok(())
+    }
+
+    // Atualização no lugar: localiza o registro pelo índice no principal (ou
+    // no overflow), aplica `alterar` e regrava na mesma posição
+    // pelo WAL. Como a chave não muda, a ordem do principal e o índice continuam
+    // valendo. Retorna o registro como ficou.
+    pub fn atualizar(&self, indice: &IndiceParcial, chave: i64, alterar: impl FnOnce(&mut R) -> Resultado<()>) -> Resultado<R> {
+        let Some((alvo, posicao, antigo)) = self.localizar(indice, chave)? else {
+            return Err(Erro::ChaveNaoEncontrada(chave));
+        };
+        let mut registro = antigo.clone();
+        alterar(&mut registro)?;
+        if registro.chave() != chave {
+            return Err(Erro::AlteracaoInvalida(format!(
+                "a chave {} não pode ser alterada (remova e insira de novo)",
+                chave
+            )));
+        }
+        let bytes = self.bytes_para(&mut self.textos_do_alvo(alvo)?, &registro)?;
+        self.secundarios_antes_da_escrita(Some(&antigo), Some(&registro))?;
+        self.wal().executar(&[Escrita { alvo, posicao, bytes }])?;
+        self.secundarios_depois_da_escrita(Some(&antigo), Some(&registro))?;
+        Ok(registro)
+    }
+
+    // Onde está o registro com a chave: no principal (pelo índice) ou no overflow
+    pub(crate) fn localizar(&self, indice: &IndiceParcial, chave: i64) -> Resultado<Option<(ArquivoAlvo, u64, R)>> {
+        if let Some(encontrado) = self.localizar_em_disco(chave)? {
+            return Ok(Some(encontrado));
+        }
+        if Path::new(&self.caminho_principal).exists()
+            && let Some((posicao, registro)) = localizar_com_indice::<R>(&self.caminho_principal, indice, chave)?
+        {
+            return Ok(Some((ArquivoAlvo::Principal, posicao, registro)));
+        }
+        Ok(self
+            .localizar_no_overflow(chave)?
+            .map(|(posicao, registro)| (ArquivoAlvo::Overflow, posicao, registro)))
+    }
+
+    // Acrescenta o registro no overflow; reconstrói tudo se o overflow passar do
+    // limite. Se a chave já existir, segue a politica_duplicatas.
+    pub fn inserir(&self, registro: &R, indice: &mut IndiceParcial) -> Resultado<Insercao> {
+        if let Some((alvo, posicao, antigo)) = self.localizar(indice, registro.chave())? {
+            return match self.politica_duplicatas {
+                PoliticaDuplicatas::Rejeitar => Err(Erro::ChaveDuplicada(registro.chave())),
+                PoliticaDuplicatas::Substituir => {
+                    let bytes = self.bytes_para(&mut self.textos_do_alvo(alvo)?, registro)?;
+                    self.secundarios_antes_da_escrita(Some(&antigo), Some(registro))?;
+                    self.wal().executar(&[Escrita { alvo, posicao, bytes }])?;
+                    self.secundarios_depois_da_escrita(Some(&antigo), Some(registro))?;
+                    Ok(Insercao::Substituido)
+                }
+            };
+        }
+        // Tudo o que pode falhar na leitura vem antes da escrita: depois do WAL a
+        // inserção já está feita e um erro diria o contrário ao chamador
+        let num_principal = self.num_registros_em(ArquivoAlvo::Principal)?;
+        // Registro novo e contador do cabeçalho vão juntos numa única entrada do WAL
+        let vazio = std::fs::metadata(&self.caminho_overflow).map(|m| m.len() == 0).unwrap_or(true);
+        let num_overflow = self.num_registros_overflow()?;
+        let mut textos = self.textos_do_alvo(ArquivoAlvo::Overflow)?;
+        let mut escritas = vec![Escrita {
+            alvo: ArquivoAlvo::Overflow,
+            posicao: posicao_registro::<R>(num_overflow),
+            bytes: self.bytes_para(&mut textos, registro)?,
+        }];
+        if vazio {
+            let novo = Cabecalho::novo(TipoArquivo::Dados, R::LAYOUT, 0, 1).com_formato_textos(textos.formato());
+            escritas.push(Escrita { alvo: ArquivoAlvo::Overflow, posicao: 0, bytes: novo.to_bytes().to_vec() });
+        } else {
+            escritas.push(Escrita {
+                alvo: ArquivoAlvo::Overflow,
+                posicao: cabecalho::OFFSET_NUM_REGISTROS,
+                bytes: (num_overflow + 1).to_le_bytes().to_vec(),
+            });
+        }
+        self.indices_em_disco_inserir(
+            registro.chave(),
+            Ponteiro { alvo: ArquivoAlvo::Overflow, posicao: posicao_registro::<R>(num_overflow) },
+        )?;
+        self.indice_overflow_antes_da_insercao(registro.chave(), posicao_registro::<R>(num_overflow))?;
+        self.secundarios_antes_da_escrita(None, Some(registro))?;
+        self.wal().executar(&escritas)?;
+
+        if (num_overflow + 1) as f64 > num_principal as f64 * LIMITE_OVERFLOW {
+            self.reconstruir(indice)?;
+            return Ok(Insercao::Reconstruido);
+        }
+        Ok(Insercao::Inserido)
+    }
+
+    // Junta principal e overflow (sem os removidos), reescreve o principal ordenado,
+    // esvazia o overflow e reconstrói o índice. Chaves repetidas (de antes da
+    // política de duplicatas, ou de arquivos importados) ficam só com o registro
+    // mais recente: o do overflow, ou o último acrescentado a ele. As descartadas
+    // aparecem no relatório.
+    //
+    // Os arquivos novos são gravados ao lado dos atuais e só substituem os antigos
+    // todos juntos, pelo manifesto: uma queda no meio deixa o estado antigo ou o
+    // novo, nunca uma mistura. Com caminho_indice definido o índice também é
+    // salvo nessa troca; sem ele, fica só em memória para o chamador salvar.
+    pub fn reconstruir(&self, indice: &mut IndiceParcial) -> Resultado<RelatorioReconstrucao> {
+        self.reconstruir_com(indice, None)
+    }
+
+    // Reconstrução que, em vez de parar no primeiro registro corrompido, move os
+    // registros ilegíveis (e bytes soltos no fim dos arquivos) para a quarentena
+    // (produtos.dat.quarentena).
+    pub fn reparar(&self, indice: &mut IndiceParcial) -> Resultado<RelatorioReconstrucao> {
+        let quarentena = caminho_quarentena(&self.caminho_principal);
+        self.reconstruir_com(indice, Some(&quarentena))
+    }
+
+    fn reconstruir_com(&self, indice: &mut IndiceParcial, quarentena: Option<&str>) -> Resultado<RelatorioReconstrucao> {
+        let caminho_indice_overflow = self.caminho_indice_overflow();
+        let textos_principal = textos::caminho_textos(&self.caminho_principal);
+        let textos_overflow = textos::caminho_textos(&self.caminho_overflow);
+        let mut definitivos = vec![
+            self.caminho_principal.as_str(),
+            self.caminho_overflow.as_str(),
+            caminho_indice_overflow.as_str(),
+            textos_principal.as_str(),
+            textos_overflow.as_str(),
+        ];
+        if let Some(caminho_indice) = &self.caminho_indice {
+            definitivos.push(caminho_indice);
+        }
+        let secundarios: Vec<(String, String)> = self
+            .campos_com_indice_secundario()
+            .into_iter()
+            .map(|campo| {
+                let caminho = self.caminho_indice_secundario(&campo);
+                (campo, caminho)
+            })
+            .collect();
+        let pendencias: Vec<String> = secundarios.iter().map(|(_, caminho)| caminho_pendencias(caminho)).collect();
+        definitivos.extend(secundarios.iter().map(|(_, caminho)| caminho.as_str()));
+        definitivos.extend(pendencias.iter().map(String::as_str));
+        let caminho_arvore = self.caminho_arvore();
+        let ordem_arvore = parametro_para_reconstrucao(self.ordem_arvore, &caminho_arvore, ORDEM_PADRAO, ORDEM_MINIMA..=ORDEM_MAXIMA)?;
+        if ordem_arvore.is_some() {
+            definitivos.push(&caminho_arvore);
+        }
+        let caminho_hash = self.caminho_hash();
+        let capacidade_hash =
+            parametro_para_reconstrucao(self.capacidade_hash, &caminho_hash, CAPACIDADE_PADRAO, CAPACIDADE_MINIMA..=CAPACIDADE_MAXIMA)?;
+        if capacidade_hash.is_some() {
+            definitivos.push(&caminho_hash);
+        }
+        let (relatorio, novo_indice) = manifesto::trocar(&self.caminho_principal, &definitivos, || {
+            let novo_principal = caminho_temporario(&self.caminho_principal);
+            let mut ordenador =
+                OrdenadorExterno::<R>::novo(&novo_principal, self.memoria_ordenacao).com_formato_textos(self.formato_textos);
+            let mut quarentenados = 0;
+            for caminho in [&self.caminho_principal, &self.caminho_overflow] {
+                match quarentena {
+                    None => percorrer_validos::<R>(caminho, |r| ordenador.adicionar(r))?,
+                    Some(quarentena) => {
+                        quarentenados += separar_corrompidos::<R>(caminho, quarentena, |r| ordenador.adicionar(r))?;
+                    }
+                }
+            }
+            let (gravados, duplicatas_descartadas) = ordenador.finalizar_sem_duplicatas()?;
+
+            textos::criar_arquivo_dados(&caminho_temporario(&self.caminho_overflow), R::LAYOUT, self.formato_textos)?;
+            construir_indice_overflow::<R>(&caminho_temporario(&self.caminho_overflow), &caminho_temporario(&caminho_indice_overflow))?;
+            let novo_indice = construir_indice_parcial(&novo_principal, indice.fator_esparsidade, R::LAYOUT)?;
+            if let Some(caminho_indice) = &self.caminho_indice {
+                novo_indice.salvar_binario(&caminho_temporario(caminho_indice))?;
+            }
+            if let Some(ordem) = ordem_arvore {
+                construir_arvore::<R>(&novo_principal, &caminho_temporario(&self.caminho_overflow), &caminho_temporario(&caminho_arvore), ordem)?;
+            }
+            if let Some(capacidade) = capacidade_hash {
+                construir_hash::<R>(&novo_principal, &caminho_temporario(&self.caminho_overflow), &caminho_temporario(&caminho_hash), capacidade)?;
+            }
+            // Índices secundários refeitos do principal novo, sem pendências
+            for (campo, caminho) in &secundarios {
+                indice_secundario::construir::<R>(&[&novo_principal], campo, &caminho_temporario(caminho))?;
+                File::create(caminho_temporario(&caminho_pendencias(caminho)))?;
+            }
+            Ok((RelatorioReconstrucao { gravados, quarentenados, duplicatas_descartadas }, novo_indice))
+        })?;
+        // O WAL era da geração anterior, já incorporada aos arquivos novos
+        self.wal().descartar()?;
+        *indice = novo_indice;
+        Ok(relatorio)
+    }
+
+    // Conclui ou descarta uma reconstrução interrompida e reaplica o WAL. Deve
+    // rodar antes de qualquer outra operação depois de uma queda.
+    pub fn recuperar(&self) -> Resultado<Recuperacao> {
+        let reconstrucao = manifesto::recuperar(&self.caminho_principal)?;
+        let operacoes_reaplicadas = self.wal().reaplicar()?;
+        // Dados num layout anterior não servem para refazer índices; a migração refaz todos
+        if self.migracao_pendente() {
+            return Ok(Recuperacao { reconstrucao, operacoes_reaplicadas, arvore_refeita: false, hash_refeito: false });
+        }
+        // Árvore ou hash marcados como sujos (ou ilegíveis): refeitos com os dados já em dia
+        let mut arvore_refeita = false;
+        if self.abrir_arvore().is_err()
+            && let Some(ordem) = parametro_para_reconstrucao(None, &self.caminho_arvore(), ORDEM_PADRAO, ORDEM_MINIMA..=ORDEM_MAXIMA)?
+        {
+            self.construir_arvore(ordem)?;
+            arvore_refeita = true;
+        }
+        let mut hash_refeito = false;
+        if self.abrir_hash().is_err()
+            && let Some(capacidade) =
+                parametro_para_reconstrucao(None, &self.caminho_hash(), CAPACIDADE_PADRAO, CAPACIDADE_MINIMA..=CAPACIDADE_MAXIMA)?
+        {
+            self.construir_hash(capacidade)?;
+            hash_refeito = true;
+        }
+        Ok(Recuperacao { reconstrucao, operacoes_reaplicadas, arvore_refeita, hash_refeito })
+    }
+}
+
+// Parâmetro (ordem da árvore, capacidade dos baldes) de um índice em disco a
+// refazer: o configurado ou, se o arquivo existe sem estar configurado, o
+// gravado no próprio cabeçalho (lido sem validar o resto: o índice pode estar sujo).
+// None quando não há índice a refazer.
+fn parametro_para_reconstrucao(
+    configurado: Option<usize>,
+    caminho: &str,
+    padrao: usize,
+    limites: std::ops::RangeInclusive<usize>,
+) -> Resultado<Option<usize>> {
+    if configurado.is_some() {
+        return Ok(configurado);
+    }
+    if !Path::new(caminho).exists() {
+        return Ok(None);
+    }
+    let cabecalho = Cabecalho::ler(&mut File::open(caminho)?);
+    Ok(Some(cabecalho.map(|c| c.fator_esparsidade as usize).unwrap_or(padrao).clamp(*limites.start(), *limites.end())))
+}
+
+// Abre um arquivo de dados para leitura, validando o cabeçalho contra o layout
+// de R, junto com os textos dele
+fn abrir<R: Registro>(caminho: &str) -> Resultado<(File, u64, Textos)> {
+    let mut arquivo = File::open(caminho)?;
+    let cabecalho = cabecalho::ler_cabecalho_dados(&mut arquivo, R::LAYOUT)?;
+    Ok((arquivo, cabecalho.num_registros, Textos::do_arquivo(caminho, R::LAYOUT, &cabecalho)))
+}
+
+fn posicao_registro<R: Registro>(indice: u64) -> u64 {
+    TAMANHO_CABECALHO as u64 + indice * R::TAMANHO_REGISTRO as u64
+}
+
+pub fn gravar_ordenados<R: Registro>(registros: &mut [R], caminho: &str) -> Resultado<()> {
+    gravar_ordenados_com(registros, caminho, FormatoTextos::default())
+}
+
+pub fn gravar_ordenados_com<R: Registro>(registros: &mut [R], caminho: &str, formato: FormatoTextos) -> Resultado<()> {
+    registros.sort_by_key(|r| r.chave());
+    let (mut arquivo, mut textos) = textos::criar_arquivo_dados(caminho, R::LAYOUT, formato)?;
+    let mut saida = std::io::BufWriter::new(&mut arquivo);
+    for registro in registros.iter() {
+        saida.write_all(&registro.to_bytes(&mut textos)?)?;
+    }
+    textos.sincronizar()?;
+    saida.flush()?;
+    drop(saida);
+    cabecalho::atualizar_num_registros(&mut arquivo, registros.len() as u64)
+}
+
+pub fn ler_primeiros<R: Registro>(caminho: &str, limite: usize) -> Resultado<Vec<R>> {
+    let (mut arquivo, _, mut textos) = abrir::<R>(caminho)?;
+    let mut registros = Vec::new();
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    for i in 0..limite as u64 {
+        match arquivo.read_exact(&mut buffer) {
+            Ok(_) => registros.push(R::ler_em(&buffer, posicao_registro::<R>(i), &mut textos)?),
+            Err(_) => break,
+        }
+    }
+    Ok(registros)
+}
+
+// Lê todos os registros não removidos; arquivo inexistente ou vazio é tratado como vazio
+pub fn ler_validos<R: Registro>(caminho: &str) -> Resultado<Vec<R>> {
+    let mut registros = Vec::new();
+    percorrer_validos::<R>(caminho, |r| {
+        registros.push(r);
+        Ok(())
+    })?;
+    Ok(registros)
+}
+
+// Passa cada registro não removido para `visitar`, sem carregar o arquivo inteiro
+pub fn percorrer_validos<R: Registro>(caminho: &str, mut visitar: impl FnMut(R) -> Resultado<()>) -> Resultado<()> {
+    if std::fs::metadata(caminho).map(|m| m.len() == 0).unwrap_or(true) {
+        return Ok(());
+    }
+    let (arquivo, num_registros, mut textos) = abrir::<R>(caminho)?;
+    let mut leitor = std::io::BufReader::new(arquivo);
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    for i in 0..num_registros {
+        leitor.read_exact(&mut buffer)?;
+        let registro = R::ler_em(&buffer, posicao_registro::<R>(i), &mut textos)?;
+        if !registro.removido() {
+            visitar(registro)?;
+        }
+    }
+    Ok(())
+}
+
+// Passa cada registro completo do arquivo (sem decodificar) e sua posição,
+// mesmo que o contador do cabeçalho não bata com o tamanho do arquivo.
+// Retorna quantos bytes sobraram depois do último registro completo.
+pub fn percorrer_brutos<R: Registro>(caminho: &str, mut visitar: impl FnMut(u64, &[u8]) -> Resultado<()>) -> Resultado<u64> {
+    if std::fs::metadata(caminho).map(|m| m.len() == 0).unwrap_or(true) {
+        return Ok(0);
+    }
+    let mut arquivo = File::open(caminho)?;
+    Cabecalho::ler(&mut arquivo)?.validar(TipoArquivo::Dados, R::LAYOUT)?;
+    let corpo = arquivo.metadata()?.len().saturating_sub(TAMANHO_CABECALHO as u64);
+    let completos = corpo / R::TAMANHO_REGISTRO as u64;
+    let mut leitor = std::io::BufReader::new(arquivo);
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    for i in 0..completos {
+        leitor.read_exact(&mut buffer)?;
+        visitar(posicao_registro::<R>(i), &buffer)?;
+    }
+    Ok(corpo % R::TAMANHO_REGISTRO as u64)
+}
+
+pub fn caminho_quarentena(caminho_principal: &str) -> String {
+    format!("{}.quarentena", caminho_principal)
+}
+
+// Passa os registros válidos (não removidos) para `visitar` e acrescenta os
+// ilegíveis e os bytes soltos do fim ao arquivo de quarentena. Retorna quantos
+// registros foram para a quarentena.
+fn separar_corrompidos<R: Registro>(caminho: &str, quarentena: &str, mut visitar: impl FnMut(R) -> Resultado<()>) -> Resultado<u64> {
+    let mut saida: Option<File> = None;
+    let mut quarentenar = |bytes: &[u8]| -> Resultado<()> {
+        if saida.is_none() {
+            saida = Some(std::fs::OpenOptions::new().create(true).append(true).open(quarentena)?);
+        }
+        if let Some(arquivo) = saida.as_mut() {
+            arquivo.write_all(bytes)?;
+        }
+        Ok(())
+    };
+    let mut quarentenados = 0;
+    let mut textos = textos_do_arquivo(caminho, R::LAYOUT, FormatoTextos::Fixos)?;
+    let sobra = percorrer_brutos::<R>(caminho, |_, bytes| {
+        match R::from_bytes(bytes, &mut textos) {
+            Ok(registro) if !registro.removido() => visitar(registro)?,
+            Ok(_) => {}
+            Err(_) => {
+                quarentenar(bytes)?;
+                quarentenados += 1;
+            }
+        }
+        Ok(())
+    })?;
+    if sobra > 0 {
+        let bytes = std::fs::read(caminho)?;
+        quarentenar(&bytes[bytes.len() - sobra as usize..])?;
+    }
+    if let Some(arquivo) = saida.as_mut() {
+        arquivo.sync_all()?;
+    }
+    Ok(quarentenados)
+}
+
+pub fn busca_binaria<R: Registro>(caminho: &str, chave: i64) -> Resultado<Option<R>> {
+    let (mut arquivo, num_registros, mut textos) = abrir::<R>(caminho)?;
+    let mut esq = 0i64;
+    let mut dir = num_registros as i64 - 1;
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    while esq <= dir {
+        let meio = (esq + dir) / 2;
+        let posicao = posicao_registro::<R>(meio as u64);
+        arquivo.seek(SeekFrom::Start(posicao))?;
+        arquivo.read_exact(&mut buffer)?;
+        let registro = R::ler_em(&buffer, posicao, &mut textos)?;
+        if registro.chave() < chave {
+            esq = meio + 1;
+        } else if registro.chave() > chave {
+            dir = meio - 1;
+        } else {
+            return Ok(Some(registro));
+        }
+    }
+    Ok(None)
+}
+
+pub fn consultar_com_indice<R: Registro>(
+    caminho: &str,
+    indice: &IndiceParcial,
+    chave: i64,
+) -> Resultado<Option<R>> {
+    Ok(localizar_com_indice(caminho, indice, chave)?.map(|(_, registro)| registro))
+}
+
+// Como consultar_com_indice, devolvendo também a posição do registro no arquivo
+pub fn localizar_com_indice<R: Registro>(
+    caminho: &str,
+    indice: &IndiceParcial,
+    chave: i64,
+) -> Resultado<Option<(u64, R)>> {
+    if let Some((idx, posicao_inicial)) = indice.buscar_posicao(chave) {
+        let (mut arquivo, _, mut textos) = abrir::<R>(caminho)?;
+        let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+        let tamanho_arquivo = arquivo.metadata()?.len();
+        let posicao_final = if idx + 1 < indice.entradas.len() {
+            indice.entradas[idx + 1].posicao
+        } else {
+            tamanho_arquivo
+        };
+        if posicao_inicial < TAMANHO_CABECALHO as u64
+            || !(posicao_inicial - TAMANHO_CABECALHO as u64).is_multiple_of(R::TAMANHO_REGISTRO as u64)
+            || posicao_final > tamanho_arquivo
+        {
+            return Err(Erro::IndiceInconsistente(format!(
+                "entrada {} aponta para a posição {}, fora dos registros de {}",
+                idx, posicao_inicial, caminho
+            )));
+        }
+        arquivo.seek(SeekFrom::Start(posicao_inicial))?;
+        let mut pos_atual = posicao_inicial;
+        while pos_atual < posicao_final {
+            if arquivo.read_exact(&mut buffer).is_err() {
+                break;
+            }
+            let registro = R::ler_em(&buffer, pos_atual, &mut textos)?;
+            // O primeiro registro do bloco deve ter a chave da entrada (a não ser que tenha sido removido)
+            if pos_atual == posicao_inicial
+                && !indice.entradas.is_empty()
+                && !registro.removido()
+                && registro.chave() != indice.entradas[idx].chave
+                && posicao_inicial == indice.entradas[idx].posicao
+            {
+                return Err(Erro::IndiceInconsistente(format!(
+                    "entrada {} indica a chave {} na posição {}, mas o arquivo tem {}",
+                    idx, indice.entradas[idx].chave, posicao_inicial, registro.chave()
+                )));
+            }
+            if registro.chave() == chave {
+                return Ok(Some((pos_atual, registro)));
+            }
+            if registro.chave() > chave {
+                break;
+            }
+            pos_atual += R::TAMANHO_REGISTRO as u64;
+        }
+    }
+    Ok(None)
+}
+
+// Passos de uma consulta pelo índice parcial, para quem quiser mostrar como a
+// busca andou (a opção "com debug" do menu)
+#[derive(Debug, Clone)]
+pub struct RastroConsulta<R> {
+    pub chave: i64,
+    pub entradas_indice: usize,
+    pub fator_esparsidade: usize,
+    // Entrada usada e as vizinhas: (posição no índice, chave, posição no arquivo)
+    pub entrada: Option<(usize, i64, u64)>,
+    pub vizinhas: Vec<(usize, i64, u64)>,
+    // Trecho do principal (em bytes) percorrido a partir da entrada
+    pub intervalo: Option<(u64, u64)>,
+    // (chave, posição) de cada registro lido no principal e no overflow. None
+    // no overflow quando ele não foi consultado (inexistente, vazio ou não pedido).
+    pub lidos_principal: Vec<(i64, u64)>,
+    pub lidos_overflow: Option<Vec<(i64, u64)>>,
+    pub encontrado: Option<R>,
+}
+
+// Mesma busca de consultar_com_indice (e, com caminho_overflow, a sequencial no
+// overflow), guardando cada passo
+pub fn rastrear_consulta<R: Registro>(
+    caminho: &str,
+    caminho_overflow: Option<&str>,
+    indice: &IndiceParcial,
+    chave: i64,
+) -> Resultado<RastroConsulta<R>> {
+    let mut rastro = RastroConsulta {
+        chave,
+        entradas_indice: indice.entradas.len(),
+        fator_esparsidade: indice.fator_esparsidade,
+        entrada: None,
+        vizinhas: Vec::new(),
+        intervalo: None,
+        lidos_principal: Vec::new(),
+        lidos_overflow: None,
+        encontrado: None,
+    };
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    if let Some((idx, posicao_inicial)) = indice.buscar_posicao(chave) {
+        let trio = |i: usize| (i, indice.entradas[i].chave, indice.entradas[i].posicao);
+        rastro.entrada = (idx < indice.entradas.len()).then(|| trio(idx));
+        rastro.vizinhas = (idx.saturating_sub(2)..(idx + 3).min(indice.entradas.len())).map(trio).collect();
+        let (mut arquivo, _, mut textos) = abrir::<R>(caminho)?;
+        let posicao_final = match indice.entradas.get(idx + 1) {
+            Some(seguinte) => seguinte.posicao,
+            None => arquivo.metadata()?.len(),
+        };
+        rastro.intervalo = Some((posicao_inicial, posicao_final));
+        arquivo.seek(SeekFrom::Start(posicao_inicial))?;
+        let mut posicao = posicao_inicial;
+        while posicao < posicao_final && arquivo.read_exact(&mut buffer).is_ok() {
+            let registro = R::ler_em(&buffer, posicao, &mut textos)?;
+            rastro.lidos_principal.push((registro.chave(), posicao));
+            if registro.chave() == chave {
+                rastro.encontrado = Some(registro);
+                return Ok(rastro);
+            }
+            if registro.chave() > chave {
+                break;
+            }
+            posicao += R::TAMANHO_REGISTRO as u64;
+        }
+    }
+    let Some(caminho_overflow) = caminho_overflow else {
+        return Ok(rastro);
+    };
+    if std::fs::metadata(caminho_overflow).map(|m| m.len() == 0).unwrap_or(true) {
+        return Ok(rastro);
+    }
+    let (arquivo, num_registros, mut textos) = abrir::<R>(caminho_overflow)?;
+    let mut leitor = std::io::BufReader::new(arquivo);
+    let mut lidos = Vec::new();
+    for i in 0..num_registros {
+        leitor.read_exact(&mut buffer)?;
+        let registro = R::ler_em(&buffer, posicao_registro::<R>(i), &mut textos)?;
+        lidos.push((registro.chave(), posicao_registro::<R>(i)));
+        if registro.chave() == chave {
+            rastro.encontrado = Some(registro);
+            break;
+        }
+    }
+    rastro.lidos_overflow = Some(lidos);
+    Ok(rastro)
+}
+
+pub fn busca_sequencial<R: Registro>(caminho: &str, chave: i64) -> Resultado<Option<R>> {
+    if std::fs::metadata(caminho)?.len() == 0 {
+        return Ok(None);
+    }
+    let (arquivo, num_registros, mut textos) = abrir::<R>(caminho)?;
+    let mut leitor = std::io::BufReader::new(arquivo);
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    for i in 0..num_registros {
+        leitor.read_exact(&mut buffer)?;
+        if R::chave_de_bytes(&buffer) == chave {
+            return Ok(Some(R::ler_em(&buffer, posicao_registro::<R>(i), &mut textos)?));
+        }
+    }
+    Ok(None)
+}
+
+// Lê e confere o registro que começa na posição (em bytes) do arquivo
+pub fn ler_registro_em<R: Registro>(caminho: &str, posicao: u64) -> Resultado<R> {
+    let buffer = ler_bytes_em::<R>(caminho, posicao)?;
+    let mut textos = textos_do_arquivo(caminho, R::LAYOUT, FormatoTextos::Fixos)?;
+    R::ler_em(&buffer, posicao, &mut textos)
+}
+
+// Bytes do registro na posição, sem decodificar
+fn ler_bytes_em<R: Registro>(caminho: &str, posicao: u64) -> Resultado<Vec<u8>> {
+    let mut arquivo = File::open(caminho)?;
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    arquivo.seek(SeekFrom::Start(posicao))?;
+    arquivo.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+// Posição (em bytes, desde o início do arquivo) do registro com a chave
+pub fn localizar_sequencial<R: Registro>(caminho: &str, chave: i64) -> Resultado<Option<u64>> {
+    if std::fs::metadata(caminho)?.len() == 0 {
+        return Ok(None);
+    }
+    let (arquivo, num_registros, _) = abrir::<R>(caminho)?;
+    let mut leitor = std::io::BufReader::new(arquivo);
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    for i in 0..num_registros {
+        leitor.read_exact(&mut buffer)?;
+        if R::chave_de_bytes(&buffer) == chave {
+            return Ok(Some(posicao_registro::<R>(i)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+
+    fn produto(product_id: i64, centavos: i64) -> Produto {
+        Produto { product_id, price: Preco::em_centavos(centavos), material: "gold".to_string(), ..Produto::default() }
+    }
+
+    // Principal com as chaves pares de 0 a 98 e overflow com 1 e 3
+    fn preparar(nome: &str) -> (ArquivoSequencial<Produto>, IndiceParcial) {
+        let dir = std::env::temp_dir().join(format!("aed2_arquivo_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("produtos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("produtos_overflow.dat").to_string_lossy().to_string();
+        let mut produtos: Vec<Produto> = (0..50).map(|i| produto(i * 2, 100)).collect();
+        gravar_ordenados(&mut produtos, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 10, Produto::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow);
+        for chave in [1, 3] {
+            assert_eq!(arquivo.inserir(&produto(chave, 100), &mut indice).unwrap(), Insercao::Inserido);
+        }
+        (arquivo, indice)
+    }
+
+    fn tamanhos(arquivo: &ArquivoSequencial<Produto>) -> (u64, u64) {
+        let tamanho = |caminho: &str| std::fs::metadata(caminho).unwrap().len();
+        (tamanho(&arquivo.caminho_principal), tamanho(&arquivo.caminho_overflow))
+    }
+
+    // A atualização regrava o registro onde ele está, no principal ou no
+    // overflow, sem acrescentar nada; mudar a chave é recusado
+    #[test]
+    fn atualizacao_no_principal_e_no_overflow() {
+        let (arquivo, indice) = preparar("atualizacao");
+        let antes = tamanhos(&arquivo);
+        for (chave, alvo) in [(42, ArquivoAlvo::Principal), (3, ArquivoAlvo::Overflow)] {
+            let (_, posicao, _) = arquivo.localizar(&indice, chave).unwrap().unwrap();
+            let atualizado = arquivo
+                .atualizar(&indice, chave, |p| {
+                    p.price = Preco::em_centavos(999);
+                    p.stone = "ruby".to_string();
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!((atualizado.product_id, atualizado.price), (chave, Preco::em_centavos(999)));
+            let (alvo_depois, posicao_depois, lido) = arquivo.localizar(&indice, chave).unwrap().unwrap();
+            assert_eq!((alvo_depois, posicao_depois), (alvo, posicao));
+            assert_eq!((lido.price, lido.stone.as_str(), lido.material.as_str()), (Preco::em_centavos(999), "ruby", "gold"));
+        }
+        assert_eq!(tamanhos(&arquivo), antes);
+        assert_eq!(arquivo.consultar(&indice, 44).unwrap().unwrap().price, Preco::em_centavos(100));
+
+        assert!(matches!(arquivo.atualizar(&indice, 5, |_| Ok(())), Err(Erro::ChaveNaoEncontrada(5))));
+        assert!(matches!(
+            arquivo.atualizar(&indice, 42, |p| {
+                p.product_id = 43;
+                Ok(())
+            }),
+            Err(Erro::AlteracaoInvalida(_))
+        ));
+        assert!(arquivo.consultar(&indice, 43).unwrap().is_none());
+    }
+
+    // Com Rejeitar nada é gravado; com Substituir o registro existente é
+    // regravado na mesma posição, esteja ele no principal ou no overflow
+    #[test]
+    fn insercao_de_chave_repetida() {
+        let (arquivo, mut indice) = preparar("duplicatas");
+        let antes = tamanhos(&arquivo);
+        let arquivo = arquivo.com_politica_duplicatas(PoliticaDuplicatas::Rejeitar);
+        for chave in [42, 3] {
+            assert!(matches!(arquivo.inserir(&produto(chave, 555), &mut indice), Err(Erro::ChaveDuplicada(c)) if c == chave));
+            assert_eq!(arquivo.consultar(&indice, chave).unwrap().unwrap().price, Preco::em_centavos(100));
+        }
+        assert_eq!(tamanhos(&arquivo), antes);
+
+        let arquivo = arquivo.com_politica_duplicatas(PoliticaDuplicatas::Substituir);
+        for (chave, alvo) in [(42, ArquivoAlvo::Principal), (3, ArquivoAlvo::Overflow)] {
+            let (_, posicao, _) = arquivo.localizar(&indice, chave).unwrap().unwrap();
+            assert_eq!(arquivo.inserir(&produto(chave, 555), &mut indice).unwrap(), Insercao::Substituido);
+            let (alvo_depois, posicao_depois, lido) = arquivo.localizar(&indice, chave).unwrap().unwrap();
+            assert_eq!((alvo_depois, posicao_depois), (alvo, posicao));
+            assert_eq!(lido.price, Preco::em_centavos(555));
+        }
+        assert_eq!(tamanhos(&arquivo), antes);
+        assert_eq!(arquivo.num_registros_overflow().unwrap(), 2);
+    }
+
+    // Hash ou árvore desatualizados (aqui, uma cópia de antes da inserção posta
+    // de volta) não escondem registros que o índice parcial e o overflow acham
+    #[test]
+    fn falta_no_hash_ou_na_arvore_volta_ao_indice_parcial() {
+        for (nome, hash) in [("falta_hash", true), ("falta_arvore", false)] {
+            let (arquivo, mut indice) = preparar(nome);
+            let arquivo = if hash { arquivo.com_hash(4) } else { arquivo.com_arvore_b(4) };
+            let caminho = if hash {
+                arquivo.construir_hash(4).unwrap();
+                arquivo.caminho_hash()
+            } else {
+                arquivo.construir_arvore(4).unwrap();
+                arquivo.caminho_arvore()
+            };
+            let copia = format!("{}.copia", caminho);
+            std::fs::copy(&caminho, &copia).unwrap();
+            arquivo.inserir(&produto(5, 100), &mut indice).unwrap();
+            arquivo.atualizar(&indice, 42, |p| {
+                p.price = Preco::em_centavos(7);
+                Ok(())
+            }).unwrap();
+            std::fs::rename(&copia, &caminho).unwrap();
+
+            assert_eq!(arquivo.consultar(&indice, 5).unwrap().unwrap().product_id, 5);
+            assert_eq!(arquivo.consultar(&indice, 42).unwrap().unwrap().price, Preco::em_centavos(7));
+            assert!(matches!(arquivo.localizar(&indice, 5).unwrap(), Some((ArquivoAlvo::Overflow, _, _))));
+            assert!(arquivo.consultar(&indice, 7).unwrap().is_none());
+        }
+    }
+
+    // A remoção acha o registro pelo índice parcial (principal) ou no overflow;
+    // sem índice, o principal é percorrido do início
+    #[test]
+    fn remocao_pelo_indice_e_sem_indice() {
+        let (arquivo, indice) = preparar("remocao");
+        for chave in [42, 3] {
+            arquivo.remover(&indice, chave).unwrap();
+            assert!(arquivo.consultar(&indice, chave).unwrap().is_none());
+            assert!(matches!(arquivo.remover(&indice, chave), Err(Erro::ChaveNaoEncontrada(c)) if c == chave));
+        }
+        assert!(matches!(arquivo.remover(&indice, 43), Err(Erro::ChaveNaoEncontrada(43))));
+
+        let sem_indice = IndiceParcial::novo(10, Produto::LAYOUT);
+        arquivo.remover(&sem_indice, 88).unwrap();
+        assert!(arquivo.consultar(&indice, 88).unwrap().is_none());
+        arquivo.remover(&sem_indice, 1).unwrap();
+        assert!(arquivo.buscar(1).unwrap().is_none());
+        assert_eq!(arquivo.consultar(&indice, 44).unwrap().unwrap().price, Preco::em_centavos(100));
+    }
+
+    // Sem o principal (entidade nova), a inserção não falha depois de já ter
+    // gravado no overflow: o principal conta como vazio e a reconstrução o cria
+    #[test]
+    fn insercao_sem_arquivo_principal() {
+        let dir = std::env::temp_dir().join(format!("aed2_arquivo_sem_principal_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("produtos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("produtos_overflow.dat").to_string_lossy().to_string();
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow);
+        let mut indice = IndiceParcial::novo(2, Produto::LAYOUT);
+        assert_eq!(arquivo.inserir(&produto(7, 100), &mut indice).unwrap(), Insercao::Reconstruido);
+        assert_eq!(arquivo.num_registros_overflow().unwrap(), 0);
+        assert_eq!(ler_validos::<Produto>(&principal).unwrap().len(), 1);
+        assert_eq!(arquivo.consultar(&indice, 7).unwrap().unwrap().product_id, 7);
+    }
+}
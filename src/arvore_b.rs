@@ -0,0 +1,585 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::arquivo::{percorrer_brutos, ArquivoSequencial};
+use crate::cabecalho::{Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::manifesto::caminho_temporario;
+use crate::registro::Registro;
+use crate::textos::{textos_do_arquivo, FormatoTextos};
+use crate::wal::ArquivoAlvo;
+
+// Índice em árvore B+ gravado em disco (produtos.dat.arvore), alternativa ao
+// índice parcial: cobre o principal e o overflow, é lido página a página (não
+// precisa caber em memória) e aceita inserções e remoções sem reconstrução.
+//
+// Layout: os primeiros TAMANHO_META bytes têm o cabeçalho comum (ordem no
+// campo do fator de esparsidade, número de chaves em num_registros) seguido de
+// [raiz u64][num_paginas u64][suja u8]. Depois vêm as páginas, numeradas a
+// partir de 1, todas com tamanho_pagina(ordem) bytes:
+//
+//   [folha u8][n u16][5 livres][próxima folha u64][n chaves i64]...
+//   folha:   depois das chaves, n ponteiros [arquivo u8][posição u64]
+//   interna: depois das chaves, n + 1 páginas filhas u64
+//
+// Cada nó guarda no máximo `ordem` chaves. A remoção é preguiçosa: a chave sai
+// da folha, mas folhas com poucas chaves (ou vazias) não são juntadas às
+// vizinhas; a próxima reconstrução refaz a árvore compacta.
+//
+// A árvore não passa pelo WAL. Antes de mexer nas páginas ela é marcada como
+// suja (e sincronizada); só volta a limpa depois que as páginas estão no disco.
+// Uma árvore suja depois de uma queda é refeita a partir dos dados pelo
+// `recuperar`. Quem consulta confere a chave do registro apontado, então um
+// ponteiro que ficou para trás nunca devolve o registro errado.
+const TAMANHO_META: u64 = 128;
+const TAMANHO_PONTEIRO: usize = 9;
+pub const ORDEM_PADRAO: usize = 64;
+pub const ORDEM_MINIMA: usize = 4;
+pub const ORDEM_MAXIMA: usize = 1024;
+
+// Onde está o registro de uma chave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ponteiro {
+    pub alvo: ArquivoAlvo,
+    pub posicao: u64,
+}
+
+#[derive(Debug, Clone)]
+struct No {
+    folha: bool,
+    chaves: Vec<i64>,
+    // Só nas folhas
+    ponteiros: Vec<Ponteiro>,
+    // Só nos nós internos
+    filhos: Vec<u64>,
+    // Próxima folha na ordem das chaves (0 = nenhuma)
+    proxima: u64,
+}
+
+impl No {
+    fn folha() -> Self {
+        No { folha: true, chaves: Vec::new(), ponteiros: Vec::new(), filhos: Vec::new(), proxima: 0 }
+    }
+
+    fn interno(chaves: Vec<i64>, filhos: Vec<u64>) -> Self {
+        No { folha: false, chaves, ponteiros: Vec::new(), filhos, proxima: 0 }
+    }
+
+    // Filho que pode conter a chave: o primeiro depois das chaves <= chave
+    fn filho_para(&self, chave: i64) -> usize {
+        self.chaves.partition_point(|&c| c <= chave)
+    }
+}
+
+pub fn tamanho_pagina(ordem: usize) -> usize {
+    16 + 8 * ordem + (TAMANHO_PONTEIRO * ordem).max(8 * (ordem + 1))
+}
+
+pub fn caminho_arvore(caminho_dados: &str) -> String {
+    format!("{}.arvore", caminho_dados)
+}
+
+#[derive(Debug)]
+pub struct ArvoreB {
+    arquivo: File,
+    layout: LayoutRegistro,
+    pub ordem: usize,
+    raiz: u64,
+    num_paginas: u64,
+    pub num_chaves: u64,
+    suja: bool,
+}
+
+impl ArvoreB {
+    // Árvore vazia (uma folha sem chaves como raiz)
+    pub fn criar(caminho: &str, layout: LayoutRegistro, ordem: usize) -> Resultado<Self> {
+        validar_ordem(ordem)?;
+        let arquivo = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(caminho)?;
+        let mut arvore = ArvoreB { arquivo, layout, ordem, raiz: 1, num_paginas: 1, num_chaves: 0, suja: false };
+        arvore.gravar_no(1, &No::folha())?;
+        arvore.gravar_meta()?;
+        arvore.arquivo.sync_all()?;
+        Ok(arvore)
+    }
+
+    pub fn abrir(caminho: &str, layout: LayoutRegistro) -> Resultado<Self> {
+        let mut arquivo = OpenOptions::new().read(true).write(true).open(caminho)?;
+        let cabecalho = Cabecalho::ler(&mut arquivo)?;
+        cabecalho.validar(TipoArquivo::ArvoreB, layout)?;
+        let ordem = cabecalho.fator_esparsidade as usize;
+        validar_ordem(ordem)?;
+        let mut meta = [0u8; 17];
+        arquivo.seek(SeekFrom::Start(TAMANHO_CABECALHO as u64))?;
+        arquivo.read_exact(&mut meta)?;
+        let raiz = u64::from_le_bytes(meta[0..8].try_into().unwrap());
+        let num_paginas = u64::from_le_bytes(meta[8..16].try_into().unwrap());
+        let arvore = ArvoreB { arquivo, layout, ordem, raiz, num_paginas, num_chaves: cabecalho.num_registros, suja: meta[16] != 0 };
+        if arvore.suja {
+            return Err(Erro::IndiceInconsistente(format!(
+                "{} foi interrompida no meio de uma alteração; reconstrua o índice",
+                caminho
+            )));
+        }
+        let esperado = TAMANHO_META + num_paginas * tamanho_pagina(ordem) as u64;
+        if raiz == 0 || raiz > num_paginas || arvore.arquivo.metadata()?.len() < esperado {
+            return Err(Erro::IndiceInconsistente(format!("{}: raiz ou número de páginas inválido", caminho)));
+        }
+        Ok(arvore)
+    }
+
+    pub fn buscar(&mut self, chave: i64) -> Resultado<Option<Ponteiro>> {
+        let no = self.folha_para(chave)?;
+        Ok(no.chaves.binary_search(&chave).ok().map(|i| no.ponteiros[i]))
+    }
+
+    // Insere ou, se a chave já existir, troca o ponteiro
+    pub fn inserir(&mut self, chave: i64, ponteiro: Ponteiro) -> Resultado<()> {
+        self.marcar_suja()?;
+        if let Some((separador, nova)) = self.inserir_em(self.raiz, chave, ponteiro)? {
+            let raiz = self.nova_pagina();
+            self.gravar_no(raiz, &No::interno(vec![separador], vec![self.raiz, nova]))?;
+            self.raiz = raiz;
+        }
+        self.marcar_limpa()
+    }
+
+    // Remove a chave da folha; false se ela não estava na árvore
+    pub fn remover(&mut self, chave: i64) -> Resultado<bool> {
+        let mut pagina = self.raiz;
+        let mut no = self.ler_no(pagina)?;
+        while !no.folha {
+            pagina = no.filhos[no.filho_para(chave)];
+            no = self.ler_no(pagina)?;
+        }
+        let Ok(i) = no.chaves.binary_search(&chave) else {
+            return Ok(false);
+        };
+        self.marcar_suja()?;
+        no.chaves.remove(i);
+        no.ponteiros.remove(i);
+        self.gravar_no(pagina, &no)?;
+        self.num_chaves -= 1;
+        self.marcar_limpa()?;
+        Ok(true)
+    }
+
+    // Todas as entradas, em ordem de chave, seguindo o encadeamento das folhas
+    pub fn entradas(&mut self) -> Resultado<Vec<(i64, Ponteiro)>> {
+        let mut no = self.folha_para(i64::MIN)?;
+        let mut entradas = Vec::with_capacity(self.num_chaves as usize);
+        loop {
+            entradas.extend(no.chaves.iter().copied().zip(no.ponteiros.iter().copied()));
+            if no.proxima == 0 {
+                return Ok(entradas);
+            }
+            no = self.ler_no(no.proxima)?;
+        }
+    }
+
+    // Número de níveis (1 = só a raiz folha)
+    pub fn altura(&mut self) -> Resultado<usize> {
+        let mut altura = 1;
+        let mut no = self.ler_no(self.raiz)?;
+        while !no.folha {
+            no = self.ler_no(no.filhos[0])?;
+            altura += 1;
+        }
+        Ok(altura)
+    }
+
+    pub fn num_paginas(&self) -> u64 {
+        self.num_paginas
+    }
+
+    fn folha_para(&mut self, chave: i64) -> Resultado<No> {
+        let mut no = self.ler_no(self.raiz)?;
+        while !no.folha {
+            no = self.ler_no(no.filhos[no.filho_para(chave)])?;
+        }
+        Ok(no)
+    }
+
+    // Insere na subárvore; se a página se dividir, devolve a primeira chave e o
+    // número da página nova, que o pai precisa acrescentar
+    fn inserir_em(&mut self, pagina: u64, chave: i64, ponteiro: Ponteiro) -> Resultado<Option<(i64, u64)>> {
+        let mut no = self.ler_no(pagina)?;
+        if no.folha {
+            match no.chaves.binary_search(&chave) {
+                Ok(i) => {
+                    no.ponteiros[i] = ponteiro;
+                    self.gravar_no(pagina, &no)?;
+                    return Ok(None);
+                }
+                Err(i) => {
+                    no.chaves.insert(i, chave);
+                    no.ponteiros.insert(i, ponteiro);
+                    self.num_chaves += 1;
+                }
+            }
+            if no.chaves.len() <= self.ordem {
+                self.gravar_no(pagina, &no)?;
+                return Ok(None);
+            }
+            let meio = no.chaves.len() / 2;
+            let nova = self.nova_pagina();
+            let direita = No {
+                folha: true,
+                chaves: no.chaves.split_off(meio),
+                ponteiros: no.ponteiros.split_off(meio),
+                filhos: Vec::new(),
+                proxima: no.proxima,
+            };
+            no.proxima = nova;
+            // A direita primeiro: a esquerda só passa a apontar para ela depois
+            self.gravar_no(nova, &direita)?;
+            self.gravar_no(pagina, &no)?;
+            return Ok(Some((direita.chaves[0], nova)));
+        }
+
+        let i = no.filho_para(chave);
+        let Some((separador, filho_novo)) = self.inserir_em(no.filhos[i], chave, ponteiro)? else {
+            return Ok(None);
+        };
+        no.chaves.insert(i, separador);
+        no.filhos.insert(i + 1, filho_novo);
+        if no.chaves.len() <= self.ordem {
+            self.gravar_no(pagina, &no)?;
+            return Ok(None);
+        }
+        // A chave do meio sobe para o pai e não fica em nenhuma das metades
+        let meio = no.chaves.len() / 2;
+        let chaves_direita = no.chaves.split_off(meio + 1);
+        let sobe = no.chaves.pop().unwrap();
+        let filhos_direita = no.filhos.split_off(meio + 1);
+        let nova = self.nova_pagina();
+        self.gravar_no(nova, &No::interno(chaves_direita, filhos_direita))?;
+        self.gravar_no(pagina, &no)?;
+        Ok(Some((sobe, nova)))
+    }
+
+    fn nova_pagina(&mut self) -> u64 {
+        self.num_paginas += 1;
+        self.num_paginas
+    }
+
+    fn posicao_pagina(&self, pagina: u64) -> u64 {
+        TAMANHO_META + (pagina - 1) * tamanho_pagina(self.ordem) as u64
+    }
+
+    fn ler_no(&mut self, pagina: u64) -> Resultado<No> {
+        if pagina == 0 || pagina > self.num_paginas {
+            return Err(Erro::IndiceInconsistente(format!("página {} da árvore não existe", pagina)));
+        }
+        let mut bytes = vec![0u8; tamanho_pagina(self.ordem)];
+        self.arquivo.seek(SeekFrom::Start(self.posicao_pagina(pagina)))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        let folha = bytes[0] == 1;
+        let n = u16::from_le_bytes(bytes[1..3].try_into().unwrap()) as usize;
+        if n > self.ordem {
+            return Err(Erro::IndiceInconsistente(format!("página {} da árvore com {} chaves", pagina, n)));
+        }
+        let proxima = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let ler_u64 = |pos: usize| u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+        let chaves: Vec<i64> = (0..n).map(|i| ler_u64(16 + 8 * i) as i64).collect();
+        let inicio = 16 + 8 * self.ordem;
+        let mut no = No { folha, chaves, ponteiros: Vec::new(), filhos: Vec::new(), proxima };
+        if folha {
+            for i in 0..n {
+                let pos = inicio + TAMANHO_PONTEIRO * i;
+                let alvo = if bytes[pos] == ArquivoAlvo::Overflow as u8 { ArquivoAlvo::Overflow } else { ArquivoAlvo::Principal };
+                no.ponteiros.push(Ponteiro { alvo, posicao: ler_u64(pos + 1) });
+            }
+        } else {
+            no.filhos = (0..=n).map(|i| ler_u64(inicio + 8 * i)).collect();
+        }
+        Ok(no)
+    }
+
+    fn gravar_no(&mut self, pagina: u64, no: &No) -> Resultado<()> {
+        let mut bytes = vec![0u8; tamanho_pagina(self.ordem)];
+        bytes[0] = if no.folha { 1 } else { 2 };
+        bytes[1..3].copy_from_slice(&(no.chaves.len() as u16).to_le_bytes());
+        bytes[8..16].copy_from_slice(&no.proxima.to_le_bytes());
+        for (i, chave) in no.chaves.iter().enumerate() {
+            bytes[16 + 8 * i..24 + 8 * i].copy_from_slice(&chave.to_le_bytes());
+        }
+        let inicio = 16 + 8 * self.ordem;
+        if no.folha {
+            for (i, ponteiro) in no.ponteiros.iter().enumerate() {
+                let pos = inicio + TAMANHO_PONTEIRO * i;
+                bytes[pos] = ponteiro.alvo as u8;
+                bytes[pos + 1..pos + 9].copy_from_slice(&ponteiro.posicao.to_le_bytes());
+            }
+        } else {
+            for (i, filho) in no.filhos.iter().enumerate() {
+                bytes[inicio + 8 * i..inicio + 8 * i + 8].copy_from_slice(&filho.to_le_bytes());
+            }
+        }
+        self.arquivo.seek(SeekFrom::Start(self.posicao_pagina(pagina)))?;
+        self.arquivo.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn gravar_meta(&mut self) -> Resultado<()> {
+        let mut bytes = vec![0u8; TAMANHO_META as usize];
+        let cabecalho = Cabecalho::novo(TipoArquivo::ArvoreB, self.layout, self.ordem, self.num_chaves);
+        bytes[..TAMANHO_CABECALHO].copy_from_slice(&cabecalho.to_bytes());
+        bytes[64..72].copy_from_slice(&self.raiz.to_le_bytes());
+        bytes[72..80].copy_from_slice(&self.num_paginas.to_le_bytes());
+        bytes[80] = self.suja as u8;
+        self.arquivo.seek(SeekFrom::Start(0))?;
+        self.arquivo.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn marcar_suja(&mut self) -> Resultado<()> {
+        self.suja = true;
+        self.gravar_meta()?;
+        self.arquivo.sync_data()?;
+        Ok(())
+    }
+
+    // As páginas vão para o disco antes da marca de limpa
+    fn marcar_limpa(&mut self) -> Resultado<()> {
+        self.arquivo.sync_data()?;
+        self.suja = false;
+        self.gravar_meta()
+    }
+}
+
+fn validar_ordem(ordem: usize) -> Resultado<()> {
+    if !(ORDEM_MINIMA..=ORDEM_MAXIMA).contains(&ordem) {
+        return Err(Erro::Configuracao(format!(
+            "ordem da árvore B+ deve estar entre {} e {} (recebido {})",
+            ORDEM_MINIMA, ORDEM_MAXIMA, ordem
+        )));
+    }
+    Ok(())
+}
+
+// Grava a árvore de uma vez a partir das entradas já ordenadas por chave (sem
+// repetidas): folhas cheias da esquerda para a direita, depois cada nível de
+// cima, até sobrar uma página
+fn carregar_ordenadas(caminho: &str, layout: LayoutRegistro, ordem: usize, entradas: &[(i64, Ponteiro)]) -> Resultado<ArvoreB> {
+    let mut arvore = ArvoreB::criar(caminho, layout, ordem)?;
+    if entradas.is_empty() {
+        return Ok(arvore);
+    }
+    arvore.num_paginas = 0;
+    // (primeira chave da subárvore, página)
+    let mut nivel: Vec<(i64, u64)> = Vec::new();
+    let grupos = repartir(entradas.len(), ordem);
+    let primeira = arvore.num_paginas + 1;
+    let mut inicio = 0;
+    for (i, tamanho) in grupos.iter().enumerate() {
+        let pagina = arvore.nova_pagina();
+        let fatia = &entradas[inicio..inicio + tamanho];
+        let no = No {
+            folha: true,
+            chaves: fatia.iter().map(|(c, _)| *c).collect(),
+            ponteiros: fatia.iter().map(|(_, p)| *p).collect(),
+            filhos: Vec::new(),
+            proxima: if i + 1 < grupos.len() { primeira + i as u64 + 1 } else { 0 },
+        };
+        arvore.gravar_no(pagina, &no)?;
+        nivel.push((fatia[0].0, pagina));
+        inicio += tamanho;
+    }
+    while nivel.len() > 1 {
+        let mut acima = Vec::new();
+        let mut inicio = 0;
+        for tamanho in repartir(nivel.len(), ordem + 1) {
+            let filhos = &nivel[inicio..inicio + tamanho];
+            let pagina = arvore.nova_pagina();
+            let no = No::interno(filhos[1..].iter().map(|(c, _)| *c).collect(), filhos.iter().map(|(_, p)| *p).collect());
+            arvore.gravar_no(pagina, &no)?;
+            acima.push((filhos[0].0, pagina));
+            inicio += tamanho;
+        }
+        nivel = acima;
+    }
+    arvore.raiz = nivel[0].1;
+    arvore.num_chaves = entradas.len() as u64;
+    arvore.gravar_meta()?;
+    arvore.arquivo.sync_all()?;
+    Ok(arvore)
+}
+
+// Divide n itens em grupos de no máximo `maximo`, com tamanhos parecidos (um
+// nó interno nunca fica com um filho só)
+fn repartir(n: usize, maximo: usize) -> Vec<usize> {
+    let grupos = n.div_ceil(maximo);
+    (0..grupos).map(|i| n / grupos + usize::from(i < n % grupos)).collect()
+}
+
+// Árvore com todos os registros válidos do principal e do overflow
+pub fn construir_arvore<R: Registro>(caminho_principal: &str, caminho_overflow: &str, destino: &str, ordem: usize) -> Resultado<ArvoreB> {
+    validar_ordem(ordem)?;
+    let entradas = ponteiros_validos::<R>(caminho_principal, caminho_overflow)?;
+    carregar_ordenadas(destino, R::LAYOUT, ordem, &entradas)
+}
+
+// Chave e posição de cada registro válido do principal e do overflow, em ordem
+// de chave. Com chaves repetidas vale a do principal, como na busca sem índice.
+pub(crate) fn ponteiros_validos<R: Registro>(caminho_principal: &str, caminho_overflow: &str) -> Resultado<Vec<(i64, Ponteiro)>> {
+    let mut entradas: Vec<(i64, Ponteiro)> = Vec::new();
+    for (caminho, alvo) in [(caminho_principal, ArquivoAlvo::Principal), (caminho_overflow, ArquivoAlvo::Overflow)] {
+        if !Path::new(caminho).exists() || std::fs::metadata(caminho)?.len() == 0 {
+            continue;
+        }
+        // Os removidos também contam para a posição dos seguintes
+        let mut textos = textos_do_arquivo(caminho, R::LAYOUT, FormatoTextos::Fixos)?;
+        percorrer_brutos::<R>(caminho, |posicao, bytes| {
+            let registro = R::ler_em(bytes, posicao, &mut textos)?;
+            if !registro.removido() {
+                entradas.push((registro.chave(), Ponteiro { alvo, posicao }));
+            }
+            Ok(())
+        })?;
+    }
+    // Estável: entre chaves iguais a do principal (que veio antes) fica
+    entradas.sort_by_key(|(chave, _)| *chave);
+    entradas.dedup_by_key(|(chave, _)| *chave);
+    Ok(entradas)
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn caminho_arvore(&self) -> String {
+        caminho_arvore(&self.caminho_principal)
+    }
+
+    // Refaz a árvore B+ a partir dos dados (num temporário, trocado no fim)
+    pub fn construir_arvore(&self, ordem: usize) -> Resultado<ArvoreB> {
+        let caminho = self.caminho_arvore();
+        let temporario = caminho_temporario(&caminho);
+        construir_arvore::<R>(&self.caminho_principal, &self.caminho_overflow, &temporario, ordem)?;
+        std::fs::rename(&temporario, &caminho)?;
+        ArvoreB::abrir(&caminho, R::LAYOUT)
+    }
+
+    // Árvore da entidade, se existir. Toda escrita a mantém, mesmo quando as
+    // consultas estão configuradas para o índice parcial.
+    pub(crate) fn abrir_arvore(&self) -> Resultado<Option<ArvoreB>> {
+        let caminho = self.caminho_arvore();
+        if !Path::new(&caminho).exists() {
+            return Ok(None);
+        }
+        ArvoreB::abrir(&caminho, R::LAYOUT).map(Some)
+    }
+
+    pub(crate) fn localizar_na_arvore(&self, arvore: &mut ArvoreB, chave: i64) -> Resultado<Option<(ArquivoAlvo, u64, R)>> {
+        match arvore.buscar(chave)? {
+            Some(ponteiro) => self.registro_apontado(ponteiro, chave),
+            None => Ok(None),
+        }
+    }
+
+    // Registro para onde um índice (árvore ou hash) aponta, conferido: um
+    // ponteiro de uma inserção interrompida pode estar além do fim do arquivo
+    // ou num registro que depois foi removido
+    pub(crate) fn registro_apontado(&self, ponteiro: Ponteiro, chave: i64) -> Resultado<Option<(ArquivoAlvo, u64, R)>> {
+        let caminho = match ponteiro.alvo {
+            ArquivoAlvo::Principal => &self.caminho_principal,
+            ArquivoAlvo::Overflow => &self.caminho_overflow,
+        };
+        if !Path::new(caminho).exists() || std::fs::metadata(caminho)?.len() < ponteiro.posicao + R::TAMANHO_REGISTRO as u64 {
+            return Ok(None);
+        }
+        let registro = crate::arquivo::ler_registro_em::<R>(caminho, ponteiro.posicao)?;
+        if registro.chave() != chave {
+            return Ok(None);
+        }
+        Ok(Some((ponteiro.alvo, ponteiro.posicao, registro)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::indice::construir_indice_parcial;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+
+    // Inserções fora de ordem com várias divisões, remoções e a mesma árvore
+    // carregada de uma vez devem dar as mesmas entradas
+    #[test]
+    fn arvore_insere_remove_e_carrega_em_lote() {
+        let dir = std::env::temp_dir().join(format!("aed2_arvore_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let caminho = dir.join("a.arvore").to_string_lossy().to_string();
+        let ponteiro = |posicao| Ponteiro { alvo: ArquivoAlvo::Overflow, posicao };
+
+        let mut arvore = ArvoreB::criar(&caminho, Produto::LAYOUT, 4).unwrap();
+        let chaves: Vec<i64> = (0..500).map(|i| (i * 7919) % 1000 - 500).collect();
+        for &chave in &chaves {
+            arvore.inserir(chave, ponteiro(chave as u64)).unwrap();
+        }
+        arvore.inserir(chaves[10], ponteiro(1)).unwrap();
+        for &chave in chaves.iter().step_by(3) {
+            assert!(arvore.remover(chave).unwrap());
+        }
+        assert!(!arvore.remover(chaves[0]).unwrap());
+        assert!(arvore.altura().unwrap() > 3);
+
+        let mut arvore = ArvoreB::abrir(&caminho, Produto::LAYOUT).unwrap();
+        let mut esperadas: Vec<i64> = chaves.iter().enumerate().filter(|(i, _)| i % 3 != 0).map(|(_, c)| *c).collect();
+        esperadas.sort();
+        let entradas = arvore.entradas().unwrap();
+        assert_eq!(entradas.iter().map(|(c, _)| *c).collect::<Vec<_>>(), esperadas);
+        assert_eq!(arvore.num_chaves, esperadas.len() as u64);
+        assert_eq!(arvore.buscar(chaves[10]).unwrap(), Some(ponteiro(1)));
+        assert_eq!(arvore.buscar(chaves[3]).unwrap(), None);
+
+        let lote = dir.join("b.arvore").to_string_lossy().to_string();
+        let mut carregada = carregar_ordenadas(&lote, Produto::LAYOUT, 5, &entradas).unwrap();
+        assert_eq!(carregada.entradas().unwrap(), entradas);
+        for (chave, p) in &entradas {
+            assert_eq!(carregada.buscar(*chave).unwrap(), Some(*p));
+        }
+    }
+
+    fn produto(product_id: i64, centavos: i64) -> Produto {
+        Produto { product_id, price: Preco::em_centavos(centavos), ..Produto::default() }
+    }
+
+    // Com removidos no principal e no overflow, a árvore (mantida nas escritas
+    // ou refeita dos dados) deve apontar para o registro certo de cada chave
+    #[test]
+    fn consultas_pela_arvore_depois_de_remocoes() {
+        let dir = std::env::temp_dir().join(format!("aed2_arvore_consultas_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("produtos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("produtos_overflow.dat").to_string_lossy().to_string();
+        let mut produtos: Vec<Produto> = (0..100).map(|i| produto(i * 2, i * 2)).collect();
+        gravar_ordenados(&mut produtos, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 10, Produto::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow).com_arvore_b(4);
+        arquivo.construir_arvore(4).unwrap();
+
+        for chave in [1, 51, 101, 151] {
+            arquivo.inserir(&produto(chave, chave), &mut indice).unwrap();
+        }
+        let removidas = [0, 10, 12, 98, 51];
+        for chave in removidas {
+            arquivo.remover(&indice, chave).unwrap();
+        }
+
+        let conferir = |arquivo: &ArquivoSequencial<Produto>| {
+            for chave in 0..200 {
+                let esperado = (chave % 2 == 0 || [1, 101, 151].contains(&chave)) && !removidas.contains(&chave);
+                let encontrado = arquivo.consultar(&indice, chave).unwrap();
+                assert_eq!(encontrado.as_ref().map(|p| p.product_id), esperado.then_some(chave), "chave {}", chave);
+                if let Some(p) = encontrado {
+                    assert_eq!(p.price, Preco::em_centavos(chave));
+                }
+            }
+        };
+        conferir(&arquivo);
+        arquivo.construir_arvore(4).unwrap();
+        conferir(&arquivo);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
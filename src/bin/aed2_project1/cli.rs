@@ -0,0 +1,763 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use aed2_project1::textos::{RegrasTextos, Truncamento};
+use aed2_project1::arquivo::{caminho_quarentena, Insercao};
+use aed2_project1::arvore_b::{ORDEM_MAXIMA, ORDEM_MINIMA, ORDEM_PADRAO};
+use aed2_project1::indice_hash::{CAPACIDADE_MAXIMA, CAPACIDADE_MINIMA, CAPACIDADE_PADRAO};
+use aed2_project1::cabecalho::TipoRegistro;
+use aed2_project1::data_hora::DataHora;
+use aed2_project1::desempenho::comparar_indices;
+use aed2_project1::importacao::{self, MapeamentoColunas, RelatorioImportacao};
+use aed2_project1::indice::{construir_indice_parcial, IndiceParcial};
+use aed2_project1::indice_secundario::ValorSecundario;
+use aed2_project1::integridade::{self, pedidos_orfaos, ArquivosRelacionados};
+use aed2_project1::pedido::{importar_pedidos_csv, AlteracaoPedido, Pedido};
+use aed2_project1::preco::Preco;
+use aed2_project1::produto::{importar_produtos_csv, AlteracaoProduto, Produto};
+use aed2_project1::verificar::verificar;
+use aed2_project1::{ArquivoSequencial, Erro, Registro, Resultado};
+
+use aed2_project1::config::{CaminhosEntidade, Configuracao};
+
+use crate::menu;
+
+// Códigos de saída: encontrado/sucesso, não encontrado e erro
+pub const SUCESSO: i32 = 0;
+pub const NAO_ENCONTRADO: i32 = 1;
+pub const FALHA: i32 = 2;
+
+const USO: &str = "uso: aed2_project1 <comando> <produto|pedido> [argumentos] [opções]
+
+comandos:
+  import <entidade> [--mapeamento ARQ]   gera o arquivo ordenado a partir do CSV
+  list <entidade> [--limit N]            mostra os primeiros N registros (padrão 10)
+  get <entidade> <chave>                 busca um registro (via índice, se existir)
+  range <entidade> <min> <max>           registros com chave entre min e max, em ordem
+  find <entidade> <campo> <valor> [<até>]  registros com o valor no campo, ou com ele entre valor e até
+                                         (em ordem do campo; índice secundário, se existir)
+  insert <entidade> campo=valor ...      insere um registro na área de overflow
+  update <entidade> <chave> campo=valor ...  altera só os campos informados, no lugar
+  delete <entidade> <chave>              remove logicamente um registro
+  rebuild <entidade>                     reconstrói arquivo principal e índice
+  verify <entidade>                      confere CRC, ordem, duplicatas e índice (saída 2 se houver problema)
+  orphans pedido                         lista pedidos cujo product_id não existe (saída 2 se houver)
+  repair <entidade>                      move registros corrompidos para a quarentena e reconstrói
+  migrate <entidade>                     converte arquivos de formatos e layouts antigos para o atual
+  index build <entidade> [--fator N]     constrói o índice parcial e os da configuração (árvore, hash, secundários)
+  index show <entidade>                  mostra a estrutura do índice
+  bench <entidade> [--consultas N] [--ordem N] [--capacidade N]
+                                         compara consultas pelo índice parcial, pela árvore B+ e pelo hash
+
+opções de arquivos: --dados CAMINHO --overflow CAMINHO --indice CAMINHO
+opções globais: --config ARQUIVO.toml --dir DIRETORIO_DADOS --csv ARQUIVO.csv
+
+campos de produto: product_id category_alias price material stone
+campos de pedido:  order_id user_id event_time product_id price
+
+campos de busca (find): produto category_alias material stone; pedido user_id event_time product_id
+
+datas (event_time): \"AAAA-MM-DD HH:MM:SS+HH:MM\"; sem a hora vale 00:00:00, sem o fuso vale UTC
+preços (price): \"123.45\" ou \"123.45 BRL\"; sem a moeda vale USD
+
+integridade: insert pedido exige que o product_id exista; delete de produto com pedidos
+             segue remocao_produtos da configuração (restringir, cascata ou ignorar)
+
+saída: 0 = sucesso/encontrado, 1 = não encontrado, 2 = erro";
+
+#[derive(Debug)]
+enum ErroCli {
+    Uso(String),
+    Armazenamento(Erro),
+}
+
+impl From<Erro> for ErroCli {
+    fn from(e: Erro) -> Self {
+        ErroCli::Armazenamento(e)
+    }
+}
+
+struct Argumentos {
+    posicionais: Vec<String>,
+    opcoes: HashMap<String, String>,
+}
+
+impl Argumentos {
+    fn separar(args: &[String]) -> Result<Self, ErroCli> {
+        let mut posicionais = Vec::new();
+        let mut opcoes = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(nome) = arg.strip_prefix("--") {
+                let valor = iter
+                    .next()
+                    .ok_or_else(|| ErroCli::Uso(format!("opção --{} sem valor", nome)))?;
+                opcoes.insert(nome.to_string(), valor.clone());
+            } else {
+                posicionais.push(arg.clone());
+            }
+        }
+        Ok(Argumentos { posicionais, opcoes })
+    }
+
+    fn posicional(&self, i: usize, nome: &str) -> Result<&str, ErroCli> {
+        self.posicionais
+            .get(i)
+            .map(|s| s.as_str())
+            .ok_or_else(|| ErroCli::Uso(format!("falta o argumento <{}>", nome)))
+    }
+
+    fn opcao_numero<T: std::str::FromStr>(&self, nome: &str, padrao: T) -> Result<T, ErroCli> {
+        match self.opcoes.get(nome) {
+            Some(valor) => valor
+                .parse()
+                .map_err(|_| ErroCli::Uso(format!("valor inválido para --{}: {}", nome, valor))),
+            None => Ok(padrao),
+        }
+    }
+
+    // Caminhos da configuração, com --dados/--overflow/--indice por cima
+    fn caminhos(&self, config: &Configuracao, tipo: TipoRegistro) -> CaminhosEntidade {
+        let mut caminhos = config.caminhos(tipo);
+        if let Some(dados) = self.opcoes.get("dados") {
+            caminhos.dados = dados.clone();
+        }
+        if let Some(overflow) = self.opcoes.get("overflow") {
+            caminhos.overflow = overflow.clone();
+        }
+        if let Some(indice) = self.opcoes.get("indice") {
+            caminhos.indice = indice.clone();
+        }
+        caminhos
+    }
+}
+
+// O que a linha de comando precisa saber de cada entidade além do Registro
+trait RegistroCli: Registro {
+    fn importar(caminho_csv: &str, caminho: &str, mapeamento: &MapeamentoColunas, memoria: usize, regras: &RegrasTextos) -> Resultado<RelatorioImportacao>;
+    fn de_campos(campos: &HashMap<String, String>) -> Result<Self, ErroCli>;
+    fn atualizar(
+        arquivo: &ArquivoSequencial<Self>,
+        indice: &IndiceParcial,
+        chave: i64,
+        campos: &HashMap<String, String>,
+        regras: &RegrasTextos,
+    ) -> Result<(Self, Vec<Truncamento>), ErroCli>;
+
+    // Chaves estrangeiras do registro novo, conferidas antes da inserção
+    fn conferir_referencias(&self, _config: &Configuracao) -> Resultado<()> {
+        Ok(())
+    }
+
+    // Remoção com os efeitos nas outras entidades; false se a chave não existe
+    fn remover(arquivo: &ArquivoSequencial<Self>, indice: &IndiceParcial, chave: i64, _config: &Configuracao) -> Resultado<bool> {
+        match arquivo.remover(indice, chave) {
+            Ok(()) => Ok(true),
+            Err(Erro::ChaveNaoEncontrada(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// Textos cortados de campos com a política "avisar" (os de "truncar" passam em silêncio)
+fn avisar_truncamentos(truncamentos: &[Truncamento]) {
+    for truncamento in truncamentos.iter().filter(|t| t.avisar) {
+        eprintln!("aviso: {}", truncamento);
+    }
+}
+
+// Pares campo=valor dos argumentos posicionais
+fn ler_campos(pares: &[String]) -> Result<HashMap<String, String>, ErroCli> {
+    pares
+        .iter()
+        .map(|par| {
+            par.split_once('=')
+                .map(|(campo, valor)| (campo.to_string(), valor.to_string()))
+                .ok_or_else(|| ErroCli::Uso(format!("esperado campo=valor, recebido: {}", par)))
+        })
+        .collect()
+}
+
+// Na atualização um campo desconhecido (ou a chave) seria ignorado em silêncio
+fn exigir_campos_alteraveis(campos: &HashMap<String, String>, alteraveis: &[&str]) -> Result<(), ErroCli> {
+    if campos.is_empty() {
+        return Err(ErroCli::Uso("informe pelo menos um campo=valor para alterar".to_string()));
+    }
+    match campos.keys().find(|campo| !alteraveis.contains(&campo.as_str())) {
+        Some(campo) => Err(ErroCli::Uso(format!("campo não pode ser alterado: {} (alteráveis: {})", campo, alteraveis.join(" ")))),
+        None => Ok(()),
+    }
+}
+
+fn campo_opcional_numero<T: std::str::FromStr>(campos: &HashMap<String, String>, nome: &str) -> Result<Option<T>, ErroCli> {
+    campos
+        .get(nome)
+        .map(|valor| valor.parse().map_err(|_| ErroCli::Uso(format!("valor inválido para {}: {}", nome, valor))))
+        .transpose()
+}
+
+fn campo_numero<T: std::str::FromStr>(campos: &HashMap<String, String>, nome: &str, obrigatorio: bool, padrao: T) -> Result<T, ErroCli> {
+    match campos.get(nome) {
+        Some(valor) => valor
+            .parse()
+            .map_err(|_| ErroCli::Uso(format!("valor inválido para {}: {}", nome, valor))),
+        None if obrigatorio => Err(ErroCli::Uso(format!("campo obrigatório ausente: {}", nome))),
+        None => Ok(padrao),
+    }
+}
+
+// Preço sem o campo vale zero; com ele, segue as regras da importação
+fn campo_preco(campos: &HashMap<String, String>) -> Result<Preco, ErroCli> {
+    let Some(valor) = campos.get("price") else {
+        return Ok(Preco::default());
+    };
+    let preco = valor.parse().map_err(|e| ErroCli::Uso(format!("price: {}", e)))?;
+    importacao::validar_preco("price", &preco).map_err(ErroCli::Uso)?;
+    Ok(preco)
+}
+
+fn campo_texto(campos: &HashMap<String, String>, nome: &str) -> String {
+    campos.get(nome).cloned().unwrap_or_default()
+}
+
+impl RegistroCli for Produto {
+    fn importar(caminho_csv: &str, caminho: &str, mapeamento: &MapeamentoColunas, memoria: usize, regras: &RegrasTextos) -> Resultado<RelatorioImportacao> {
+        importar_produtos_csv(caminho_csv, caminho, mapeamento, memoria, regras)
+    }
+
+    fn de_campos(campos: &HashMap<String, String>) -> Result<Self, ErroCli> {
+        Ok(Produto {
+            product_id: campo_numero(campos, "product_id", true, 0)?,
+            category_alias: campo_texto(campos, "category_alias"),
+            price: campo_preco(campos)?,
+            material: campo_texto(campos, "material"),
+            stone: campo_texto(campos, "stone"),
+        })
+    }
+
+    fn atualizar(
+        arquivo: &ArquivoSequencial<Self>,
+        indice: &IndiceParcial,
+        chave: i64,
+        campos: &HashMap<String, String>,
+        regras: &RegrasTextos,
+    ) -> Result<(Self, Vec<Truncamento>), ErroCli> {
+        exigir_campos_alteraveis(campos, &["category_alias", "price", "material", "stone"])?;
+        let alteracao = AlteracaoProduto {
+            category_alias: campos.get("category_alias").cloned(),
+            price: campo_opcional_numero(campos, "price")?,
+            material: campos.get("material").cloned(),
+            stone: campos.get("stone").cloned(),
+        };
+        let mut truncamentos = Vec::new();
+        let produto = arquivo.atualizar(indice, chave, |produto| {
+            alteracao.aplicar(produto).map_err(Erro::AlteracaoInvalida)?;
+            truncamentos = regras.ajustar_registro(produto).map_err(Erro::AlteracaoInvalida)?;
+            Ok(())
+        })?;
+        Ok((produto, truncamentos))
+    }
+
+    // Os pedidos do produto seguem remocao_produtos (integridade.rs)
+    fn remover(arquivo: &ArquivoSequencial<Self>, indice: &IndiceParcial, chave: i64, config: &Configuracao) -> Resultado<bool> {
+        let caminhos_pedidos = config.caminhos(TipoRegistro::Pedido);
+        let indice_pedidos = carregar_ou_novo::<Pedido>(&caminhos_pedidos.indice, config.fator_esparsidade)?;
+        let pedidos = abrir_arquivo::<Pedido>(&caminhos_pedidos, config);
+        match integridade::remover_produto(arquivo, indice, &pedidos, &indice_pedidos, chave, config.remocao_produtos)? {
+            Some(remocao) => {
+                if !remocao.pedidos_removidos.is_empty() {
+                    println!("pedidos removidos junto: {}", juntar_chaves(&remocao.pedidos_removidos));
+                }
+                if !remocao.pedidos_orfaos.is_empty() {
+                    println!("pedidos que ficaram sem o produto: {}", juntar_chaves(&remocao.pedidos_orfaos));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl RegistroCli for Pedido {
+    fn importar(caminho_csv: &str, caminho: &str, mapeamento: &MapeamentoColunas, memoria: usize, regras: &RegrasTextos) -> Resultado<RelatorioImportacao> {
+        importar_pedidos_csv(caminho_csv, caminho, mapeamento, memoria, regras)
+    }
+
+    fn de_campos(campos: &HashMap<String, String>) -> Result<Self, ErroCli> {
+        Ok(Pedido {
+            order_id: campo_numero(campos, "order_id", true, 0)?,
+            user_id: campo_numero(campos, "user_id", false, 0)?,
+            event_time: campo_numero(campos, "event_time", true, DataHora::default())?,
+            product_id: campo_numero(campos, "product_id", true, 0)?,
+            price: campo_preco(campos)?,
+        })
+    }
+
+    fn atualizar(
+        arquivo: &ArquivoSequencial<Self>,
+        indice: &IndiceParcial,
+        chave: i64,
+        campos: &HashMap<String, String>,
+        regras: &RegrasTextos,
+    ) -> Result<(Self, Vec<Truncamento>), ErroCli> {
+        exigir_campos_alteraveis(campos, &["user_id", "event_time", "product_id", "price"])?;
+        let alteracao = AlteracaoPedido {
+            user_id: campo_opcional_numero(campos, "user_id")?,
+            event_time: campo_opcional_numero(campos, "event_time")?,
+            product_id: campo_opcional_numero(campos, "product_id")?,
+            price: campo_opcional_numero(campos, "price")?,
+        };
+        let mut truncamentos = Vec::new();
+        let pedido = arquivo.atualizar(indice, chave, |pedido| {
+            alteracao.aplicar(pedido).map_err(Erro::AlteracaoInvalida)?;
+            truncamentos = regras.ajustar_registro(pedido).map_err(Erro::AlteracaoInvalida)?;
+            Ok(())
+        })?;
+        Ok((pedido, truncamentos))
+    }
+
+    fn conferir_referencias(&self, config: &Configuracao) -> Resultado<()> {
+        let caminhos_produtos = config.caminhos(TipoRegistro::Produto);
+        let indice_produtos = carregar_ou_novo::<Produto>(&caminhos_produtos.indice, config.fator_esparsidade)?;
+        integridade::conferir_produto(&ArquivosRelacionados::novo(&caminhos_produtos, &indice_produtos), self)
+    }
+}
+
+pub fn executar(args: &[String], config: &Configuracao) -> i32 {
+    match executar_comando(args, config) {
+        Ok(codigo) => codigo,
+        Err(ErroCli::Uso(msg)) => {
+            eprintln!("{}\n\n{}", msg, USO);
+            FALHA
+        }
+        Err(ErroCli::Armazenamento(e)) => {
+            eprintln!("erro: {}", e);
+            FALHA
+        }
+    }
+}
+
+fn executar_comando(args: &[String], config: &Configuracao) -> Result<i32, ErroCli> {
+    let argumentos = Argumentos::separar(args)?;
+    let comando = argumentos.posicional(0, "comando")?;
+    if comando == "help" || comando == "ajuda" {
+        println!("{}", USO);
+        return Ok(SUCESSO);
+    }
+    // "index build"/"index show" têm um nível a mais antes da entidade
+    let pos_entidade = if comando == "index" { 2 } else { 1 };
+    let nome_entidade = argumentos.posicional(pos_entidade, "entidade")?;
+    let tipo = TipoRegistro::from_nome(nome_entidade)
+        .ok_or_else(|| ErroCli::Uso(format!("entidade desconhecida: {}", nome_entidade)))?;
+    match tipo {
+        TipoRegistro::Produto => executar_para::<Produto>(&argumentos, config, tipo),
+        TipoRegistro::Pedido => executar_para::<Pedido>(&argumentos, config, tipo),
+    }
+}
+
+fn executar_para<R: RegistroCli>(argumentos: &Argumentos, config: &Configuracao, tipo: TipoRegistro) -> Result<i32, ErroCli> {
+    let caminhos = argumentos.caminhos(config, tipo);
+    let arquivo = abrir_arquivo::<R>(&caminhos, config);
+    // Arquivos indicados por --dados não passam pela recuperação nem pelo
+    // checkpoint que o main faz com os arquivos da configuração
+    let fora_da_configuracao = argumentos.opcoes.contains_key("dados");
+    if fora_da_configuracao {
+        arquivo.recuperar()?;
+    }
+    let resultado = executar_operacao(argumentos, config, tipo, &caminhos, &arquivo);
+    if fora_da_configuracao {
+        arquivo.wal().checkpoint()?;
+    }
+    resultado
+}
+
+// Arquivos da entidade com os índices e as políticas da configuração
+pub(crate) fn abrir_arquivo<R: Registro>(caminhos: &CaminhosEntidade, config: &Configuracao) -> ArquivoSequencial<R> {
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow)
+        .com_indice(&caminhos.indice)
+        .com_memoria_ordenacao(config.memoria_ordenacao())
+        .com_politica_duplicatas(config.duplicatas)
+        .com_indices_secundarios(&caminhos.indices_secundarios)
+        .com_formato_textos(caminhos.textos.formato);
+    let arquivo = match caminhos.arvore {
+        Some(ordem) => arquivo.com_arvore_b(ordem),
+        None => arquivo,
+    };
+    match caminhos.hash {
+        Some(capacidade) => arquivo.com_hash(capacidade),
+        None => arquivo,
+    }
+}
+
+fn executar_operacao<R: RegistroCli>(
+    argumentos: &Argumentos,
+    config: &Configuracao,
+    tipo: TipoRegistro,
+    caminhos: &CaminhosEntidade,
+    arquivo: &ArquivoSequencial<R>,
+) -> Result<i32, ErroCli> {
+    match argumentos.posicional(0, "comando")? {
+        "import" => {
+            let mapeamento = match argumentos.opcoes.get("mapeamento") {
+                Some(caminho) => MapeamentoColunas::carregar(caminho)?,
+                None => config.mapeamento_colunas()?,
+            };
+            let relatorio = R::importar(&config.caminho_csv(), &caminhos.dados, &mapeamento, config.memoria_ordenacao(), &caminhos.textos)?;
+            println!("{}", relatorio);
+            println!("{} registros de {} gravados em {}", relatorio.gravados, tipo.nome(), caminhos.dados);
+            Ok(SUCESSO)
+        }
+        "list" => {
+            let limite = argumentos.opcao_numero("limit", 10usize)?;
+            for registro in aed2_project1::arquivo::ler_primeiros::<R>(&caminhos.dados, limite)? {
+                println!("{:?}", registro);
+            }
+            Ok(SUCESSO)
+        }
+        "get" => {
+            let chave = ler_chave(argumentos)?;
+            let encontrado = if Path::new(&caminhos.indice).exists() {
+                let indice = IndiceParcial::carregar_binario(&caminhos.indice, R::LAYOUT)?;
+                arquivo.consultar(&indice, chave)?
+            } else {
+                arquivo.buscar(chave)?
+            };
+            match encontrado {
+                Some(registro) => {
+                    println!("{:?}", registro);
+                    Ok(SUCESSO)
+                }
+                None => {
+                    eprintln!("{} {} não encontrado", tipo.nome(), chave);
+                    Ok(NAO_ENCONTRADO)
+                }
+            }
+        }
+        "range" => {
+            let min = ler_numero(argumentos, 2, "min")?;
+            let max = ler_numero(argumentos, 3, "max")?;
+            let indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            let mut encontrados = 0;
+            for registro in arquivo.buscar_intervalo(&indice, min, max)? {
+                println!("{:?}", registro?);
+                encontrados += 1;
+            }
+            if encontrados == 0 {
+                eprintln!("nenhum {} com chave entre {} e {}", tipo.nome(), min, max);
+                return Ok(NAO_ENCONTRADO);
+            }
+            Ok(SUCESSO)
+        }
+        "find" => {
+            let campo = argumentos.posicional(2, "campo")?;
+            let texto = argumentos.posicional(3, "valor")?;
+            let Some((_, tipo_valor)) = R::CAMPOS_SECUNDARIOS.iter().find(|(nome, _)| *nome == campo) else {
+                let nomes: Vec<&str> = R::CAMPOS_SECUNDARIOS.iter().map(|(nome, _)| *nome).collect();
+                return Err(ErroCli::Uso(format!("campo de busca desconhecido: {} (use {})", campo, nomes.join(" "))));
+            };
+            let valor = ValorSecundario::interpretar(*tipo_valor, texto).map_err(ErroCli::Uso)?;
+            let ate = match argumentos.posicionais.get(4) {
+                Some(texto) => ValorSecundario::interpretar(*tipo_valor, texto).map_err(ErroCli::Uso)?,
+                None => valor.clone(),
+            };
+            let indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            let encontrados = arquivo.buscar_por_faixa(&indice, campo, &valor, &ate)?;
+            if encontrados.is_empty() {
+                if ate == valor {
+                    eprintln!("nenhum {} com {} = {}", tipo.nome(), campo, valor);
+                } else {
+                    eprintln!("nenhum {} com {} entre {} e {}", tipo.nome(), campo, valor, ate);
+                }
+                return Ok(NAO_ENCONTRADO);
+            }
+            for registro in encontrados {
+                println!("{:?}", registro);
+            }
+            Ok(SUCESSO)
+        }
+        "insert" => {
+            let campos = ler_campos(&argumentos.posicionais[2..])?;
+            let mut registro = R::de_campos(&campos)?;
+            let truncamentos = caminhos.textos.ajustar_registro(&mut registro).map_err(Erro::RegistroInvalido)?;
+            registro.conferir_referencias(config)?;
+            let mut indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            avisar_truncamentos(&truncamentos);
+            match arquivo.inserir(&registro, &mut indice) {
+                Ok(Insercao::Inserido) => println!("{} {} inserido", tipo.nome(), registro.chave()),
+                Ok(Insercao::Reconstruido) => {
+                    println!("{} {} inserido", tipo.nome(), registro.chave());
+                    println!("overflow cheio: arquivo e índice reconstruídos");
+                }
+                Ok(Insercao::Substituido) => println!("{} {} já existia e foi substituído", tipo.nome(), registro.chave()),
+                Err(Erro::ChaveDuplicada(chave)) => {
+                    eprintln!("{} {} já existe (duplicatas = \"rejeitar\")", tipo.nome(), chave);
+                    return Ok(FALHA);
+                }
+                Err(e) => return Err(e.into()),
+            }
+            Ok(SUCESSO)
+        }
+        "update" => {
+            let chave = ler_chave(argumentos)?;
+            let campos = ler_campos(&argumentos.posicionais[3..])?;
+            let indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            match R::atualizar(arquivo, &indice, chave, &campos, &caminhos.textos) {
+                Ok((registro, truncamentos)) => {
+                    avisar_truncamentos(&truncamentos);
+                    println!("{:?}", registro);
+                    Ok(SUCESSO)
+                }
+                Err(ErroCli::Armazenamento(Erro::ChaveNaoEncontrada(_))) => {
+                    eprintln!("{} {} não encontrado", tipo.nome(), chave);
+                    Ok(NAO_ENCONTRADO)
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "delete" => {
+            let chave = ler_chave(argumentos)?;
+            let indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            if R::remover(arquivo, &indice, chave, config)? {
+                println!("{} {} removido", tipo.nome(), chave);
+                Ok(SUCESSO)
+            } else {
+                eprintln!("{} {} não encontrado", tipo.nome(), chave);
+                Ok(NAO_ENCONTRADO)
+            }
+        }
+        "rebuild" => {
+            let mut indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            let relatorio = arquivo.reconstruir(&mut indice)?;
+            println!("{}", relatorio);
+            println!("Entradas no índice: {}", indice.entradas.len());
+            Ok(SUCESSO)
+        }
+        "verify" => {
+            let relatorio = verificar(arquivo)?;
+            println!("{}", relatorio);
+            Ok(if relatorio.sem_problemas() { SUCESSO } else { FALHA })
+        }
+        "orphans" => {
+            if tipo != TipoRegistro::Pedido {
+                return Err(ErroCli::Uso("orphans vale só para pedido".to_string()));
+            }
+            let caminhos_produtos = config.caminhos(TipoRegistro::Produto);
+            let indice_produtos = carregar_ou_novo::<Produto>(&caminhos_produtos.indice, config.fator_esparsidade)?;
+            let produtos = ArquivosRelacionados::novo(&caminhos_produtos, &indice_produtos);
+            let relatorio = pedidos_orfaos(&caminhos.dados, &caminhos.overflow, &produtos)?;
+            println!("{}", relatorio);
+            Ok(if relatorio.sem_orfaos() { SUCESSO } else { FALHA })
+        }
+        "repair" => {
+            let mut indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)
+                .unwrap_or_else(|_| IndiceParcial::novo(config.fator_esparsidade, R::LAYOUT));
+            let relatorio = arquivo.reparar(&mut indice)?;
+            println!("{}", relatorio);
+            println!("Entradas no índice: {}", indice.entradas.len());
+            if relatorio.quarentenados > 0 {
+                println!("Quarentena: {}", caminho_quarentena(&caminhos.dados));
+            }
+            Ok(SUCESSO)
+        }
+        "migrate" => {
+            // O índice de um layout anterior não carrega; a migração reconstrói outro
+            let mut indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)
+                .unwrap_or_else(|_| IndiceParcial::novo(config.fator_esparsidade, R::LAYOUT));
+            let relatorio = arquivo.migrar(&mut indice)?;
+            println!("{}", relatorio);
+            if relatorio.quarentenados > 0 {
+                println!("Quarentena: {}", caminho_quarentena(&caminhos.dados));
+            }
+            Ok(SUCESSO)
+        }
+        "index" => match argumentos.posicional(1, "build|show")? {
+            "build" => {
+                let fator = argumentos.opcao_numero("fator", config.fator_esparsidade)?;
+                if fator == 0 {
+                    return Err(ErroCli::Uso("--fator deve ser maior que zero".to_string()));
+                }
+                let indice = construir_indice_parcial(&caminhos.dados, fator, R::LAYOUT)?;
+                indice.salvar_binario(&caminhos.indice)?;
+                println!("índice com {} entradas salvo em {}", indice.entradas.len(), caminhos.indice);
+                let indice_overflow = arquivo.construir_indice_overflow()?;
+                println!(
+                    "índice do overflow com {} entradas salvo em {}",
+                    indice_overflow.num_entradas(),
+                    arquivo.caminho_indice_overflow()
+                );
+                if let Some(ordem) = caminhos.arvore {
+                    let mut arvore = arquivo.construir_arvore(ordem)?;
+                    let altura = arvore.altura()?;
+                    println!(
+                        "árvore B+ de ordem {} com {} chaves ({} níveis) salva em {}",
+                        ordem,
+                        arvore.num_chaves,
+                        altura,
+                        arquivo.caminho_arvore()
+                    );
+                }
+                if let Some(capacidade) = caminhos.hash {
+                    let mut hash = arquivo.construir_hash(capacidade)?;
+                    let baldes = hash.num_baldes()?;
+                    println!(
+                        "índice hash com {} chaves em {} baldes de {} entradas salvo em {}",
+                        hash.num_chaves,
+                        baldes,
+                        capacidade,
+                        arquivo.caminho_hash()
+                    );
+                }
+                for (campo, entradas) in arquivo.construir_indices_secundarios()? {
+                    println!("índice de {} com {} entradas salvo em {}", campo, entradas, arquivo.caminho_indice_secundario(&campo));
+                }
+                Ok(SUCESSO)
+            }
+            "show" => {
+                if !Path::new(&caminhos.indice).exists() {
+                    eprintln!("índice não encontrado: {}", caminhos.indice);
+                    return Ok(NAO_ENCONTRADO);
+                }
+                menu::mostrar_estrutura_indices(&caminhos.indice, R::LAYOUT);
+                Ok(SUCESSO)
+            }
+            outro => Err(ErroCli::Uso(format!("subcomando de index desconhecido: {}", outro))),
+        },
+        "bench" => {
+            let consultas = argumentos.opcao_numero("consultas", 1000usize)?;
+            let ordem = argumentos.opcao_numero("ordem", caminhos.arvore.unwrap_or(ORDEM_PADRAO))?;
+            if !(ORDEM_MINIMA..=ORDEM_MAXIMA).contains(&ordem) {
+                return Err(ErroCli::Uso(format!("--ordem deve estar entre {} e {}", ORDEM_MINIMA, ORDEM_MAXIMA)));
+            }
+            let capacidade = argumentos.opcao_numero("capacidade", caminhos.hash.unwrap_or(CAPACIDADE_PADRAO))?;
+            if !(CAPACIDADE_MINIMA..=CAPACIDADE_MAXIMA).contains(&capacidade) {
+                return Err(ErroCli::Uso(format!(
+                    "--capacidade deve estar entre {} e {}",
+                    CAPACIDADE_MINIMA, CAPACIDADE_MAXIMA
+                )));
+            }
+            let indice = carregar_ou_novo::<R>(&caminhos.indice, config.fator_esparsidade)?;
+            println!("{}", comparar_indices(arquivo, &indice, ordem, capacidade, consultas)?);
+            Ok(SUCESSO)
+        }
+        outro => Err(ErroCli::Uso(format!("comando desconhecido: {}", outro))),
+    }
+}
+
+fn ler_chave(argumentos: &Argumentos) -> Result<i64, ErroCli> {
+    ler_numero(argumentos, 2, "chave")
+}
+
+fn ler_numero(argumentos: &Argumentos, posicao: usize, nome: &str) -> Result<i64, ErroCli> {
+    let valor = argumentos.posicional(posicao, nome)?;
+    valor
+        .parse()
+        .map_err(|_| ErroCli::Uso(format!("{} inválido: {}", nome, valor)))
+}
+
+fn juntar_chaves(chaves: &[i64]) -> String {
+    chaves.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn carregar_ou_novo<R: Registro>(caminho_indice: &str, fator: usize) -> Resultado<IndiceParcial> {
+    if Path::new(caminho_indice).exists() {
+        IndiceParcial::carregar_binario(caminho_indice, R::LAYOUT)
+    } else {
+        Ok(IndiceParcial::novo(fator, R::LAYOUT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "event_time,order_id,product_id,quantity,category_id,category_alias,brand_id,price,user_id,gender,color,metal,gem
+2018-12-01 11:40:00+00:00,1000,5000,1,1,jewelry.earring,0,10.00,900,f,red,gold,diamond
+2018-12-01 11:40:01+00:00,1007,5013,1,1,jewelry.ring,0,1.50,901,f,red,silver,ruby
+2018-12-01 11:40:02+00:00,1014,5020,1,1,jewelry.pendant,0,7.25,902,m,red,gold,
+";
+
+    // Diretório de dados próprio para o teste, com o CSV acima
+    fn configuracao(nome: &str) -> Configuracao {
+        let dir = std::env::temp_dir().join(format!("aed2_cli_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("jewelry.csv"), CSV).unwrap();
+        Configuracao { diretorio_dados: dir, ..Configuracao::default() }
+    }
+
+    fn rodar(config: &Configuracao, args: &[&str]) -> i32 {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        executar(&args, config)
+    }
+
+    fn separar(args: &[&str]) -> Result<Argumentos, ErroCli> {
+        Argumentos::separar(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    // Opções --nome valor saem dos posicionais; opção sem valor, argumento
+    // faltando e campo sem "=" são erros de uso
+    #[test]
+    fn separacao_dos_argumentos() {
+        let argumentos = separar(&["range", "produto", "--limit", "5", "10", "20", "--dados", "x.dat"]).unwrap();
+        assert_eq!(argumentos.posicionais, ["range", "produto", "10", "20"]);
+        assert_eq!(argumentos.opcao_numero("limit", 10usize).ok(), Some(5));
+        assert_eq!(ler_numero(&argumentos, 3, "max").ok(), Some(20));
+        assert_eq!(argumentos.caminhos(&Configuracao::default(), TipoRegistro::Produto).dados, "x.dat");
+
+        assert!(matches!(separar(&["list", "produto", "--limit"]), Err(ErroCli::Uso(_))));
+        assert!(matches!(argumentos.posicional(4, "valor"), Err(ErroCli::Uso(_))));
+        let argumentos = separar(&["get", "produto", "abc", "--limit", "muitos"]).unwrap();
+        assert!(matches!(ler_chave(&argumentos), Err(ErroCli::Uso(_))));
+        assert!(matches!(argumentos.opcao_numero("limit", 10usize), Err(ErroCli::Uso(_))));
+
+        let campos = ler_campos(&["product_id=7".to_string(), "material=gold".to_string()]).unwrap();
+        assert_eq!((campos["product_id"].as_str(), campos["material"].as_str()), ("7", "gold"));
+        assert!(matches!(ler_campos(&["material".to_string()]), Err(ErroCli::Uso(_))));
+    }
+
+    // 0 quando acha ou conclui, 1 quando a chave não existe e 2 para uso
+    // errado, valor inválido ou operação recusada
+    #[test]
+    fn codigos_de_saida() {
+        let config = configuracao("codigos");
+        assert_eq!(rodar(&config, &["import", "produto"]), SUCESSO);
+        assert_eq!(rodar(&config, &["import", "pedido"]), SUCESSO);
+        assert_eq!(rodar(&config, &["index", "build", "produto"]), SUCESSO);
+
+        assert_eq!(rodar(&config, &["get", "produto", "5013"]), SUCESSO);
+        assert_eq!(rodar(&config, &["get", "produto", "4999"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["range", "produto", "5000", "5013"]), SUCESSO);
+        assert_eq!(rodar(&config, &["range", "produto", "6000", "7000"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["find", "produto", "material", "gold"]), SUCESSO);
+        assert_eq!(rodar(&config, &["find", "produto", "material", "platinum"]), NAO_ENCONTRADO);
+
+        assert_eq!(rodar(&config, &[]), FALHA);
+        assert_eq!(rodar(&config, &["get"]), FALHA);
+        assert_eq!(rodar(&config, &["get", "cliente", "1"]), FALHA);
+        assert_eq!(rodar(&config, &["get", "produto", "abc"]), FALHA);
+        assert_eq!(rodar(&config, &["list", "produto", "--limit"]), FALHA);
+        assert_eq!(rodar(&config, &["find", "produto", "peso", "1"]), FALHA);
+
+        assert_eq!(rodar(&config, &["insert", "produto", "product_id=7000", "price=abc"]), FALHA);
+        assert_eq!(rodar(&config, &["insert", "produto", "product_id=7000", "price=12.00", "material=gold"]), SUCESSO);
+        assert_eq!(rodar(&config, &["insert", "pedido", "order_id=2000", "event_time=2018-12-02", "product_id=4999"]), FALHA);
+        assert_eq!(rodar(&config, &["insert", "pedido", "order_id=2000", "event_time=2018-12-02", "product_id=7000", "price=12.00"]), SUCESSO);
+
+        assert_eq!(rodar(&config, &["update", "produto", "7000", "stone=ruby"]), SUCESSO);
+        assert_eq!(rodar(&config, &["update", "produto", "4999", "stone=ruby"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["update", "produto", "7000", "product_id=7001"]), FALHA);
+
+        // Com remocao_produtos = restringir, o produto com pedidos fica
+        assert_eq!(rodar(&config, &["delete", "produto", "7000"]), FALHA);
+        assert_eq!(rodar(&config, &["delete", "pedido", "2000"]), SUCESSO);
+        assert_eq!(rodar(&config, &["delete", "pedido", "2000"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["delete", "produto", "7000"]), SUCESSO);
+        assert_eq!(rodar(&config, &["get", "produto", "7000"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["delete", "produto", "4999"]), NAO_ENCONTRADO);
+        assert_eq!(rodar(&config, &["delete", "produto", "abc"]), FALHA);
+
+        assert_eq!(rodar(&config, &["verify", "produto"]), SUCESSO);
+        assert_eq!(rodar(&config, &["orphans", "pedido"]), SUCESSO);
+        std::fs::remove_dir_all(&config.diretorio_dados).unwrap();
+    }
+}
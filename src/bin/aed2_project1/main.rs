@@ -0,0 +1,99 @@
+mod cli;
+mod menu;
+
+use aed2_project1::config::Configuracao;
+use aed2_project1::manifesto::Estado;
+use aed2_project1::{ArquivoSequencial, Pedido, Produto, Registro, Resultado};
+
+// Opções globais, aceitas tanto no menu quanto nos subcomandos
+const USO_GLOBAL: &str = "opções globais: --config ARQUIVO.toml --dir DIRETORIO_DADOS --csv ARQUIVO.csv";
+
+// Separa --config/--dir/--csv dos demais argumentos e monta a configuração
+// (arquivo TOML, depois variáveis de ambiente, depois linha de comando)
+fn carregar_configuracao(args: &[String]) -> Resultado<(Configuracao, Vec<String>)> {
+    let mut arquivo_config = None;
+    let mut dir = None;
+    let mut csv = None;
+    let mut resto = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let destino = match arg.as_str() {
+            "--config" => &mut arquivo_config,
+            "--dir" => &mut dir,
+            "--csv" => &mut csv,
+            _ => {
+                resto.push(arg.clone());
+                continue;
+            }
+        };
+        match iter.next() {
+            Some(valor) => *destino = Some(valor.clone()),
+            None => {
+                return Err(aed2_project1::Erro::Configuracao(format!("opção {} sem valor", arg)));
+            }
+        }
+    }
+
+    let mut config = Configuracao::carregar(arquivo_config.as_deref())?;
+    if let Some(dir) = dir {
+        config.diretorio_dados = dir.into();
+    }
+    if let Some(csv) = csv {
+        config.csv = csv;
+    }
+    Ok((config, resto))
+}
+
+// Conclui ou descarta reconstruções interrompidas e reaplica o WAL de cada entidade
+fn recuperar<R: Registro>(config: &Configuracao) -> Resultado<()> {
+    let caminhos = config.caminhos(R::TIPO);
+    let recuperacao = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow).recuperar()?;
+    match recuperacao.reconstrucao {
+        Some(Estado::Renomeando) => eprintln!("reconstrução interrompida de {} concluída", caminhos.dados),
+        Some(_) => eprintln!("reconstrução interrompida de {} descartada", caminhos.dados),
+        None => {}
+    }
+    if recuperacao.arvore_refeita {
+        eprintln!("árvore B+ de {} refeita depois de uma alteração interrompida", caminhos.dados);
+    }
+    if recuperacao.hash_refeito {
+        eprintln!("índice hash de {} refeito depois de uma alteração interrompida", caminhos.dados);
+    }
+    if recuperacao.operacoes_reaplicadas > 0 {
+        eprintln!("{} operações reaplicadas do WAL de {}", recuperacao.operacoes_reaplicadas, caminhos.dados);
+    }
+    Ok(())
+}
+
+fn checkpoint<R: Registro>(config: &Configuracao) -> Resultado<()> {
+    let caminhos = config.caminhos(R::TIPO);
+    ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow).wal().checkpoint()
+}
+
+// Sem argumentos abre o menu interativo; com argumentos executa um subcomando
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (config, resto) = match carregar_configuracao(&args) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("erro: {}\n{}", e, USO_GLOBAL);
+            std::process::exit(cli::FALHA);
+        }
+    };
+    if let Err(e) = recuperar::<Produto>(&config).and_then(|_| recuperar::<Pedido>(&config)) {
+        eprintln!("erro: {}", e);
+        std::process::exit(cli::FALHA);
+    }
+    let codigo = if resto.is_empty() {
+        menu::executar(&config);
+        cli::SUCESSO
+    } else {
+        cli::executar(&resto, &config)
+    };
+    // Saída normal: dados sincronizados, o WAL não precisa ser reaplicado na próxima vez
+    if let Err(e) = checkpoint::<Produto>(&config).and_then(|_| checkpoint::<Pedido>(&config)) {
+        eprintln!("erro: {}", e);
+        std::process::exit(cli::FALHA);
+    }
+    std::process::exit(codigo);
+}
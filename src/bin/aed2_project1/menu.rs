@@ -0,0 +1,874 @@
+use std::io::{self, Write};
+use aed2_project1::cabecalho::{self, TipoRegistro};
+use aed2_project1::indice::*;
+use aed2_project1::pedido::*;
+use aed2_project1::produto::*;
+use aed2_project1::registro::Registro;
+use aed2_project1::utils::*;
+use aed2_project1::arquivo::{caminho_quarentena, Insercao, RastroConsulta};
+use aed2_project1::verificar::verificar;
+use aed2_project1::arvore_b::ORDEM_PADRAO;
+use aed2_project1::indice_hash::{CAPACIDADE_MAXIMA, CAPACIDADE_MINIMA, CAPACIDADE_PADRAO};
+use aed2_project1::desempenho::comparar_indices;
+use aed2_project1::{ArquivoSequencial, Erro, Resultado};
+use aed2_project1::config::{CaminhosEntidade, Configuracao};
+use aed2_project1::textos::Truncamento;
+use aed2_project1::data_hora::DataHora;
+use aed2_project1::importacao::validar_preco;
+use aed2_project1::integridade::{pedidos_orfaos, ArquivosRelacionados};
+use aed2_project1::preco::{totais, Preco};
+use crate::cli::abrir_arquivo;
+
+
+pub fn executar(config: &Configuracao) {
+    loop {
+        println!("\n=== MENU PRINCIPAL ===");
+        println!("1 - Funções de Produtos");
+        println!("2 - Funções de Pedidos");
+        println!("0 - Sair");
+        let escolha = match ler_opcao() {
+            Ok(Some(escolha)) => escolha,
+            Ok(None) => break,
+            Err(e) => {
+                println!("Erro: {}", e);
+                break;
+            }
+        };
+        match escolha.as_str() {
+            "1" => menu_produtos(config),
+            "2" => menu_pedidos(config),
+            "0" => {
+                println!("Saindo...");
+                break;
+            }
+            _ => println!("Opção inválida!"),
+        }
+    }
+}
+
+// Mostra o prompt e lê a opção escolhida; None quando a entrada acabou
+fn ler_opcao() -> Resultado<Option<String>> {
+    print!("Escolha uma opção: ");
+    io::stdout().flush()?;
+    let mut escolha = String::new();
+    if io::stdin().read_line(&mut escolha)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(escolha.trim().to_string()))
+}
+
+// Carrega o índice salvo; se ainda não existir, começa um vazio com o fator da configuração
+fn carregar_indice(caminho: &str, layout: LayoutRegistro, fator: usize) -> Resultado<IndiceParcial> {
+    if !std::path::Path::new(caminho).exists() {
+        println!("Indice nao encontrado, criando novo com fator {}", fator);
+        return Ok(IndiceParcial::novo(fator, layout));
+    }
+    IndiceParcial::carregar_binario(caminho, layout)
+}
+
+fn menu_produtos(config: &Configuracao) {
+    loop {
+        println!("\n=== MENU PRINCIPAL ===");
+        println!("1 - Gerar arquivo binário de produtos a partir do CSV");
+        println!("2 - Mostrar produtos (primeiros N)");
+        println!("3 - Buscar produto por product_id (busca binária)");
+        println!("4 - Construir índice parcial de produtos");
+        println!("5 - Consultar produto via índice parcial");
+        println!("6 - Consultar produto via índice (com debug)");
+        println!("7 - Inserir novo produto");
+        println!("8 - Remover produto por product_id");
+        println!("9 - Mostrar estrutura do arquivo de índices");
+        println!("10 - Reconstruir arquivo e índice");
+        println!("11 - Migrar arquivos de formatos e layouts antigos");
+        println!("12 - Verificar integridade dos arquivos");
+        println!("13 - Reparar arquivos (quarentena de registros corrompidos)");
+        println!("14 - Atualizar campos de um registro");
+        println!("15 - Buscar por intervalo de chaves");
+        println!("16 - Buscar produtos por material");
+        println!("17 - Construir índice em árvore B+");
+        println!("18 - Comparar índice parcial, árvore B+ e hash");
+        println!("19 - Construir índice hash");
+        println!("20 - Consultar via índice hash");
+        println!("0 - Sair");
+        let escolha = match ler_opcao() {
+            Ok(Some(escolha)) => escolha,
+            Ok(None) => break,
+            Err(e) => {
+                println!("Erro: {}", e);
+                break;
+            }
+        };
+        if escolha == "0" {
+            println!("Saindo...");
+            break;
+        }
+        if let Err(e) = executar_opcao_produtos(&escolha, config) {
+            println!("Erro: {}", e);
+        }
+    }
+}
+
+fn executar_opcao_produtos(escolha: &str, config: &Configuracao) -> Resultado<()> {
+    let caminhos = config.caminhos(TipoRegistro::Produto);
+    let produtos_path = caminhos.dados.as_str();
+    let indice_produto_path = caminhos.indice.as_str();
+    let overflow_produto_path = caminhos.overflow.as_str();
+    let produtos = abrir_arquivo::<Produto>(&caminhos, config);
+    let exige_arquivo = matches!(escolha, "3" | "4" | "5" | "6" | "8" | "14" | "15" | "16" | "17" | "18" | "19" | "20");
+    if exige_arquivo && !std::path::Path::new(produtos_path).exists() {
+        println!("Arquivo de produtos nao encontrado! Execute primeiro a opcao 1.");
+        return Ok(());
+    }
+
+    match escolha {
+        "1" => {
+            println!("Gerando arquivo binário de produtos a partir do CSV...");
+            let relatorio = importar_produtos_csv(&config.caminho_csv(), produtos_path, &config.mapeamento_colunas()?, config.memoria_ordenacao(), &caminhos.textos)?;
+            println!("{}", relatorio);
+            println!("Arquivo de produtos criado e ordenado!");
+        }
+        "2" => {
+            println!("Quantos produtos deseja mostrar?");
+            let n = ler_linha()?.trim().parse().unwrap_or(10);
+            let lista = mostrar_produtos(produtos_path, n)?;
+            for p in lista {
+                println!("{:?}", p);
+            }
+        }
+        "3" => {
+            println!("Informe o product_id para buscar:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            match buscar_produto_com_overflow(produtos_path, overflow_produto_path, chave)? {
+                Some(produto) => println!("Produto encontrado: {:?}", produto),
+                None => println!("Produto NÃO encontrado!"),
+            }
+        }
+        "4" => {
+            println!("Informe fator de esparsidade para índice parcial:");
+            let fator = ler_linha()?.trim().parse().unwrap_or(config.fator_esparsidade);
+            let indice = construir_indice_parcial(produtos_path, fator, Produto::LAYOUT)?;
+            indice.salvar_binario(indice_produto_path)?;
+            println!("Índice parcial construído e salvo em formato binário!");
+            construir_indices_secundarios::<Produto>(&caminhos)?;
+        }
+        "5" => {
+            println!("Informe o product_id para consulta via índice:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            match consultar_com_indice_e_overflow(produtos_path, overflow_produto_path, &indice, chave)? {
+                Some(produto) => println!("Produto encontrado: {:?}", produto),
+                None => println!("Produto NÃO encontrado!"),
+            }
+        }
+        "6" => {
+            println!("Informe o product_id para consulta via índice (com debug):");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let rastro = consultar_com_indice_e_overflow_debug(produtos_path, overflow_produto_path, &indice, chave)?;
+            mostrar_rastro(&rastro);
+            match rastro.encontrado {
+                Some(produto) => println!("\n✅ Produto encontrado: {:?}", produto),
+                None => println!("\n❌ Produto NÃO encontrado!"),
+            }
+        }
+        "7" => {
+            println!("Informe dados do novo produto:");
+            let product_id = read_num("product_id")?;
+            let category_alias = read_string("category_alias")?;
+            let price = match read_preco()? {
+                Ok(price) => price,
+                Err(motivo) => {
+                    println!("Produto recusado: {}", motivo);
+                    return Ok(());
+                }
+            };
+            let material = read_string("material")?;
+            let stone = read_string("stone")?;
+
+            let mut indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let mut produto = Produto {
+                product_id,
+                category_alias,
+                price,
+                material,
+                stone,
+            };
+            let truncamentos = match caminhos.textos.ajustar_registro(&mut produto) {
+                Ok(truncamentos) => truncamentos,
+                Err(motivo) => {
+                    println!("Produto recusado: {}", motivo);
+                    return Ok(());
+                }
+            };
+            mostrar_truncamentos(&truncamentos);
+            match inserir_novo_produto(&produtos, produto, &mut indice) {
+                Ok(Insercao::Substituido) => println!("Produto já existia e foi substituído!"),
+                Ok(_) => println!("Novo produto inserido (área de overflow)!"),
+                Err(Erro::ChaveDuplicada(chave)) => println!("Já existe um produto com a chave {}; inserção recusada.", chave),
+                Err(e) => return Err(e),
+            }
+        }
+        "8" => {
+            println!("Informe o product_id para remoção:");
+            let chave = read_num("product_id")?;
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let caminhos_pedidos = config.caminhos(TipoRegistro::Pedido);
+            let indice_pedidos = carregar_indice(&caminhos_pedidos.indice, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let pedidos = abrir_arquivo::<Pedido>(&caminhos_pedidos, config);
+            match remover_produto_com_overflow(&produtos, &indice, chave, &pedidos, &indice_pedidos, config.remocao_produtos) {
+                Ok(Some(remocao)) => println!("{}", remocao),
+                Ok(None) => println!("Produto NÃO encontrado para remoção!"),
+                Err(e @ Erro::ProdutoComPedidos { .. }) => println!("Remoção recusada: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        "9" => {
+            mostrar_estrutura_indices(indice_produto_path, Produto::LAYOUT);
+        }
+        "10" => {
+            println!("Reconstruindo arquivo e índice...");
+            let mut indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let relatorio = reconstruir_arquivo_e_indice(&produtos, &mut indice)?;
+            println!("{}", relatorio);
+            println!("Entradas no indice: {}", indice.entradas.len());
+            println!("✅ Reconstrução concluída!");
+        }
+        "11" => {
+            migrar_arquivos::<Produto>(&caminhos, config.fator_esparsidade, config.memoria_ordenacao())?;
+        }
+        "12" => {
+            verificar_arquivos::<Produto>(&caminhos);
+        }
+        "13" => {
+            // Um índice ilegível também é reparado: a reconstrução gera outro
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)
+                .unwrap_or_else(|_| IndiceParcial::novo(config.fator_esparsidade, Produto::LAYOUT));
+            reparar_arquivos::<Produto>(&caminhos, indice, config.memoria_ordenacao())?;
+        }
+        "14" => {
+            println!("Informe o product_id a atualizar:");
+            let chave = read_num("product_id")?;
+            println!("Novos valores (deixe em branco para manter):");
+            let alteracao = AlteracaoProduto {
+                category_alias: read_opcional("category_alias")?,
+                price: read_opcional("price")?,
+                material: read_opcional("material")?,
+                stone: read_opcional("stone")?,
+            };
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            match atualizar_produto(&produtos, &indice, chave, &alteracao, &caminhos.textos) {
+                Ok((produto, truncamentos)) => {
+                    mostrar_truncamentos(&truncamentos);
+                    println!("Produto atualizado: {:?}", produto);
+                }
+                Err(Erro::ChaveNaoEncontrada(_)) => println!("Produto NÃO encontrado!"),
+                Err(e) => return Err(e),
+            }
+        }
+        "15" => {
+            println!("Informe o intervalo de product_id:");
+            let min = read_num("mínimo")?;
+            let max = read_num("máximo")?;
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let mut encontrados = 0;
+            for produto in buscar_intervalo_produtos(produtos_path, overflow_produto_path, &indice, min, max)? {
+                println!("{:?}", produto?);
+                encontrados += 1;
+            }
+            println!("{} produtos no intervalo", encontrados);
+        }
+        "16" => {
+            let material = read_string("material")?;
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            let produtos = produtos_por_material(produtos_path, overflow_produto_path, &indice, material.trim())?;
+            for produto in &produtos {
+                println!("{:?}", produto);
+            }
+            println!("{} produtos de {}", produtos.len(), material.trim());
+        }
+        "17" => {
+            construir_arvore_b::<Produto>(&caminhos)?;
+        }
+        "18" => {
+            let indice = carregar_indice(indice_produto_path, Produto::LAYOUT, config.fator_esparsidade)?;
+            comparar_indices_em_disco::<Produto>(&caminhos, &indice)?;
+        }
+        "19" => {
+            construir_indice_hash::<Produto>(&caminhos)?;
+        }
+        "20" => {
+            println!("Informe o product_id para consulta via hash:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            match consultar_com_hash(produtos_path, overflow_produto_path, chave)? {
+                Some(produto) => println!("Produto encontrado: {:?}", produto),
+                None => println!("Produto NÃO encontrado!"),
+            }
+        }
+        _ => println!("Opção inválida!"),
+    }
+    Ok(())
+}
+
+fn menu_pedidos(config: &Configuracao) {
+    loop {
+        println!("\n=== MENU PEDIDOS ===");
+        println!("1 - Gerar arquivo binário de pedidos a partir do CSV");
+        println!("2 - Mostrar pedidos (primeiros N)");
+        println!("3 - Buscar pedido por order_id (busca binária)");
+        println!("4 - Construir índice parcial de pedidos");
+        println!("5 - Consultar pedido via índice parcial");
+        println!("6 - Consultar pedido via índice (com debug)");
+        println!("7 - Inserir novo pedido");
+        println!("8 - Remover pedido por order_id");
+        println!("9 - Mostrar estrutura do arquivo de índices");
+        println!("10 - Reconstruir arquivo e índice");
+        println!("11 - Migrar arquivos de formatos e layouts antigos");
+        println!("12 - Verificar integridade dos arquivos");
+        println!("13 - Reparar arquivos (quarentena de registros corrompidos)");
+        println!("14 - Atualizar campos de um registro");
+        println!("15 - Buscar por intervalo de chaves");
+        println!("16 - Buscar pedidos de um usuário (user_id)");
+        println!("17 - Construir índice em árvore B+");
+        println!("18 - Comparar índice parcial, árvore B+ e hash");
+        println!("19 - Construir índice hash");
+        println!("20 - Consultar via índice hash");
+        println!("21 - Buscar pedidos por período (event_time)");
+        println!("22 - Listar pedidos órfãos (product_id sem produto)");
+        println!("0 - Voltar");
+        let escolha = match ler_opcao() {
+            Ok(Some(escolha)) => escolha,
+            Ok(None) => break,
+            Err(e) => {
+                println!("Erro: {}", e);
+                break;
+            }
+        };
+        if escolha == "0" {
+            println!("Voltando...");
+            break;
+        }
+        if let Err(e) = executar_opcao_pedidos(&escolha, config) {
+            println!("Erro: {}", e);
+        }
+    }
+}
+
+fn executar_opcao_pedidos(escolha: &str, config: &Configuracao) -> Resultado<()> {
+    let caminhos = config.caminhos(TipoRegistro::Pedido);
+    let pedidos_path = caminhos.dados.as_str();
+    let indice_pedido_path = caminhos.indice.as_str();
+    let overflow_pedido_path = caminhos.overflow.as_str();
+    let pedidos = abrir_arquivo::<Pedido>(&caminhos, config);
+    let exige_arquivo = matches!(escolha, "3" | "4" | "5" | "6" | "8" | "14" | "15" | "16" | "17" | "18" | "19" | "20" | "21" | "22");
+    if exige_arquivo && !std::path::Path::new(pedidos_path).exists() {
+        println!("Arquivo de pedidos nao encontrado! Execute primeiro a opcao 1.");
+        return Ok(());
+    }
+
+    match escolha {
+        "1" => {
+            println!("Gerando arquivo binário de pedidos a partir do CSV...");
+            let relatorio = importar_pedidos_csv(&config.caminho_csv(), pedidos_path, &config.mapeamento_colunas()?, config.memoria_ordenacao(), &caminhos.textos)?;
+            println!("{}", relatorio);
+            println!("Arquivo de pedidos criado e ordenado!");
+        }
+        "2" => {
+            println!("Quantos pedidos deseja mostrar?");
+            let n = ler_linha()?.trim().parse().unwrap_or(10);
+            let lista = mostrar_pedidos(pedidos_path, n)?;
+            for p in lista {
+                println!("{:?}", p);
+            }
+        }
+        "3" => {
+            println!("Informe o order_id para buscar:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            match busca_binaria_arquivo_pedido(pedidos_path, chave)? {
+                Some(pedido) => println!("Pedido encontrado: {:?}", pedido),
+                None => println!("Pedido NÃO encontrado!"),
+            }
+        }
+        "4" => {
+            println!("Informe fator de esparsidade para índice parcial:");
+            let fator = ler_linha()?.trim().parse().unwrap_or(config.fator_esparsidade);
+            let indice = construir_indice_parcial(pedidos_path, fator, Pedido::LAYOUT)?;
+            indice.salvar_binario(indice_pedido_path)?;
+            println!("Índice parcial construído e salvo em formato binário!");
+            construir_indices_secundarios::<Pedido>(&caminhos)?;
+        }
+        "5" => {
+            println!("Informe o order_id para consulta via índice:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            match consultar_com_indice_pedido(pedidos_path, &indice, chave)? {
+                Some(pedido) => println!("Pedido encontrado: {:?}", pedido),
+                None => println!("Pedido NÃO encontrado!"),
+            }
+        }
+        "6" => {
+            println!("Informe o order_id para consulta via índice (com debug):");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let rastro = consultar_com_indice_pedido_debug(pedidos_path, &indice, chave)?;
+            mostrar_rastro(&rastro);
+            match rastro.encontrado {
+                Some(pedido) => println!("\n✅ Pedido encontrado: {:?}", pedido),
+                None => println!("\n❌ Pedido NÃO encontrado!"),
+            }
+        }
+        "7" => {
+            println!("Informe dados do novo pedido:");
+            let order_id = read_num("order_id")?;
+            let user_id = read_num("user_id")?;
+            let event_time = match read_string("event_time (AAAA-MM-DD HH:MM:SS+HH:MM)")?.parse::<DataHora>() {
+                Ok(event_time) => event_time,
+                Err(motivo) => {
+                    println!("Pedido recusado: {}", motivo);
+                    return Ok(());
+                }
+            };
+            let product_id = read_num("product_id")?;
+            let price = match read_preco()? {
+                Ok(price) => price,
+                Err(motivo) => {
+                    println!("Pedido recusado: {}", motivo);
+                    return Ok(());
+                }
+            };
+
+            let mut indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let mut pedido = Pedido {
+                order_id,
+                user_id,
+                event_time,
+                product_id,
+                price,
+            };
+            let truncamentos = match caminhos.textos.ajustar_registro(&mut pedido) {
+                Ok(truncamentos) => truncamentos,
+                Err(motivo) => {
+                    println!("Pedido recusado: {}", motivo);
+                    return Ok(());
+                }
+            };
+            mostrar_truncamentos(&truncamentos);
+            let caminhos_produtos = config.caminhos(TipoRegistro::Produto);
+            let indice_produtos = carregar_indice(&caminhos_produtos.indice, Produto::LAYOUT, config.fator_esparsidade)?;
+            let produtos = ArquivosRelacionados::novo(&caminhos_produtos, &indice_produtos);
+            match inserir_novo_pedido(&pedidos, pedido, &mut indice, &produtos) {
+                Ok(Insercao::Substituido) => println!("Pedido já existia e foi substituído!"),
+                Ok(_) => println!("Novo pedido inserido (área de overflow)!"),
+                Err(Erro::ChaveDuplicada(chave)) => println!("Já existe um pedido com a chave {}; inserção recusada.", chave),
+                Err(e @ Erro::ProdutoInexistente { .. }) => println!("Pedido recusado: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        "8" => {
+            println!("Informe o order_id para remoção:");
+            let chave = read_num("order_id")?;
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            if remover_pedido_com_overflow(&pedidos, &indice, chave)? {
+                println!("Pedido removido!");
+            } else {
+                println!("Pedido NÃO encontrado para remoção!");
+            }
+        }
+        "9" => {
+            mostrar_estrutura_indices(indice_pedido_path, Pedido::LAYOUT);
+        }
+        "10" => {
+            println!("Reconstruindo arquivo e índice...");
+            let mut indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let relatorio = reconstruir_arquivo_e_indice_pedido(&pedidos, &mut indice)?;
+            println!("{}", relatorio);
+            println!("✅ Reconstrução concluída!");
+        }
+        "11" => {
+            migrar_arquivos::<Pedido>(&caminhos, config.fator_esparsidade, config.memoria_ordenacao())?;
+        }
+        "12" => {
+            verificar_arquivos::<Pedido>(&caminhos);
+        }
+        "13" => {
+            // Um índice ilegível também é reparado: a reconstrução gera outro
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)
+                .unwrap_or_else(|_| IndiceParcial::novo(config.fator_esparsidade, Pedido::LAYOUT));
+            reparar_arquivos::<Pedido>(&caminhos, indice, config.memoria_ordenacao())?;
+        }
+        "14" => {
+            println!("Informe o order_id a atualizar:");
+            let chave = read_num("order_id")?;
+            println!("Novos valores (deixe em branco para manter):");
+            let alteracao = AlteracaoPedido {
+                user_id: read_opcional("user_id")?,
+                event_time: read_opcional("event_time")?,
+                product_id: read_opcional("product_id")?,
+                price: read_opcional("price")?,
+            };
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            match atualizar_pedido(&pedidos, &indice, chave, &alteracao, &caminhos.textos) {
+                Ok((pedido, truncamentos)) => {
+                    mostrar_truncamentos(&truncamentos);
+                    println!("Pedido atualizado: {:?}", pedido);
+                }
+                Err(Erro::ChaveNaoEncontrada(_)) => println!("Pedido NÃO encontrado!"),
+                Err(e) => return Err(e),
+            }
+        }
+        "15" => {
+            println!("Informe o intervalo de order_id:");
+            let min = read_num("mínimo")?;
+            let max = read_num("máximo")?;
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let mut encontrados = 0;
+            for pedido in buscar_intervalo_pedidos(pedidos_path, overflow_pedido_path, &indice, min, max)? {
+                println!("{:?}", pedido?);
+                encontrados += 1;
+            }
+            println!("{} pedidos no intervalo", encontrados);
+        }
+        "16" => {
+            let user_id = read_num("user_id")?;
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let pedidos = pedidos_por_usuario(pedidos_path, overflow_pedido_path, &indice, user_id)?;
+            for pedido in &pedidos {
+                println!("{:?}", pedido);
+            }
+            println!("{} pedidos do usuário {}", pedidos.len(), user_id);
+            mostrar_totais(&pedidos);
+        }
+        "17" => {
+            construir_arvore_b::<Pedido>(&caminhos)?;
+        }
+        "18" => {
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            comparar_indices_em_disco::<Pedido>(&caminhos, &indice)?;
+        }
+        "19" => {
+            construir_indice_hash::<Pedido>(&caminhos)?;
+        }
+        "20" => {
+            println!("Informe o order_id para consulta via hash:");
+            let chave = ler_linha()?.trim().parse().unwrap_or(0);
+            match consultar_com_hash_pedido(pedidos_path, overflow_pedido_path, chave)? {
+                Some(pedido) => println!("Pedido encontrado: {:?}", pedido),
+                None => println!("Pedido NÃO encontrado!"),
+            }
+        }
+        "21" => {
+            println!("Informe o período (AAAA-MM-DD HH:MM:SS+HH:MM; sem a hora vale 00:00:00):");
+            let inicio = read_opcional::<DataHora>("início")?;
+            let fim = read_opcional::<DataHora>("fim")?;
+            let (Some(inicio), Some(fim)) = (inicio, fim) else {
+                println!("Informe o início e o fim do período!");
+                return Ok(());
+            };
+            let indice = carregar_indice(indice_pedido_path, Pedido::LAYOUT, config.fator_esparsidade)?;
+            let pedidos = pedidos_no_periodo(pedidos_path, overflow_pedido_path, &indice, inicio, fim)?;
+            for pedido in &pedidos {
+                println!("{:?}", pedido);
+            }
+            println!("{} pedidos entre {} e {}", pedidos.len(), inicio, fim);
+            mostrar_totais(&pedidos);
+        }
+        "22" => {
+            let caminhos_produtos = config.caminhos(TipoRegistro::Produto);
+            let indice_produtos = carregar_indice(&caminhos_produtos.indice, Produto::LAYOUT, config.fator_esparsidade)?;
+            let produtos = ArquivosRelacionados::novo(&caminhos_produtos, &indice_produtos);
+            println!("{}", pedidos_orfaos(pedidos_path, overflow_pedido_path, &produtos)?);
+        }
+        _ => println!("Opção inválida!"),
+    }
+    Ok(())
+}
+
+// Índices secundários configurados para a entidade, refeitos a partir dos dados
+fn construir_indices_secundarios<R: Registro>(caminhos: &CaminhosEntidade) -> Resultado<()> {
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow)
+        .com_indices_secundarios(&caminhos.indices_secundarios);
+    for (campo, entradas) in arquivo.construir_indices_secundarios()? {
+        println!("Índice de {} construído ({} entradas)", campo, entradas);
+    }
+    Ok(())
+}
+
+fn construir_arvore_b<R: Registro>(caminhos: &CaminhosEntidade) -> Resultado<()> {
+    println!("Ordem da árvore (chaves por página, padrão {}):", caminhos.arvore.unwrap_or(ORDEM_PADRAO));
+    let ordem = ler_linha()?.trim().parse().unwrap_or(caminhos.arvore.unwrap_or(ORDEM_PADRAO));
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow);
+    let mut arvore = arquivo.construir_arvore(ordem)?;
+    let altura = arvore.altura()?;
+    println!(
+        "Árvore B+ com {} chaves, {} níveis e {} páginas salva em {}",
+        arvore.num_chaves,
+        altura,
+        arvore.num_paginas(),
+        arquivo.caminho_arvore()
+    );
+    if caminhos.arvore.is_none() {
+        println!("Para consultar por ela, use tipo_indice = \"arvore\" na configuração");
+    }
+    Ok(())
+}
+
+fn construir_indice_hash<R: Registro>(caminhos: &CaminhosEntidade) -> Resultado<()> {
+    println!("Entradas por balde (padrão {}):", caminhos.hash.unwrap_or(CAPACIDADE_PADRAO));
+    let capacidade = ler_linha()?
+        .trim()
+        .parse()
+        .unwrap_or(caminhos.hash.unwrap_or(CAPACIDADE_PADRAO))
+        .clamp(CAPACIDADE_MINIMA, CAPACIDADE_MAXIMA);
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow);
+    let mut hash = arquivo.construir_hash(capacidade)?;
+    let baldes = hash.num_baldes()?;
+    println!(
+        "Índice hash com {} chaves, {} baldes e profundidade {} salvo em {}",
+        hash.num_chaves,
+        baldes,
+        hash.profundidade,
+        arquivo.caminho_hash()
+    );
+    if caminhos.hash.is_none() {
+        println!("Para consultar por ele nas outras opções, use tipo_indice = \"hash\" na configuração");
+    }
+    Ok(())
+}
+
+fn comparar_indices_em_disco<R: Registro>(caminhos: &CaminhosEntidade, indice: &IndiceParcial) -> Resultado<()> {
+    let consultas = read_num("número de consultas")?.max(1) as usize;
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow);
+    let ordem = caminhos.arvore.unwrap_or(ORDEM_PADRAO);
+    let capacidade = caminhos.hash.unwrap_or(CAPACIDADE_PADRAO);
+    println!("{}", comparar_indices(&arquivo, indice, ordem, capacidade, consultas)?);
+    Ok(())
+}
+
+// Só os campos com política "avisar" aparecem; "truncar" corta em silêncio
+// Passos de uma consulta "com debug": entrada do índice, trecho do principal e overflow
+fn mostrar_rastro<R>(rastro: &RastroConsulta<R>) {
+    println!("\n=== DEBUG: CONSULTA COM ÍNDICE ===");
+    println!("Chave buscada: {}", rastro.chave);
+    println!("Total de entradas no indice: {}", rastro.entradas_indice);
+    println!("Fator de esparsidade: {}", rastro.fator_esparsidade);
+
+    println!("\nPASSO 1: Busca binária no índice");
+    match rastro.entrada {
+        Some((idx, chave, posicao)) => println!("   Entrada {}: chave {}, posição {}", idx, chave, posicao),
+        None => println!("   Nenhuma entrada no índice; a busca começa no início do arquivo"),
+    }
+    for &(i, chave, posicao) in &rastro.vizinhas {
+        let marcador = if rastro.entrada.map(|(idx, _, _)| idx) == Some(i) { "👉" } else { "  " };
+        println!("   {} [{}] Chave: {}, Posição: {}", marcador, i, chave, posicao);
+    }
+
+    if let Some((inicio, fim)) = rastro.intervalo {
+        println!("\nPASSO 2: Busca sequencial no principal, bytes {} a {}", inicio, fim);
+        for (n, (chave, posicao)) in rastro.lidos_principal.iter().enumerate() {
+            println!("   Registro {}: ID={}, Posição={}", n + 1, chave, posicao);
+        }
+    }
+    if let Some(lidos) = &rastro.lidos_overflow {
+        println!("\nPASSO 3: Busca sequencial no overflow");
+        for (n, (chave, posicao)) in lidos.iter().enumerate() {
+            println!("   Registro {}: ID={}, Posição={}", n + 1, chave, posicao);
+        }
+        println!("   Registros verificados no overflow: {}", lidos.len());
+    }
+    println!("\n=== FIM DO DEBUG ===");
+}
+
+fn mostrar_truncamentos(truncamentos: &[Truncamento]) {
+    for truncamento in truncamentos.iter().filter(|t| t.avisar) {
+        println!("⚠️  {}", truncamento);
+    }
+}
+
+// Soma dos preços dos pedidos, uma linha por moeda
+fn mostrar_totais(pedidos: &[Pedido]) {
+    match totais(pedidos.iter().map(|pedido| &pedido.price)) {
+        Ok(totais) => {
+            for total in totais {
+                println!("Total: {}", total);
+            }
+        }
+        Err(motivo) => println!("Total indisponível: {}", motivo),
+    }
+}
+
+fn verificar_arquivos<R: Registro>(caminhos: &CaminhosEntidade) {
+    println!("Verificando {} e {}...", caminhos.dados, caminhos.overflow);
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow).com_indice(&caminhos.indice);
+    match verificar(&arquivo) {
+        Ok(relatorio) => {
+            println!("{}", relatorio);
+            if relatorio.sem_problemas() {
+                println!("✅ Nenhum problema encontrado");
+            } else {
+                println!("Use a opção 13 para reparar");
+            }
+        }
+        Err(e) => println!("Erro ao verificar: {}", e),
+    }
+}
+
+fn reparar_arquivos<R: Registro>(caminhos: &CaminhosEntidade, mut indice: IndiceParcial, memoria_ordenacao: usize) -> Resultado<()> {
+    println!("Reparando arquivo e índice...");
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow)
+        .com_indice(&caminhos.indice)
+        .com_memoria_ordenacao(memoria_ordenacao)
+        .com_formato_textos(caminhos.textos.formato);
+    let relatorio = arquivo.reparar(&mut indice)?;
+    println!("{}", relatorio);
+    println!("Entradas no índice: {}", indice.entradas.len());
+    if relatorio.quarentenados > 0 {
+        println!("Quarentena: {}", caminho_quarentena(&caminhos.dados));
+    }
+    println!("✅ Reparo concluído!");
+    Ok(())
+}
+
+fn migrar_arquivos<R: Registro>(caminhos: &CaminhosEntidade, fator_esparsidade: usize, memoria_ordenacao: usize) -> Resultado<()> {
+    let arquivo = ArquivoSequencial::<R>::novo(&caminhos.dados, &caminhos.overflow)
+        .com_indice(&caminhos.indice)
+        .com_memoria_ordenacao(memoria_ordenacao)
+        .com_formato_textos(caminhos.textos.formato);
+    // O índice de um layout anterior não carrega; a migração reconstrói outro
+    let mut indice = carregar_indice(&caminhos.indice, R::LAYOUT, fator_esparsidade)
+        .unwrap_or_else(|_| IndiceParcial::novo(fator_esparsidade, R::LAYOUT));
+    let relatorio = arquivo.migrar(&mut indice)?;
+    println!("{}", relatorio);
+    if relatorio.quarentenados > 0 {
+        println!("Quarentena: {}", caminho_quarentena(&caminhos.dados));
+    }
+    Ok(())
+}
+
+pub fn mostrar_estrutura_indices(indice_path: &str, layout: LayoutRegistro) {
+    println!("\n=== ESTRUTURA DO ARQUIVO DE INDICES ===");
+    
+    if !std::path::Path::new(indice_path).exists() {
+        println!("Arquivo de indice nao encontrado: {}", indice_path);
+        println!("Dica: Execute primeiro a opcao 4 para construir o indice parcial");
+        return;
+    }
+    
+    match IndiceParcial::carregar_binario(indice_path, layout) {
+        Ok(indice) => {
+            println!("Indice carregado com sucesso!");
+            println!("Tipo de registro: {} ({} bytes)", indice.layout.tipo.nome(), indice.layout.tamanho_registro);
+            println!("Fator de esparsidade: {}", indice.fator_esparsidade);
+            println!("Total de entradas no indice: {}", indice.entradas.len());
+            println!();
+            
+            if indice.entradas.is_empty() {
+                println!("O indice esta vazio!");
+                return;
+            }
+            
+            let primeira_chave = indice.entradas[0].chave;
+            let ultima_chave = indice.entradas[indice.entradas.len() - 1].chave;
+            println!("Primeira chave: {}", primeira_chave);
+            println!("Ultima chave: {}", ultima_chave);
+            println!("Intervalo de chaves: {} a {}", primeira_chave, ultima_chave);
+            println!();
+            
+            println!("Primeiras 10 entradas do indice:");
+            println!("{:<8} {:<12} {:<15}", "Pos", "Chave", "Posicao Arquivo");
+            println!("{}", "-".repeat(40));
+            
+            for (i, entrada) in indice.entradas.iter().take(10).enumerate() {
+                println!("{:<8} {:<12} {:<15}", 
+                    i, 
+                    entrada.chave, 
+                    entrada.posicao
+                );
+            }
+            
+            if indice.entradas.len() > 10 {
+                println!("... e mais {} entradas", indice.entradas.len() - 10);
+            }
+            println!();
+            
+            if let Ok(metadata) = std::fs::metadata(indice_path) {
+                println!("Informacoes do arquivo:");
+                println!("   Tamanho: {} bytes", metadata.len());
+                println!("   Caminho: {}", indice_path);
+                println!("   Formato: Binario");
+                
+                let tamanho_cabecalho = cabecalho::TAMANHO_CABECALHO;
+                let tamanho_entradas = indice.entradas.len() * 16;
+                let tamanho_esperado = tamanho_cabecalho + tamanho_entradas;
+                println!("   Tamanho esperado: {} bytes", tamanho_esperado);
+                println!("   Tamanho por entrada: 16 bytes (8 bytes chave + 8 bytes posicao)");
+            }
+            
+            if indice.entradas.len() > 1 {
+                let mut intervalos = Vec::new();
+                for i in 1..indice.entradas.len() {
+                    let intervalo = indice.entradas[i].chave - indice.entradas[i-1].chave;
+                    intervalos.push(intervalo);
+                }
+                
+                if !intervalos.is_empty() {
+                    let media_intervalo = intervalos.iter().sum::<i64>() as f64 / intervalos.len() as f64;
+                    let intervalo_min = *intervalos.iter().min().unwrap();
+                    let intervalo_max = *intervalos.iter().max().unwrap();
+                    
+                    println!();
+                    println!("Estatisticas de distribuicao:");
+                    println!("   Intervalo medio entre chaves: {:.2}", media_intervalo);
+                    println!("   Menor intervalo: {}", intervalo_min);
+                    println!("   Maior intervalo: {}", intervalo_max);
+                }
+            }
+            
+            println!();
+            println!("Como funciona o indice:");
+            println!("   - Cada entrada aponta para uma posicao no arquivo de produtos");
+            println!("   - O fator de esparsidade {} significa que a cada {} produtos, uma entrada e criada", 
+                     indice.fator_esparsidade, indice.fator_esparsidade);
+            println!("   - Para buscar um produto, o sistema usa busca binaria no indice");
+            println!("   - Depois busca sequencialmente no intervalo indicado pelo indice");
+        }
+        Err(e) => {
+            println!("Erro ao carregar o indice: {}", e);
+            println!("Verifique se o arquivo existe e esta no formato correto");
+        }
+    }
+}
+
+fn ler_linha() -> Resultado<String> {
+    let mut s = String::new();
+    io::stdin().read_line(&mut s)?;
+    Ok(s)
+}
+fn read_num(msg: &str) -> Resultado<i64> {
+    print!("{}: ", msg);
+    io::stdout().flush()?;
+    Ok(ler_linha()?.trim().parse().unwrap_or(0))
+}
+// Valor vazio fica None (campo mantido); valor que não converte é erro, para
+// não alterar o registro com um número inventado
+fn read_opcional<T: std::str::FromStr>(msg: &str) -> Resultado<Option<T>> {
+    print!("{}: ", msg);
+    io::stdout().flush()?;
+    let linha = ler_linha()?;
+    let valor = linha.trim();
+    if valor.is_empty() {
+        return Ok(None);
+    }
+    valor
+        .parse()
+        .map(Some)
+        .map_err(|_| Erro::AlteracaoInvalida(format!("valor inválido para {}: {}", msg, valor)))
+}
+// Preço com as regras da importação; o erro de dentro é o motivo da recusa
+fn read_preco() -> Resultado<Result<Preco, String>> {
+    let texto = read_string("price (123.45, com a moeda opcional: 123.45 BRL)")?;
+    Ok(texto.parse().and_then(|preco| validar_preco("price", &preco).map(|_| preco)))
+}
+fn read_string(msg: &str) -> Resultado<String> {
+    print!("{}: ", msg);
+    io::stdout().flush()?;
+    Ok(ler_linha()?.trim().to_string())
+}
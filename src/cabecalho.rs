@@ -0,0 +1,493 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crc::crc32;
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::manifesto;
+use crate::registro::TAMANHO_CRC;
+use crate::textos::FormatoTextos;
+
+// Todo arquivo de dados, overflow ou índice começa com este cabeçalho de 64 bytes:
+//   0..4   magic "AED2"
+//   4..6   versão do formato
+//   6      tipo de arquivo (dados/índice)
+//   7      tipo de registro (produto/pedido)
+//   8..12  tamanho do registro de dados
+//   12..16 posição da chave dentro do registro
+//   16..20 fator de esparsidade (só índices)
+//   20     flags (FLAG_TEXTOS_VARIAVEIS nos arquivos de dados)
+//   24..32 número de registros (ou de entradas, nos índices)
+//   32..40 data de criação (segundos desde 1970)
+//   resto  reservado
+pub const MAGIC: [u8; 4] = *b"AED2";
+// Versão 2: cada registro termina com um CRC32
+pub const VERSAO_FORMATO: u16 = 2;
+pub const TAMANHO_CABECALHO: usize = 64;
+pub const OFFSET_NUM_REGISTROS: u64 = 24;
+// Campos de texto no formato variável (textos.rs); sem ela, texto completado com espaços
+pub const FLAG_TEXTOS_VARIAVEIS: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoArquivo {
+    Dados = 1,
+    Indice = 2,
+    // Pares (valor, chave) de um índice secundário
+    IndiceSecundario = 3,
+    // Páginas de uma árvore B+
+    ArvoreB = 4,
+    // Diretório e baldes de um índice hash extensível
+    Hash = 5,
+    // Pares (chave, posição) do overflow em ordem de chave
+    IndiceOverflow = 6,
+    // Heap com os textos que não cabem no registro
+    Textos = 7,
+}
+
+impl TipoArquivo {
+    fn from_u8(valor: u8) -> Option<Self> {
+        match valor {
+            1 => Some(TipoArquivo::Dados),
+            2 => Some(TipoArquivo::Indice),
+            3 => Some(TipoArquivo::IndiceSecundario),
+            4 => Some(TipoArquivo::ArvoreB),
+            5 => Some(TipoArquivo::Hash),
+            6 => Some(TipoArquivo::IndiceOverflow),
+            7 => Some(TipoArquivo::Textos),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoRegistro {
+    Produto = 1,
+    Pedido = 2,
+}
+
+impl TipoRegistro {
+    fn from_u8(valor: u8) -> Option<Self> {
+        match valor {
+            1 => Some(TipoRegistro::Produto),
+            2 => Some(TipoRegistro::Pedido),
+            _ => None,
+        }
+    }
+
+    // Aceita o nome no singular ou no plural ("produto", "pedidos", ...)
+    pub fn from_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "produto" | "produtos" => Some(TipoRegistro::Produto),
+            "pedido" | "pedidos" => Some(TipoRegistro::Pedido),
+            _ => None,
+        }
+    }
+
+    pub fn nome(&self) -> &'static str {
+        match self {
+            TipoRegistro::Produto => "produto",
+            TipoRegistro::Pedido => "pedido",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cabecalho {
+    pub versao: u16,
+    pub tipo_arquivo: TipoArquivo,
+    pub tipo_registro: TipoRegistro,
+    pub tamanho_registro: u32,
+    pub offset_chave: u32,
+    pub fator_esparsidade: u32,
+    pub formato_textos: FormatoTextos,
+    pub num_registros: u64,
+    pub criado_em: u64,
+}
+
+impl Cabecalho {
+    pub fn novo(tipo_arquivo: TipoArquivo, layout: LayoutRegistro, fator_esparsidade: usize, num_registros: u64) -> Self {
+        let criado_em = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Cabecalho {
+            versao: VERSAO_FORMATO,
+            tipo_arquivo,
+            tipo_registro: layout.tipo,
+            tamanho_registro: layout.tamanho_registro as u32,
+            offset_chave: layout.offset_chave as u32,
+            fator_esparsidade: fator_esparsidade as u32,
+            formato_textos: FormatoTextos::Fixos,
+            num_registros,
+            criado_em,
+        }
+    }
+
+    pub fn com_formato_textos(mut self, formato: FormatoTextos) -> Self {
+        self.formato_textos = formato;
+        self
+    }
+
+    pub fn to_bytes(&self) -> [u8; TAMANHO_CABECALHO] {
+        let mut bytes = [0u8; TAMANHO_CABECALHO];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.versao.to_le_bytes());
+        bytes[6] = self.tipo_arquivo as u8;
+        bytes[7] = self.tipo_registro as u8;
+        bytes[8..12].copy_from_slice(&self.tamanho_registro.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.offset_chave.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.fator_esparsidade.to_le_bytes());
+        if self.formato_textos == FormatoTextos::Variaveis {
+            bytes[20] |= FLAG_TEXTOS_VARIAVEIS;
+        }
+        bytes[24..32].copy_from_slice(&self.num_registros.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.criado_em.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Resultado<Self> {
+        if bytes.len() < TAMANHO_CABECALHO || bytes[0..4] != MAGIC {
+            return Err(Erro::CabecalhoInvalido(
+                "arquivo sem cabeçalho (formato antigo); execute a migração de arquivos legados".to_string(),
+            ));
+        }
+        let versao = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        if versao < VERSAO_FORMATO {
+            return Err(Erro::CabecalhoInvalido(format!(
+                "arquivo na versão de formato {} (atual {}); execute a migração de arquivos antigos",
+                versao, VERSAO_FORMATO
+            )));
+        }
+        if versao != VERSAO_FORMATO {
+            return Err(Erro::CabecalhoInvalido(format!(
+                "versão de formato {} não suportada (esperada {})",
+                versao, VERSAO_FORMATO
+            )));
+        }
+        let tipo_arquivo = TipoArquivo::from_u8(bytes[6])
+            .ok_or_else(|| Erro::CabecalhoInvalido(format!("tipo de arquivo desconhecido: {}", bytes[6])))?;
+        let tipo_registro = TipoRegistro::from_u8(bytes[7])
+            .ok_or_else(|| Erro::CabecalhoInvalido(format!("tipo de registro desconhecido: {}", bytes[7])))?;
+        Ok(Cabecalho {
+            versao,
+            tipo_arquivo,
+            tipo_registro,
+            tamanho_registro: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            offset_chave: u32::from_le_bytes(bytes[12..16].try_into().unwrap()),
+            fator_esparsidade: u32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            formato_textos: if bytes[20] & FLAG_TEXTOS_VARIAVEIS != 0 {
+                FormatoTextos::Variaveis
+            } else {
+                FormatoTextos::Fixos
+            },
+            num_registros: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+            criado_em: u64::from_le_bytes(bytes[32..40].try_into().unwrap()),
+        })
+    }
+
+    // Lê o cabeçalho do início do arquivo, deixando o cursor logo após ele
+    pub fn ler(arquivo: &mut File) -> Resultado<Self> {
+        let mut bytes = [0u8; TAMANHO_CABECALHO];
+        arquivo.seek(SeekFrom::Start(0))?;
+        arquivo.read_exact(&mut bytes).map_err(|_| {
+            Erro::CabecalhoInvalido("arquivo menor que o cabeçalho; execute a migração de arquivos legados".to_string())
+        })?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn escrever(&self, arquivo: &mut File) -> Resultado<()> {
+        arquivo.seek(SeekFrom::Start(0))?;
+        arquivo.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    // Confere se o arquivo é do tipo e layout que o chamador espera
+    pub fn validar(&self, tipo_arquivo: TipoArquivo, layout: LayoutRegistro) -> Resultado<()> {
+        if self.tipo_arquivo != tipo_arquivo {
+            return Err(Erro::CabecalhoInvalido(format!(
+                "esperado arquivo de {:?}, encontrado arquivo de {:?}",
+                tipo_arquivo, self.tipo_arquivo
+            )));
+        }
+        if self.tipo_registro != layout.tipo {
+            return Err(Erro::CabecalhoInvalido(format!(
+                "arquivo contém registros de {}, esperado {}",
+                self.tipo_registro.nome(),
+                layout.tipo.nome()
+            )));
+        }
+        if self.tamanho_registro as usize != layout.tamanho_registro || self.offset_chave as usize != layout.offset_chave {
+            return Err(Erro::CabecalhoInvalido(format!(
+                "layout incompatível: registro de {} bytes com chave em {}, esperado {} bytes com chave em {} (arquivo de um layout anterior? execute a migração)",
+                self.tamanho_registro, self.offset_chave, layout.tamanho_registro, layout.offset_chave
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Lê e valida o cabeçalho de um arquivo de dados, conferindo também se o
+// número de registros bate com o tamanho do arquivo
+pub fn ler_cabecalho_dados(arquivo: &mut File, layout: LayoutRegistro) -> Resultado<Cabecalho> {
+    let cabecalho = Cabecalho::ler(arquivo)?;
+    cabecalho.validar(TipoArquivo::Dados, layout)?;
+    let corpo = arquivo.metadata()?.len() - TAMANHO_CABECALHO as u64;
+    let tamanho = layout.tamanho_registro as u64;
+    if !corpo.is_multiple_of(tamanho) || corpo / tamanho != cabecalho.num_registros {
+        let completos = (corpo / tamanho).min(cabecalho.num_registros);
+        return Err(Erro::RegistroCorrompido {
+            posicao: TAMANHO_CABECALHO as u64 + completos * tamanho,
+            motivo: format!(
+                "cabeçalho indica {} registros, mas o arquivo tem {} bytes de dados",
+                cabecalho.num_registros, corpo
+            ),
+        });
+    }
+    Ok(cabecalho)
+}
+
+pub fn atualizar_num_registros(arquivo: &mut File, num_registros: u64) -> Resultado<()> {
+    arquivo.seek(SeekFrom::Start(OFFSET_NUM_REGISTROS))?;
+    arquivo.write_all(&num_registros.to_le_bytes())?;
+    Ok(())
+}
+
+// Cria (ou trunca) um arquivo de dados vazio, só com o cabeçalho. O heap de
+// textos é criado à parte (textos::criar_arquivo_dados cria os dois).
+pub fn criar_arquivo_dados(caminho: &str, layout: LayoutRegistro, formato: FormatoTextos) -> Resultado<File> {
+    let mut arquivo = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(caminho)?;
+    Cabecalho::novo(TipoArquivo::Dados, layout, 0, 0).com_formato_textos(formato).escrever(&mut arquivo)?;
+    Ok(arquivo)
+}
+
+// Versão gravada no arquivo: 0 para os arquivos anteriores ao cabeçalho
+fn versao_do_arquivo(bytes: &[u8]) -> u16 {
+    if bytes.len() >= TAMANHO_CABECALHO && bytes[0..4] == MAGIC {
+        u16::from_le_bytes(bytes[4..6].try_into().unwrap())
+    } else {
+        0
+    }
+}
+
+// Onde começam os registros e qual o tamanho deles num arquivo de versão antiga.
+// Antes da versão 2 os registros não tinham o CRC no final.
+fn formato_antigo(bytes: &[u8], versao: u16, layout: LayoutRegistro) -> (usize, usize) {
+    match versao {
+        0 => (0, layout.tamanho_registro - TAMANHO_CRC),
+        _ => (TAMANHO_CABECALHO, u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize),
+    }
+}
+
+// Grava o arquivo migrado ao lado (caminho.novo) e o troca pelo antigo com o
+// manifesto do principal da entidade: uma queda no meio deixa o antigo intacto
+fn trocar_migrado(caminho_principal: &str, caminho: &str, bytes: &[u8]) -> Resultado<()> {
+    let temporario = manifesto::caminho_temporario(caminho);
+    manifesto::trocar(caminho_principal, &[caminho], || {
+        let mut arquivo = File::create(&temporario)?;
+        arquivo.write_all(bytes)?;
+        arquivo.sync_all()?;
+        Ok(())
+    })
+}
+
+// Migração de arquivos de dados/overflow de formatos antigos: sem cabeçalho
+// (versão 0) ou sem CRC nos registros (versão 1). `caminho_principal` é o dono
+// do manifesto da entidade. Retorna false se o arquivo já estava no formato atual.
+pub fn migrar_dados_legado(caminho_principal: &str, caminho: &str, layout: LayoutRegistro) -> Resultado<bool> {
+    let antigo = std::fs::read(caminho)?;
+    let versao = versao_do_arquivo(&antigo);
+    if versao == VERSAO_FORMATO {
+        return Ok(false);
+    }
+    if versao > VERSAO_FORMATO {
+        return Err(Erro::CabecalhoInvalido(format!("{} está na versão {}, mais nova que esta", caminho, versao)));
+    }
+    let (inicio, tamanho_antigo) = formato_antigo(&antigo, versao, layout);
+    let corpo = &antigo[inicio..];
+    if tamanho_antigo + TAMANHO_CRC != layout.tamanho_registro || !corpo.len().is_multiple_of(tamanho_antigo) {
+        return Err(Erro::CabecalhoInvalido(format!(
+            "{} tem {} bytes de dados, que não é múltiplo do registro de {} bytes",
+            caminho,
+            corpo.len(),
+            tamanho_antigo
+        )));
+    }
+    let num_registros = (corpo.len() / tamanho_antigo) as u64;
+    let cabecalho = Cabecalho::novo(TipoArquivo::Dados, layout, 0, num_registros);
+    let mut bytes = cabecalho.to_bytes().to_vec();
+    for registro in corpo.chunks_exact(tamanho_antigo) {
+        bytes.extend_from_slice(registro);
+        bytes.extend_from_slice(&crc32(registro).to_le_bytes());
+    }
+    trocar_migrado(caminho_principal, caminho, &bytes)?;
+    Ok(true)
+}
+
+// Migração de índices antigos: sem cabeçalho ([fator u32][n u32][entradas]) ou
+// da versão 1. As posições são recalculadas para o arquivo de dados migrado,
+// que ganhou cabeçalho e registros maiores (com CRC).
+pub fn migrar_indice_legado(caminho_principal: &str, caminho: &str, layout: LayoutRegistro) -> Resultado<bool> {
+    let antigo = std::fs::read(caminho)?;
+    let versao = versao_do_arquivo(&antigo);
+    if versao == VERSAO_FORMATO {
+        return Ok(false);
+    }
+    if versao > VERSAO_FORMATO {
+        return Err(Erro::CabecalhoInvalido(format!("{} está na versão {}, mais nova que esta", caminho, versao)));
+    }
+    let (fator, num_entradas, entradas) = if versao == 0 {
+        if antigo.len() < 8 {
+            return Err(Erro::CabecalhoInvalido(format!("{} é pequeno demais para ser um índice", caminho)));
+        }
+        let fator = u32::from_le_bytes(antigo[0..4].try_into().unwrap()) as usize;
+        let num_entradas = u32::from_le_bytes(antigo[4..8].try_into().unwrap()) as usize;
+        (fator, num_entradas, &antigo[8..])
+    } else {
+        let fator = u32::from_le_bytes(antigo[16..20].try_into().unwrap()) as usize;
+        let num_entradas = u64::from_le_bytes(antigo[24..32].try_into().unwrap()) as usize;
+        (fator, num_entradas, &antigo[TAMANHO_CABECALHO..])
+    };
+    if entradas.len() != num_entradas * 16 {
+        return Err(Erro::CabecalhoInvalido(format!(
+            "{} indica {} entradas mas tem {} bytes",
+            caminho,
+            num_entradas,
+            antigo.len()
+        )));
+    }
+    let (base, tamanho_antigo) = formato_antigo(&antigo, versao, layout);
+    let cabecalho = Cabecalho::novo(TipoArquivo::Indice, layout, fator, num_entradas as u64);
+    let mut bytes = cabecalho.to_bytes().to_vec();
+    for entrada in entradas.chunks_exact(16) {
+        let antiga = u64::from_le_bytes(entrada[8..16].try_into().unwrap());
+        let indice = antiga.saturating_sub(base as u64) / tamanho_antigo as u64;
+        let posicao = TAMANHO_CABECALHO as u64 + indice * layout.tamanho_registro as u64;
+        bytes.extend_from_slice(&entrada[0..8]);
+        bytes.extend_from_slice(&posicao.to_le_bytes());
+    }
+    trocar_migrado(caminho_principal, caminho, &bytes)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indice::{construir_indice_parcial, IndiceParcial};
+    use crate::migracao::layout_original;
+    use crate::pedido::Pedido;
+    use crate::produto::Produto;
+    use crate::registro::Registro;
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_cabecalho_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(nome).to_string_lossy().to_string()
+    }
+
+    fn invalido<T: std::fmt::Debug>(resultado: Resultado<T>) -> String {
+        match resultado {
+            Err(Erro::CabecalhoInvalido(msg)) => msg,
+            outro => panic!("esperado CabecalhoInvalido, veio {:?}", outro),
+        }
+    }
+
+    // Magic, versão, tipo de arquivo, layout e tamanho errados são recusados
+    #[test]
+    fn cabecalho_recusa_arquivos_de_outro_formato() {
+        let bytes = Cabecalho::novo(TipoArquivo::Dados, Produto::LAYOUT, 0, 0).to_bytes();
+        assert_eq!(Cabecalho::from_bytes(&bytes).unwrap().tipo_registro, TipoRegistro::Produto);
+
+        let mut sem_magic = bytes;
+        sem_magic[0..4].copy_from_slice(b"XXXX");
+        assert!(invalido(Cabecalho::from_bytes(&sem_magic)).contains("sem cabeçalho"));
+        for (versao, trecho) in [(1u16, "migração"), (VERSAO_FORMATO + 1, "não suportada")] {
+            let mut outra_versao = bytes;
+            outra_versao[4..6].copy_from_slice(&versao.to_le_bytes());
+            assert!(invalido(Cabecalho::from_bytes(&outra_versao)).contains(trecho));
+        }
+
+        let cabecalho = Cabecalho::from_bytes(&bytes).unwrap();
+        assert!(cabecalho.validar(TipoArquivo::Dados, Produto::LAYOUT).is_ok());
+        invalido(cabecalho.validar(TipoArquivo::Indice, Produto::LAYOUT));
+        invalido(cabecalho.validar(TipoArquivo::Dados, Pedido::LAYOUT));
+        let layout_antigo = LayoutRegistro { tamanho_registro: Produto::TAMANHO_REGISTRO - 3, ..Produto::LAYOUT };
+        assert!(invalido(cabecalho.validar(TipoArquivo::Dados, layout_antigo)).contains("layout incompatível"));
+
+        // Menor que o cabeçalho, e com o contador maior que os registros gravados
+        let caminho = caminho_temporario("curto.dat");
+        std::fs::write(&caminho, &bytes[..10]).unwrap();
+        invalido(Cabecalho::ler(&mut File::open(&caminho).unwrap()));
+        let mut dados = Cabecalho::novo(TipoArquivo::Dados, Produto::LAYOUT, 0, 2).to_bytes().to_vec();
+        dados.extend(vec![0u8; Produto::TAMANHO_REGISTRO]);
+        std::fs::write(&caminho, &dados).unwrap();
+        assert!(matches!(
+            ler_cabecalho_dados(&mut File::open(&caminho).unwrap(), Produto::LAYOUT),
+            Err(Erro::RegistroCorrompido { posicao, .. }) if posicao == (TAMANHO_CABECALHO + Produto::TAMANHO_REGISTRO) as u64
+        ));
+    }
+
+    // Dados e índice sem cabeçalho (versão 0, Produto de 87 bytes com price em
+    // f64) migrados para o layout original: cada registro com o CRC no fim, e as
+    // entradas do índice apontando para eles no arquivo novo
+    #[test]
+    fn migracao_de_arquivos_sem_cabecalho() {
+        let caminho = caminho_temporario("legado.dat");
+        let caminho_indice = caminho_temporario("legado.idx");
+        let layout = layout_original::<Produto>();
+        let registros: Vec<Vec<u8>> = (0..7i64)
+            .map(|i| {
+                let mut bytes = (i * 10).to_le_bytes().to_vec();
+                bytes.extend_from_slice(format!("{:<30}", format!("categoria {}", i)).as_bytes());
+                bytes.extend_from_slice(&(i as f64 * 1.5).to_le_bytes());
+                bytes.extend_from_slice(&[b' '; 40]);
+                bytes.push(b'\n');
+                bytes
+            })
+            .collect();
+        let tamanho_antigo = 87;
+        assert_eq!(layout.tamanho_registro, tamanho_antigo + TAMANHO_CRC);
+        let dados = registros.concat();
+        let mut indice = 2u32.to_le_bytes().to_vec();
+        indice.extend(4u32.to_le_bytes());
+        for (i, registro) in registros.iter().enumerate().step_by(2) {
+            indice.extend_from_slice(&registro[0..8]);
+            indice.extend(((i * tamanho_antigo) as u64).to_le_bytes());
+        }
+        std::fs::write(&caminho, &dados).unwrap();
+        std::fs::write(&caminho_indice, &indice).unwrap();
+
+        // Sem conseguir gravar o temporário, o arquivo antigo fica como estava
+        let temporario = manifesto::caminho_temporario(&caminho);
+        std::fs::create_dir_all(&temporario).unwrap();
+        assert!(migrar_dados_legado(&caminho, &caminho, layout).is_err());
+        assert_eq!(std::fs::read(&caminho).unwrap(), dados);
+        std::fs::remove_dir(&temporario).unwrap();
+        manifesto::recuperar(&caminho).unwrap();
+
+        assert!(migrar_dados_legado(&caminho, &caminho, layout).unwrap());
+        assert!(migrar_indice_legado(&caminho, &caminho_indice, layout).unwrap());
+        assert!(!migrar_dados_legado(&caminho, &caminho, layout).unwrap());
+        assert!(!migrar_indice_legado(&caminho, &caminho_indice, layout).unwrap());
+
+        let migrado = std::fs::read(&caminho).unwrap();
+        let cabecalho = Cabecalho::from_bytes(&migrado).unwrap();
+        assert_eq!((cabecalho.num_registros, cabecalho.tamanho_registro as usize), (7, layout.tamanho_registro));
+        for (novo, antigo) in migrado[TAMANHO_CABECALHO..].chunks(layout.tamanho_registro).zip(&registros) {
+            assert_eq!(&novo[..tamanho_antigo], antigo.as_slice());
+            assert_eq!(novo[tamanho_antigo..], crc32(antigo).to_le_bytes());
+        }
+        let carregado = IndiceParcial::carregar_binario(&caminho_indice, layout).unwrap();
+        let reconstruido = construir_indice_parcial(&caminho, 2, layout).unwrap();
+        assert_eq!(carregado.fator_esparsidade, 2);
+        let pares = |indice: &IndiceParcial| indice.entradas.iter().map(|e| (e.chave, e.posicao)).collect::<Vec<_>>();
+        assert_eq!(pares(&carregado), pares(&reconstruido));
+        assert!(!std::path::Path::new(&temporario).exists());
+        assert_eq!(manifesto::Manifesto::ler(&caminho).unwrap().estado, manifesto::Estado::Concluido);
+    }
+}
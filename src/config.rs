@@ -0,0 +1,438 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::arquivo::PoliticaDuplicatas;
+use crate::arvore_b::{ORDEM_MAXIMA, ORDEM_MINIMA, ORDEM_PADRAO};
+use crate::indice_hash::{CAPACIDADE_MAXIMA, CAPACIDADE_MINIMA, CAPACIDADE_PADRAO};
+use crate::cabecalho::TipoRegistro;
+use crate::erro::{Erro, Resultado};
+use crate::importacao::MapeamentoColunas;
+use crate::integridade::PoliticaRemocao;
+use crate::ordenacao_externa::MEMORIA_ORDENACAO_PADRAO;
+use crate::pedido::Pedido;
+use crate::produto::Produto;
+use crate::registro::Registro;
+use crate::textos::{FormatoTextos, PoliticasTexto, RegrasTextos};
+
+// Arquivo de configuração procurado no diretório atual quando nenhum é indicado
+pub const ARQUIVO_CONFIGURACAO_PADRAO: &str = "aed2.toml";
+
+// Exemplo de aed2.toml (todos os campos são opcionais):
+//
+//   diretorio_dados = "/var/lib/aed2"
+//   csv = "jewelry.csv"
+//   fator_esparsidade = 10
+//   mapeamento = "colunas.toml"   # formato e colunas do CSV (ver importacao.rs)
+//   memoria_ordenacao_mb = 64     # acima disso importação e reconstrução ordenam em disco
+//   duplicatas = "rejeitar"       # ou "substituir": inserir uma chave existente regrava o registro
+//   remocao_produtos = "restringir"
+//                                 # produto com pedidos: "restringir" recusa a remoção, "cascata"
+//                                 # remove os pedidos junto, "ignorar" deixa os pedidos órfãos
+//
+//   [produtos]
+//   dados = "produtos.dat"
+//   overflow = "produtos_overflow.dat"
+//   indice = "indice_produtos.bin"
+//   indices_secundarios = ["material", "stone"]   # padrão ["material"]
+//   tipo_indice = "arvore"        # consultas pela árvore B+ (produtos.dat.arvore) ou
+//                                 # "hash" (produtos.dat.hash); padrão "parcial"
+//   ordem_arvore = 64             # chaves por página da árvore
+//   capacidade_hash = 64          # entradas por balde do índice hash
+//   textos = "fixos"              # padrão, textos com largura fixa; "variaveis" grava os
+//                                 # longos no heap (vale para os arquivos criados daqui em diante)
+//
+//   [produtos.politica_textos]    # texto maior que o campo: "rejeitar" (padrão),
+//   padrao = "rejeitar"           # "truncar" (corta no último caractere que cabe)
+//   category_alias = "avisar"     # ou "avisar" (corta e mostra o corte)
+//
+//   [pedidos]
+//   dados = "pedidos.dat"
+//   indices_secundarios = ["user_id"]             # padrão; [] desliga
+//
+// Nomes relativos são resolvidos a partir de diretorio_dados. As variáveis
+// AED2_CONFIG, AED2_DIR_DADOS, AED2_CSV, AED2_FATOR, AED2_MAPEAMENTO,
+// AED2_MEMORIA_MB, AED2_DUPLICATAS e AED2_REMOCAO_PRODUTOS sobrescrevem o arquivo. O mapeamento é relativo ao diretório atual.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Configuracao {
+    pub diretorio_dados: PathBuf,
+    pub csv: String,
+    pub fator_esparsidade: usize,
+    pub mapeamento: Option<String>,
+    pub memoria_ordenacao_mb: usize,
+    pub duplicatas: PoliticaDuplicatas,
+    pub remocao_produtos: PoliticaRemocao,
+    pub produtos: ArquivosEntidade,
+    pub pedidos: ArquivosEntidade,
+}
+
+// Nomes vazios (não informados no arquivo) são completados com o padrão da entidade
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArquivosEntidade {
+    pub dados: String,
+    pub overflow: String,
+    pub indice: String,
+    // Campos com índice secundário (pedidos.dat.user_id.idx); None usa o padrão da entidade
+    pub indices_secundarios: Option<Vec<String>>,
+    pub tipo_indice: TipoIndice,
+    pub ordem_arvore: Option<usize>,
+    pub capacidade_hash: Option<usize>,
+    pub textos: FormatoTextos,
+    pub politica_textos: PoliticasTexto,
+}
+
+// Índice usado nas consultas por chave
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TipoIndice {
+    // IndiceParcial do principal mais varredura do overflow
+    #[default]
+    Parcial,
+    // Árvore B+ em disco cobrindo principal e overflow
+    Arvore,
+    // Hash extensível em disco cobrindo principal e overflow
+    Hash,
+}
+
+// Caminhos já resolvidos dos arquivos de uma entidade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaminhosEntidade {
+    pub dados: String,
+    pub overflow: String,
+    pub indice: String,
+    pub indices_secundarios: Vec<String>,
+    // Ordem da árvore B+ quando ela é o índice das consultas
+    pub arvore: Option<usize>,
+    // Capacidade dos baldes quando o índice hash é o das consultas
+    pub hash: Option<usize>,
+    // Formato dos textos nos arquivos criados (overflow novo, importação,
+    // reconstrução) e o que fazer com textos de entrada maiores que o campo
+    pub textos: RegrasTextos,
+}
+
+impl ArquivosEntidade {
+    fn padrao(tipo: TipoRegistro) -> Self {
+        let (dados, overflow, indice, secundario) = match tipo {
+            TipoRegistro::Produto => ("produtos.dat", "produtos_overflow.dat", "indice_produtos.bin", "material"),
+            TipoRegistro::Pedido => ("pedidos.dat", "pedidos_overflow.dat", "indice_pedidos.bin", "user_id"),
+        };
+        ArquivosEntidade {
+            dados: dados.to_string(),
+            overflow: overflow.to_string(),
+            indice: indice.to_string(),
+            indices_secundarios: Some(vec![secundario.to_string()]),
+            tipo_indice: TipoIndice::Parcial,
+            ordem_arvore: Some(ORDEM_PADRAO),
+            capacidade_hash: Some(CAPACIDADE_PADRAO),
+            textos: FormatoTextos::default(),
+            politica_textos: PoliticasTexto::default(),
+        }
+    }
+
+    fn completar(&mut self, tipo: TipoRegistro) {
+        let padrao = ArquivosEntidade::padrao(tipo);
+        if self.dados.is_empty() {
+            self.dados = padrao.dados;
+        }
+        if self.overflow.is_empty() {
+            self.overflow = padrao.overflow;
+        }
+        if self.indice.is_empty() {
+            self.indice = padrao.indice;
+        }
+        if self.indices_secundarios.is_none() {
+            self.indices_secundarios = padrao.indices_secundarios;
+        }
+        if self.ordem_arvore.is_none() {
+            self.ordem_arvore = padrao.ordem_arvore;
+        }
+        if self.capacidade_hash.is_none() {
+            self.capacidade_hash = padrao.capacidade_hash;
+        }
+    }
+
+    fn validar(&self, tipo: TipoRegistro) -> Resultado<()> {
+        if let Some(ordem) = self.ordem_arvore
+            && !(ORDEM_MINIMA..=ORDEM_MAXIMA).contains(&ordem)
+        {
+            return Err(Erro::Configuracao(format!(
+                "ordem_arvore de {} deve estar entre {} e {}",
+                tipo.nome(),
+                ORDEM_MINIMA,
+                ORDEM_MAXIMA
+            )));
+        }
+        if let Some(capacidade) = self.capacidade_hash
+            && !(CAPACIDADE_MINIMA..=CAPACIDADE_MAXIMA).contains(&capacidade)
+        {
+            return Err(Erro::Configuracao(format!(
+                "capacidade_hash de {} deve estar entre {} e {}",
+                tipo.nome(),
+                CAPACIDADE_MINIMA,
+                CAPACIDADE_MAXIMA
+            )));
+        }
+        let campos_texto = match tipo {
+            TipoRegistro::Produto => Produto::CAMPOS_TEXTO,
+            TipoRegistro::Pedido => Pedido::CAMPOS_TEXTO,
+        };
+        for campo in self.politica_textos.campos.keys() {
+            if !campos_texto.iter().any(|(nome, _)| nome == campo) {
+                let nomes: Vec<&str> = campos_texto.iter().map(|(nome, _)| *nome).collect();
+                return Err(Erro::Configuracao(format!(
+                    "politica_textos de {}: campo de texto desconhecido {} (use padrao, {})",
+                    tipo.nome(),
+                    campo,
+                    nomes.join(", ")
+                )));
+            }
+        }
+        let campos = match tipo {
+            TipoRegistro::Produto => Produto::CAMPOS_SECUNDARIOS,
+            TipoRegistro::Pedido => Pedido::CAMPOS_SECUNDARIOS,
+        };
+        for campo in self.indices_secundarios.iter().flatten() {
+            if !campos.iter().any(|(nome, _)| nome == campo) {
+                let nomes: Vec<&str> = campos.iter().map(|(nome, _)| *nome).collect();
+                return Err(Erro::Configuracao(format!(
+                    "indices_secundarios de {}: campo desconhecido {} (use {})",
+                    tipo.nome(),
+                    campo,
+                    nomes.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Configuracao {
+    fn default() -> Self {
+        Configuracao {
+            diretorio_dados: PathBuf::from("."),
+            csv: "jewelry.csv".to_string(),
+            fator_esparsidade: 10,
+            mapeamento: None,
+            memoria_ordenacao_mb: MEMORIA_ORDENACAO_PADRAO / (1024 * 1024),
+            duplicatas: PoliticaDuplicatas::default(),
+            remocao_produtos: PoliticaRemocao::default(),
+            produtos: ArquivosEntidade::padrao(TipoRegistro::Produto),
+            pedidos: ArquivosEntidade::padrao(TipoRegistro::Pedido),
+        }
+    }
+}
+
+impl Configuracao {
+    // Carrega a configuração: arquivo indicado, senão $AED2_CONFIG, senão aed2.toml
+    // no diretório atual (se existir), senão os valores padrão. Em seguida aplica
+    // as variáveis de ambiente.
+    pub fn carregar(caminho: Option<&str>) -> Resultado<Self> {
+        Self::carregar_com(caminho, |nome| std::env::var(nome).ok())
+    }
+
+    // Como carregar, lendo as variáveis por `variavel` em vez do ambiente do processo
+    pub fn carregar_com(caminho: Option<&str>, variavel: impl Fn(&str) -> Option<String>) -> Resultado<Self> {
+        let caminho = caminho.map(|c| c.to_string()).or_else(|| variavel("AED2_CONFIG"));
+        let mut config = match caminho {
+            Some(c) => Self::ler_arquivo(&c)?,
+            None if Path::new(ARQUIVO_CONFIGURACAO_PADRAO).exists() => {
+                Self::ler_arquivo(ARQUIVO_CONFIGURACAO_PADRAO)?
+            }
+            None => Configuracao::default(),
+        };
+        config.aplicar_ambiente(variavel)?;
+        Ok(config)
+    }
+
+    pub fn ler_arquivo(caminho: &str) -> Resultado<Self> {
+        let texto = std::fs::read_to_string(caminho)?;
+        let mut config: Configuracao = toml::from_str(&texto)
+            .map_err(|e| Erro::Configuracao(format!("{}: {}", caminho, e)))?;
+        // Nomes não informados no arquivo ficam com o padrão da entidade
+        config.produtos.completar(TipoRegistro::Produto);
+        config.pedidos.completar(TipoRegistro::Pedido);
+        config.validar()?;
+        Ok(config)
+    }
+
+    fn aplicar_ambiente(&mut self, variavel: impl Fn(&str) -> Option<String>) -> Resultado<()> {
+        if let Some(dir) = variavel("AED2_DIR_DADOS") {
+            self.diretorio_dados = PathBuf::from(dir);
+        }
+        if let Some(csv) = variavel("AED2_CSV") {
+            self.csv = csv;
+        }
+        if let Some(fator) = variavel("AED2_FATOR") {
+            self.fator_esparsidade = fator
+                .parse()
+                .map_err(|_| Erro::Configuracao(format!("AED2_FATOR inválido: {}", fator)))?;
+        }
+        if let Some(mapeamento) = variavel("AED2_MAPEAMENTO") {
+            self.mapeamento = Some(mapeamento);
+        }
+        if let Some(memoria) = variavel("AED2_MEMORIA_MB") {
+            self.memoria_ordenacao_mb = memoria
+                .parse()
+                .map_err(|_| Erro::Configuracao(format!("AED2_MEMORIA_MB inválido: {}", memoria)))?;
+        }
+        if let Some(duplicatas) = variavel("AED2_DUPLICATAS") {
+            self.duplicatas = PoliticaDuplicatas::from_nome(&duplicatas)
+                .ok_or_else(|| Erro::Configuracao(format!("AED2_DUPLICATAS inválido: {} (use rejeitar ou substituir)", duplicatas)))?;
+        }
+        if let Some(remocao) = variavel("AED2_REMOCAO_PRODUTOS") {
+            self.remocao_produtos = PoliticaRemocao::from_nome(&remocao).ok_or_else(|| {
+                Erro::Configuracao(format!("AED2_REMOCAO_PRODUTOS inválido: {} (use restringir, cascata ou ignorar)", remocao))
+            })?;
+        }
+        self.validar()
+    }
+
+    fn validar(&self) -> Resultado<()> {
+        if self.fator_esparsidade == 0 {
+            return Err(Erro::Configuracao("fator_esparsidade deve ser maior que zero".to_string()));
+        }
+        if self.memoria_ordenacao_mb == 0 {
+            return Err(Erro::Configuracao("memoria_ordenacao_mb deve ser maior que zero".to_string()));
+        }
+        self.produtos.validar(TipoRegistro::Produto)?;
+        self.pedidos.validar(TipoRegistro::Pedido)
+    }
+
+    pub fn arquivos(&self, tipo: TipoRegistro) -> &ArquivosEntidade {
+        match tipo {
+            TipoRegistro::Produto => &self.produtos,
+            TipoRegistro::Pedido => &self.pedidos,
+        }
+    }
+
+    pub fn arquivos_mut(&mut self, tipo: TipoRegistro) -> &mut ArquivosEntidade {
+        match tipo {
+            TipoRegistro::Produto => &mut self.produtos,
+            TipoRegistro::Pedido => &mut self.pedidos,
+        }
+    }
+
+    pub fn caminhos(&self, tipo: TipoRegistro) -> CaminhosEntidade {
+        let arquivos = self.arquivos(tipo);
+        CaminhosEntidade {
+            dados: self.resolver(&arquivos.dados),
+            overflow: self.resolver(&arquivos.overflow),
+            indice: self.resolver(&arquivos.indice),
+            indices_secundarios: arquivos.indices_secundarios.clone().unwrap_or_default(),
+            arvore: (arquivos.tipo_indice == TipoIndice::Arvore).then(|| arquivos.ordem_arvore.unwrap_or(ORDEM_PADRAO)),
+            hash: (arquivos.tipo_indice == TipoIndice::Hash).then(|| arquivos.capacidade_hash.unwrap_or(CAPACIDADE_PADRAO)),
+            textos: RegrasTextos { formato: arquivos.textos, politicas: arquivos.politica_textos.clone() },
+        }
+    }
+
+    pub fn caminho_csv(&self) -> String {
+        self.resolver(&self.csv)
+    }
+
+    pub fn memoria_ordenacao(&self) -> usize {
+        self.memoria_ordenacao_mb.saturating_mul(1024 * 1024)
+    }
+
+    // Mapeamento de colunas do CSV; sem arquivo configurado vale o layout do Kaggle
+    pub fn mapeamento_colunas(&self) -> Resultado<MapeamentoColunas> {
+        match &self.mapeamento {
+            Some(caminho) => MapeamentoColunas::carregar(caminho),
+            None => Ok(MapeamentoColunas::default()),
+        }
+    }
+
+    // Junta o nome ao diretório de dados; caminhos absolutos ficam como estão
+    pub fn resolver(&self, nome: &str) -> String {
+        self.diretorio_dados.join(nome).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn caminho_temporario(nome: &str, conteudo: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_config_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let caminho = dir.join(nome).to_string_lossy().to_string();
+        std::fs::write(&caminho, conteudo).unwrap();
+        caminho
+    }
+
+    fn carregar(caminho: &str, variaveis: &[(&str, &str)]) -> Resultado<Configuracao> {
+        let variaveis: HashMap<String, String> = variaveis.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect();
+        Configuracao::carregar_com(Some(caminho), |nome| variaveis.get(nome).cloned())
+    }
+
+    fn mensagem(resultado: Resultado<Configuracao>) -> String {
+        match resultado {
+            Err(Erro::Configuracao(msg)) => msg,
+            outro => panic!("esperado Erro::Configuracao, veio {:?}", outro),
+        }
+    }
+
+    // Arquivo completado com os padrões, variáveis por cima dele e cada valor inválido recusado
+    #[test]
+    fn arquivo_e_variaveis_de_ambiente() {
+        let caminho = caminho_temporario(
+            "aed2.toml",
+            "diretorio_dados = \"/dados\"\nfator_esparsidade = 4\nduplicatas = \"substituir\"\n\
+             [produtos]\ndados = \"p.dat\"\ntipo_indice = \"arvore\"\nordem_arvore = 8\n\
+             [pedidos]\nindices_secundarios = []\ntextos = \"fixos\"\n",
+        );
+        let config = carregar(&caminho, &[]).unwrap();
+        assert_eq!((config.fator_esparsidade, config.duplicatas), (4, PoliticaDuplicatas::Substituir));
+        assert_eq!(config.remocao_produtos, PoliticaRemocao::Restringir);
+        let produtos = config.caminhos(TipoRegistro::Produto);
+        assert_eq!(produtos.dados, "/dados/p.dat");
+        assert_eq!(produtos.overflow, "/dados/produtos_overflow.dat");
+        assert_eq!((produtos.arvore, produtos.hash), (Some(8), None));
+        assert_eq!(produtos.indices_secundarios, vec!["material".to_string()]);
+        let pedidos = config.caminhos(TipoRegistro::Pedido);
+        assert!(pedidos.indices_secundarios.is_empty());
+        assert_eq!(pedidos.textos.formato, FormatoTextos::Fixos);
+
+        let config = carregar(
+            &caminho,
+            &[
+                ("AED2_DIR_DADOS", "/outro"),
+                ("AED2_CSV", "outro.csv"),
+                ("AED2_FATOR", "7"),
+                ("AED2_MEMORIA_MB", "2"),
+                ("AED2_DUPLICATAS", "rejeitar"),
+                ("AED2_REMOCAO_PRODUTOS", "cascata"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.caminho_csv(), "/outro/outro.csv");
+        assert_eq!(config.caminhos(TipoRegistro::Produto).dados, "/outro/p.dat");
+        assert_eq!((config.fator_esparsidade, config.memoria_ordenacao()), (7, 2 * 1024 * 1024));
+        assert_eq!((config.duplicatas, config.remocao_produtos), (PoliticaDuplicatas::Rejeitar, PoliticaRemocao::Cascata));
+        let sem_caminho: HashMap<&str, String> = HashMap::from([("AED2_CONFIG", caminho.clone())]);
+        let config = Configuracao::carregar_com(None, |nome| sem_caminho.get(nome).cloned()).unwrap();
+        assert_eq!(config.fator_esparsidade, 4);
+
+        assert!(mensagem(carregar(&caminho, &[("AED2_FATOR", "0")])).contains("fator_esparsidade"));
+        assert!(mensagem(carregar(&caminho, &[("AED2_FATOR", "dez")])).contains("AED2_FATOR"));
+        assert!(mensagem(carregar(&caminho, &[("AED2_MEMORIA_MB", "0")])).contains("memoria_ordenacao_mb"));
+        assert!(mensagem(carregar(&caminho, &[("AED2_DUPLICATAS", "talvez")])).contains("AED2_DUPLICATAS"));
+        assert!(mensagem(carregar(&caminho, &[("AED2_REMOCAO_PRODUTOS", "x")])).contains("AED2_REMOCAO_PRODUTOS"));
+
+        let invalidos = [
+            ("memoria.toml", "memoria_ordenacao_mb = 0", "memoria_ordenacao_mb"),
+            ("ordem.toml", "[produtos]\nordem_arvore = 1", "ordem_arvore"),
+            ("capacidade.toml", "[pedidos]\ncapacidade_hash = 100000000", "capacidade_hash"),
+            ("campo.toml", "fator = 3", "fator"),
+            ("entidade.toml", "[produtos]\nindice_hash = true", "indice_hash"),
+            ("secundario.toml", "[pedidos]\nindices_secundarios = [\"cor\"]", "cor"),
+            ("politica.toml", "[produtos.politica_textos]\npreco = \"truncar\"", "preco"),
+        ];
+        for (nome, conteudo, trecho) in invalidos {
+            let msg = mensagem(carregar(&caminho_temporario(nome, conteudo), &[]));
+            assert!(msg.contains(trecho), "{}: {}", nome, msg);
+        }
+    }
+}
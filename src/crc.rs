@@ -0,0 +1,27 @@
+// CRC-32 (polinômio IEEE 802.3, o mesmo do zip/png), calculado byte a byte com tabela
+
+const fn gerar_tabela() -> [u32; 256] {
+    let mut tabela = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        tabela[i] = c;
+        i += 1;
+    }
+    tabela
+}
+
+const TABELA: [u32; 256] = gerar_tabela();
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in bytes {
+        crc = TABELA[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xFFFF_FFFF
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Data e hora de um evento: instante em milissegundos desde 1970-01-01 UTC e o
+// fuso em que ele foi informado, para mostrar de volta como veio do CSV
+// ("2018-12-01 11:40:29+00:00"). No registro ocupa TAMANHO_DATA_HORA bytes:
+// millis (i64) e fuso em minutos (i16).
+//
+// Igualdade e ordem são pelo instante: 11:40+00:00 e 08:40-03:00 são iguais.
+#[derive(Clone, Copy, Default)]
+pub struct DataHora {
+    pub millis: i64,
+    pub fuso_minutos: i16,
+}
+
+pub const TAMANHO_DATA_HORA: usize = 10;
+
+const MILLIS_POR_DIA: i64 = 86_400_000;
+// Maior fuso aceito, como no ISO 8601
+const FUSO_MAXIMO_MINUTOS: i16 = 18 * 60;
+
+impl DataHora {
+    pub fn to_bytes(&self) -> [u8; TAMANHO_DATA_HORA] {
+        let mut bytes = [0u8; TAMANHO_DATA_HORA];
+        bytes[0..8].copy_from_slice(&self.millis.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.fuso_minutos.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let millis = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let fuso_minutos = i16::from_le_bytes(bytes[8..10].try_into().unwrap());
+        if fuso_minutos.abs() > FUSO_MAXIMO_MINUTOS {
+            return Err(format!("fuso de {} minutos fora do intervalo", fuso_minutos));
+        }
+        Ok(DataHora { millis, fuso_minutos })
+    }
+}
+
+impl PartialEq for DataHora {
+    fn eq(&self, outro: &Self) -> bool {
+        self.millis == outro.millis
+    }
+}
+
+impl Eq for DataHora {}
+
+impl PartialOrd for DataHora {
+    fn partial_cmp(&self, outro: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(outro))
+    }
+}
+
+impl Ord for DataHora {
+    fn cmp(&self, outro: &Self) -> std::cmp::Ordering {
+        self.millis.cmp(&outro.millis)
+    }
+}
+
+// "AAAA-MM-DD HH:MM:SS+HH:MM" no fuso original; milissegundos só quando houver
+impl fmt::Display for DataHora {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let local = self.millis + self.fuso_minutos as i64 * 60_000;
+        let (ano, mes, dia) = data_civil(local.div_euclid(MILLIS_POR_DIA));
+        let no_dia = local.rem_euclid(MILLIS_POR_DIA);
+        let segundos = no_dia / 1000;
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", ano, mes, dia, segundos / 3600, segundos / 60 % 60, segundos % 60)?;
+        if no_dia % 1000 != 0 {
+            write!(f, ".{:03}", no_dia % 1000)?;
+        }
+        let sinal = if self.fuso_minutos < 0 { '-' } else { '+' };
+        let fuso = self.fuso_minutos.unsigned_abs();
+        write!(f, "{}{:02}:{:02}", sinal, fuso / 60, fuso % 60)
+    }
+}
+
+// Nos {:?} dos registros aparece como no CSV
+impl fmt::Debug for DataHora {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Aceita "AAAA-MM-DD", "AAAA-MM-DD HH:MM:SS" (ou com 'T' no lugar do espaço),
+// com fração de segundo opcional e fuso "+HH:MM", "+HHMM", "+HH", "Z" ou "UTC".
+// Sem fuso, vale UTC.
+impl FromStr for DataHora {
+    type Err = String;
+
+    fn from_str(texto: &str) -> Result<Self, String> {
+        let invalida = || format!("data e hora inválida: {:?} (esperado AAAA-MM-DD HH:MM:SS+HH:MM)", texto);
+        let t = texto.trim();
+        if t.len() < 10 || !t.is_char_boundary(10) {
+            return Err(invalida());
+        }
+        let (data, resto) = t.split_at(10);
+        let ano = numero(data, 0..4).ok_or_else(invalida)?;
+        let mes = numero(data, 5..7).ok_or_else(invalida)?;
+        let dia = numero(data, 8..10).ok_or_else(invalida)?;
+        if &data[4..5] != "-" || &data[7..8] != "-" || !(1..=12).contains(&mes) || dia < 1 || dia > dias_no_mes(ano, mes) {
+            return Err(invalida());
+        }
+
+        let mut millis_no_dia = 0;
+        let mut fuso = resto;
+        if let Some(hora) = resto.strip_prefix([' ', 'T']) {
+            if !hora.is_char_boundary(8) || hora.get(2..3) != Some(":") || hora.get(5..6) != Some(":") {
+                return Err(invalida());
+            }
+            let h = numero(hora, 0..2).ok_or_else(invalida)?;
+            let m = numero(hora, 3..5).ok_or_else(invalida)?;
+            let s = numero(hora, 6..8).ok_or_else(invalida)?;
+            if h > 23 || m > 59 || s > 59 {
+                return Err(invalida());
+            }
+            millis_no_dia = ((h * 60 + m) * 60 + s) * 1000;
+            fuso = &hora[8..];
+            if let Some(fracao) = fuso.strip_prefix('.') {
+                let digitos = fracao.bytes().take_while(u8::is_ascii_digit).count();
+                if digitos == 0 {
+                    return Err(invalida());
+                }
+                // Só os milissegundos ficam; o resto da fração é descartado
+                let ms = format!("{:0<3}", &fracao[..digitos.min(3)]);
+                millis_no_dia += ms.parse::<i64>().map_err(|_| invalida())?;
+                fuso = &fracao[digitos..];
+            }
+        }
+        let fuso_minutos = interpretar_fuso(fuso.trim_start()).ok_or_else(invalida)?;
+        let millis = dias_desde_1970(ano, mes, dia) * MILLIS_POR_DIA + millis_no_dia - fuso_minutos as i64 * 60_000;
+        Ok(DataHora { millis, fuso_minutos })
+    }
+}
+
+// Dígitos decimais em `faixa` do texto, sem sinal
+fn numero(texto: &str, faixa: std::ops::Range<usize>) -> Option<i64> {
+    let parte = texto.get(faixa)?;
+    if !parte.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    parte.parse().ok()
+}
+
+fn interpretar_fuso(texto: &str) -> Option<i16> {
+    if texto.is_empty() || texto == "Z" || texto == "UTC" {
+        return Some(0);
+    }
+    let sinal = match texto.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let resto = &texto[1..];
+    let (h, m) = match resto.len() {
+        2 => (numero(resto, 0..2)?, 0),
+        4 => (numero(resto, 0..2)?, numero(resto, 2..4)?),
+        5 if resto.get(2..3) == Some(":") => (numero(resto, 0..2)?, numero(resto, 3..5)?),
+        _ => return None,
+    };
+    let minutos = (h * 60 + m) as i16;
+    if m > 59 || minutos > FUSO_MAXIMO_MINUTOS {
+        return None;
+    }
+    Some(sinal * minutos)
+}
+
+fn bissexto(ano: i64) -> bool {
+    ano % 4 == 0 && (ano % 100 != 0 || ano % 400 == 0)
+}
+
+fn dias_no_mes(ano: i64, mes: i64) -> i64 {
+    match mes {
+        2 if bissexto(ano) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Dias entre 1970-01-01 e a data do calendário gregoriano (negativo antes de 1970).
+// Conta em eras de 400 anos com o ano começando em março, para o 29 de fevereiro
+// cair no fim.
+fn dias_desde_1970(ano: i64, mes: i64, dia: i64) -> i64 {
+    let ano = if mes <= 2 { ano - 1 } else { ano };
+    let era = ano.div_euclid(400);
+    let ano_da_era = ano - era * 400;
+    let dia_do_ano = (153 * ((mes + 9) % 12) + 2) / 5 + dia - 1;
+    let dia_da_era = ano_da_era * 365 + ano_da_era / 4 - ano_da_era / 100 + dia_do_ano;
+    era * 146_097 + dia_da_era - 719_468
+}
+
+// Inverso de dias_desde_1970
+fn data_civil(dias: i64) -> (i64, i64, i64) {
+    let dias = dias + 719_468;
+    let era = dias.div_euclid(146_097);
+    let dia_da_era = dias - era * 146_097;
+    let ano_da_era = (dia_da_era - dia_da_era / 1460 + dia_da_era / 36_524 - dia_da_era / 146_096) / 365;
+    let dia_do_ano = dia_da_era - (365 * ano_da_era + ano_da_era / 4 - ano_da_era / 100);
+    let mes_de_marco = (5 * dia_do_ano + 2) / 153;
+    let dia = dia_do_ano - (153 * mes_de_marco + 2) / 5 + 1;
+    let mes = if mes_de_marco < 10 { mes_de_marco + 3 } else { mes_de_marco - 9 };
+    let ano = ano_da_era + era * 400;
+    (if mes <= 2 { ano + 1 } else { ano }, mes, dia)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(texto: &str) -> DataHora {
+        texto.parse().unwrap()
+    }
+
+    // O texto volta como veio, o instante independe do fuso e datas impossíveis são recusadas
+    #[test]
+    fn data_hora_volta_no_fuso_original() {
+        for texto in ["2018-12-01 11:40:29+00:00", "1969-12-31 23:59:59.250-03:30", "2024-02-29 00:00:00+14:00"] {
+            let lida = data(texto);
+            assert_eq!(lida.to_string(), texto);
+            assert_eq!(DataHora::from_bytes(&lida.to_bytes()).unwrap().to_string(), texto);
+        }
+        assert_eq!(data("2018-12-01 11:40:29+00:00").millis, 1_543_664_429_000);
+        assert_eq!(data("2018-12-01T08:40:29-0300"), data("2018-12-01 11:40:29 UTC"));
+        assert_eq!(data("2018-12-01").to_string(), "2018-12-01 00:00:00+00:00");
+        assert!(data("2018-12-02 00:00:00+01:00") < data("2018-12-01 23:59:59+00:00"));
+
+        for invalida in ["", "2018-13-01", "2019-02-29 10:00:00", "2018-12-01 24:00:00", "2018-12-01 10:00:00+19:00", "01/12/2018", "2018-12-01 10:00", "2018-12-01 1é:00:00"] {
+            assert!(invalida.parse::<DataHora>().is_err(), "{:?} deveria ser recusada", invalida);
+        }
+    }
+}
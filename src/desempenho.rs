@@ -0,0 +1,135 @@
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::arquivo::{percorrer_validos, ArquivoSequencial};
+use crate::erro::Resultado;
+use crate::indice::IndiceParcial;
+use crate::registro::Registro;
+
+// Comparação de tempo de consulta entre o índice parcial (busca no principal e
+// varredura do overflow), a árvore B+ e o hash, com as mesmas chaves sorteadas dos
+// registros válidos. Os tempos incluem abrir e ler os arquivos a cada consulta,
+// como nas consultas normais.
+
+#[derive(Debug, Clone)]
+pub struct Medicao {
+    pub nome: &'static str,
+    pub consultas: usize,
+    pub encontrados: usize,
+    pub total: Duration,
+}
+
+impl Medicao {
+    pub fn media_micros(&self) -> f64 {
+        if self.consultas == 0 {
+            return 0.0;
+        }
+        self.total.as_secs_f64() * 1e6 / self.consultas as f64
+    }
+}
+
+impl fmt::Display for Medicao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<14} {:>8} consultas {:>8} encontradas {:>10.1} µs/consulta {:>10.1} ms no total",
+            self.nome,
+            self.consultas,
+            self.encontrados,
+            self.media_micros(),
+            self.total.as_secs_f64() * 1e3
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComparacaoIndices {
+    pub parcial: Medicao,
+    pub arvore: Medicao,
+    pub entradas_indice_parcial: usize,
+    pub altura_arvore: usize,
+    pub paginas_arvore: u64,
+    pub hash: Medicao,
+    pub baldes_hash: usize,
+    pub profundidade_hash: u8,
+}
+
+impl fmt::Display for ComparacaoIndices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.parcial)?;
+        writeln!(f, "{}", self.arvore)?;
+        writeln!(f, "{}", self.hash)?;
+        writeln!(f, "Entradas no índice parcial: {}", self.entradas_indice_parcial)?;
+        writeln!(f, "Árvore B+: altura {}, {} páginas", self.altura_arvore, self.paginas_arvore)?;
+        write!(f, "Hash: {} baldes, profundidade global {}", self.baldes_hash, self.profundidade_hash)
+    }
+}
+
+// Sorteia `consultas` chaves (sempre as mesmas para os mesmos dados) e consulta
+// cada uma pelos três índices. A árvore e o hash são construídos com `ordem` e
+// `capacidade` se ainda não existirem.
+pub fn comparar_indices<R: Registro>(
+    arquivo: &ArquivoSequencial<R>,
+    indice: &IndiceParcial,
+    ordem: usize,
+    capacidade: usize,
+    consultas: usize,
+) -> Resultado<ComparacaoIndices> {
+    let mut chaves = Vec::new();
+    for caminho in [&arquivo.caminho_principal, &arquivo.caminho_overflow] {
+        if Path::new(caminho).exists() && std::fs::metadata(caminho)?.len() > 0 {
+            percorrer_validos::<R>(caminho, |registro| {
+                chaves.push(registro.chave());
+                Ok(())
+            })?;
+        }
+    }
+    let mut sorteadas = Vec::with_capacity(consultas);
+    let mut semente: u64 = 0x2545_f491_4f6c_dd1d;
+    while !chaves.is_empty() && sorteadas.len() < consultas {
+        // Gerador congruencial linear: basta para espalhar as consultas
+        semente = semente.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        sorteadas.push(chaves[(semente >> 33) as usize % chaves.len()]);
+    }
+
+    let mut arvore = match arquivo.abrir_arvore() {
+        Ok(Some(arvore)) => arvore,
+        _ => arquivo.construir_arvore(ordem)?,
+    };
+    let altura_arvore = arvore.altura()?;
+    let paginas_arvore = arvore.num_paginas();
+    drop(arvore);
+    let mut hash = match arquivo.abrir_hash() {
+        Ok(Some(hash)) => hash,
+        _ => arquivo.construir_hash(capacidade)?,
+    };
+    let baldes_hash = hash.num_baldes()?;
+    let profundidade_hash = hash.profundidade;
+    drop(hash);
+
+    let parcial = ArquivoSequencial::<R>::novo(&arquivo.caminho_principal, &arquivo.caminho_overflow);
+    let com_arvore = parcial.clone().com_arvore_b(ordem);
+    let com_hash = parcial.clone().com_hash(capacidade);
+    Ok(ComparacaoIndices {
+        parcial: medir("índice parcial", &parcial, indice, &sorteadas)?,
+        arvore: medir("árvore B+", &com_arvore, indice, &sorteadas)?,
+        entradas_indice_parcial: indice.entradas.len(),
+        altura_arvore,
+        paginas_arvore,
+        hash: medir("hash", &com_hash, indice, &sorteadas)?,
+        baldes_hash,
+        profundidade_hash,
+    })
+}
+
+fn medir<R: Registro>(nome: &'static str, arquivo: &ArquivoSequencial<R>, indice: &IndiceParcial, chaves: &[i64]) -> Resultado<Medicao> {
+    let inicio = Instant::now();
+    let mut encontrados = 0;
+    for &chave in chaves {
+        if arquivo.consultar(indice, chave)?.is_some() {
+            encontrados += 1;
+        }
+    }
+    Ok(Medicao { nome, consultas: chaves.len(), encontrados, total: inicio.elapsed() })
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+// Erros do armazenamento de produtos e pedidos
+#[derive(Debug)]
+pub enum Erro {
+    Io(std::io::Error),
+    // Registro truncado ou ilegível; posicao é o byte do arquivo onde ele começa
+    RegistroCorrompido { posicao: u64, motivo: String },
+    CabecalhoInvalido(String),
+    ChaveNaoEncontrada(i64),
+    ChaveDuplicada(i64),
+    Csv { linha: Option<u64>, motivo: String },
+    // Índice que não corresponde ao arquivo de dados
+    IndiceInconsistente(String),
+    Configuracao(String),
+    // Manifesto de reconstrução ilegível ou com troca pendente
+    Manifesto(String),
+    // Atualização com valor inválido ou que mudaria a chave do registro
+    AlteracaoInvalida(String),
+    // Registro novo com campo fora das regras (texto maior que o campo, por exemplo)
+    RegistroInvalido(String),
+    // Pedido com product_id que não está entre os produtos (integridade.rs)
+    ProdutoInexistente { order_id: i64, product_id: i64 },
+    // Remoção de produto com pedidos recusada pela PoliticaRemocao::Restringir
+    ProdutoComPedidos { product_id: i64, pedidos: usize },
+    // Troca pelo manifesto que falhou (erro) e cujos temporários também não
+    // puderam ser descartados (descarte); a recuperação tenta de novo depois
+    TrocaNaoDescartada { erro: Box<Erro>, descarte: Box<Erro> },
+}
+
+pub type Resultado<T> = Result<T, Erro>;
+
+impl fmt::Display for Erro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Erro::Io(e) => write!(f, "erro de E/S: {}", e),
+            Erro::RegistroCorrompido { posicao, motivo } => {
+                write!(f, "registro corrompido na posição {}: {}", posicao, motivo)
+            }
+            Erro::CabecalhoInvalido(msg) => write!(f, "cabeçalho inválido: {}", msg),
+            Erro::ChaveNaoEncontrada(chave) => write!(f, "chave {} não encontrada", chave),
+            Erro::ChaveDuplicada(chave) => write!(f, "chave {} duplicada", chave),
+            Erro::Csv { linha: Some(linha), motivo } => write!(f, "erro no CSV (linha {}): {}", linha, motivo),
+            Erro::Csv { linha: None, motivo } => write!(f, "erro no CSV: {}", motivo),
+            Erro::IndiceInconsistente(msg) => write!(f, "índice inconsistente com os dados: {}", msg),
+            Erro::Configuracao(msg) => write!(f, "configuração inválida: {}", msg),
+            Erro::Manifesto(msg) => write!(f, "manifesto de reconstrução: {}", msg),
+            Erro::AlteracaoInvalida(msg) => write!(f, "alteração inválida: {}", msg),
+            Erro::RegistroInvalido(msg) => write!(f, "registro inválido: {}", msg),
+            Erro::ProdutoInexistente { order_id, product_id } => {
+                write!(f, "pedido {} referencia o produto {}, que não existe", order_id, product_id)
+            }
+            Erro::ProdutoComPedidos { product_id, pedidos } => {
+                write!(f, "produto {} ainda tem {} pedidos (política de remoção \"restringir\")", product_id, pedidos)
+            }
+            Erro::TrocaNaoDescartada { erro, descarte } => write!(
+                f,
+                "{} (e os arquivos temporários não foram descartados: {}; a recuperação tenta de novo na próxima execução)",
+                erro, descarte
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Erro {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Erro::Io(e) => Some(e),
+            Erro::TrocaNaoDescartada { erro, .. } => Some(erro.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Erro {
+    fn from(e: std::io::Error) -> Self {
+        Erro::Io(e)
+    }
+}
+
+impl From<csv::Error> for Erro {
+    fn from(e: csv::Error) -> Self {
+        let linha = e.position().map(|p| p.line());
+        let motivo = e.to_string();
+        match e.into_kind() {
+            csv::ErrorKind::Io(io) => Erro::Io(io),
+            _ => Erro::Csv { linha, motivo },
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+
+use csv::StringRecord;
+use serde::Deserialize;
+
+use crate::manifesto;
+use crate::ordenacao_externa::{OrdenadorExterno, Origem};
+use crate::cabecalho::TipoRegistro;
+use crate::data_hora::DataHora;
+use crate::erro::{Erro, Resultado};
+use crate::preco::Preco;
+use crate::registro::Registro;
+use crate::textos::{caminho_textos, RegrasTextos, Truncamento};
+
+// Quantas rejeições ficam guardadas no relatório; todas vão para o arquivo de rejeitados
+pub const MAX_REJEICOES_NO_RELATORIO: usize = 20;
+// Idem para os textos cortados de campos com a política "avisar"
+pub const MAX_TRUNCAMENTOS_NO_RELATORIO: usize = 20;
+
+// Uma linha do CSV que não virou registro
+#[derive(Debug, Clone)]
+pub struct Rejeicao {
+    pub linha: u64,
+    pub motivo: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelatorioImportacao {
+    pub linhas_lidas: u64,
+    pub gravados: usize,
+    pub total_rejeitadas: u64,
+    // Só as primeiras MAX_REJEICOES_NO_RELATORIO
+    pub rejeicoes: Vec<Rejeicao>,
+    // Arquivo com as linhas rejeitadas (só existe se houve rejeição)
+    pub caminho_rejeitados: Option<String>,
+    // Textos cortados para caber no campo (políticas "truncar" e "avisar")
+    pub total_truncados: u64,
+    // Linha e corte dos campos com a política "avisar", até MAX_TRUNCAMENTOS_NO_RELATORIO
+    pub truncamentos: Vec<(u64, Truncamento)>,
+}
+
+impl fmt::Display for RelatorioImportacao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Linhas lidas: {}", self.linhas_lidas)?;
+        writeln!(f, "Registros gravados: {}", self.gravados)?;
+        write!(f, "Linhas rejeitadas: {}", self.total_rejeitadas)?;
+        for rejeicao in &self.rejeicoes {
+            write!(f, "\n   linha {}: {}", rejeicao.linha, rejeicao.motivo)?;
+        }
+        if self.total_rejeitadas > self.rejeicoes.len() as u64 {
+            write!(f, "\n   ... e mais {}", self.total_rejeitadas - self.rejeicoes.len() as u64)?;
+        }
+        if let Some(caminho) = &self.caminho_rejeitados {
+            write!(f, "\nLinhas rejeitadas gravadas em {}", caminho)?;
+        }
+        if self.total_truncados > 0 {
+            write!(f, "\nTextos cortados para caber no campo: {}", self.total_truncados)?;
+            for (linha, truncamento) in &self.truncamentos {
+                write!(f, "\n   linha {}: {}", linha, truncamento)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Coluna do CSV de onde sai um campo: pela posição (0, 1, ...) ou pelo nome no cabeçalho
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Coluna {
+    Posicao(usize),
+    Nome(String),
+}
+
+// Formato do CSV e de quais colunas sai cada campo. Exemplo de arquivo de mapeamento:
+//
+//   delimitador = ";"
+//   aspas = "'"          # "" desliga o tratamento de aspas
+//   cabecalho = true     # false para arquivos sem linha de cabeçalho
+//
+//   [produto]
+//   product_id = "sku"
+//   category_alias = "categoria"
+//   price = 4
+//
+// Sem a seção da entidade vale o layout do dataset de joias do Kaggle. Campos de
+// texto fora do mapeamento ficam vazios; chaves e preço são obrigatórios.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapeamentoColunas {
+    pub delimitador: String,
+    pub aspas: String,
+    pub cabecalho: bool,
+    pub produto: Option<BTreeMap<String, Coluna>>,
+    pub pedido: Option<BTreeMap<String, Coluna>>,
+}
+
+impl Default for MapeamentoColunas {
+    fn default() -> Self {
+        MapeamentoColunas {
+            delimitador: ",".to_string(),
+            aspas: "\"".to_string(),
+            cabecalho: true,
+            produto: None,
+            pedido: None,
+        }
+    }
+}
+
+// Campos de cada entidade e quais deles não podem ficar sem coluna
+fn campos(tipo: TipoRegistro) -> (&'static [&'static str], &'static [&'static str]) {
+    match tipo {
+        TipoRegistro::Produto => (
+            &["product_id", "category_alias", "price", "material", "stone"],
+            &["product_id", "price"],
+        ),
+        TipoRegistro::Pedido => (
+            &["order_id", "user_id", "event_time", "product_id", "price"],
+            &["order_id", "user_id", "product_id", "price"],
+        ),
+    }
+}
+
+// Posições das colunas no CSV do dataset de joias
+fn colunas_kaggle(tipo: TipoRegistro) -> BTreeMap<String, Coluna> {
+    let posicoes: &[(&str, usize)] = match tipo {
+        TipoRegistro::Produto => &[("product_id", 2), ("category_alias", 5), ("price", 7), ("material", 11), ("stone", 12)],
+        TipoRegistro::Pedido => &[("order_id", 1), ("user_id", 8), ("event_time", 0), ("product_id", 2), ("price", 7)],
+    };
+    posicoes
+        .iter()
+        .map(|(campo, posicao)| (campo.to_string(), Coluna::Posicao(*posicao)))
+        .collect()
+}
+
+// Um único caractere ASCII (ou nenhum, se `vazio_permitido`)
+fn byte_unico(valor: &str, nome: &str, vazio_permitido: bool) -> Resultado<Option<u8>> {
+    match valor.as_bytes() {
+        [] if vazio_permitido => Ok(None),
+        [b] if b.is_ascii() => Ok(Some(*b)),
+        _ => Err(Erro::Configuracao(format!("{} deve ser um único caractere ASCII: {:?}", nome, valor))),
+    }
+}
+
+impl MapeamentoColunas {
+    pub fn carregar(caminho: &str) -> Resultado<Self> {
+        let texto = std::fs::read_to_string(caminho)?;
+        let mapeamento: MapeamentoColunas = toml::from_str(&texto)
+            .map_err(|e| Erro::Configuracao(format!("{}: {}", caminho, e)))?;
+        mapeamento.validar()?;
+        Ok(mapeamento)
+    }
+
+    fn validar(&self) -> Resultado<()> {
+        byte_unico(&self.delimitador, "delimitador", false)?;
+        byte_unico(&self.aspas, "aspas", true)?;
+        for tipo in [TipoRegistro::Produto, TipoRegistro::Pedido] {
+            let colunas = self.colunas(tipo);
+            let (todos, obrigatorios) = campos(tipo);
+            for (campo, coluna) in &colunas {
+                if !todos.contains(&campo.as_str()) {
+                    return Err(Erro::Configuracao(format!("{} não tem o campo {}", tipo.nome(), campo)));
+                }
+                if matches!(coluna, Coluna::Nome(_)) && !self.cabecalho {
+                    return Err(Erro::Configuracao(format!(
+                        "{}.{} usa nome de coluna, mas o arquivo não tem cabeçalho",
+                        tipo.nome(),
+                        campo
+                    )));
+                }
+            }
+            if let Some(campo) = obrigatorios.iter().find(|c| !colunas.contains_key(**c)) {
+                return Err(Erro::Configuracao(format!("{}.{} precisa de uma coluna", tipo.nome(), campo)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn colunas(&self, tipo: TipoRegistro) -> BTreeMap<String, Coluna> {
+        let secao = match tipo {
+            TipoRegistro::Produto => &self.produto,
+            TipoRegistro::Pedido => &self.pedido,
+        };
+        secao.clone().unwrap_or_else(|| colunas_kaggle(tipo))
+    }
+
+    fn leitor(&self, caminho_csv: &str) -> Resultado<csv::Reader<File>> {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.flexible(true).has_headers(self.cabecalho);
+        if let Some(delimitador) = byte_unico(&self.delimitador, "delimitador", false)? {
+            builder.delimiter(delimitador);
+        }
+        match byte_unico(&self.aspas, "aspas", true)? {
+            Some(aspas) => builder.quote(aspas),
+            None => builder.quoting(false),
+        };
+        Ok(builder.from_path(caminho_csv)?)
+    }
+}
+
+// Posição de cada campo já resolvida contra o cabeçalho do arquivo
+#[derive(Debug, Clone)]
+pub struct Colunas {
+    posicoes: BTreeMap<String, usize>,
+}
+
+impl Colunas {
+    fn resolver(mapa: &BTreeMap<String, Coluna>, cabecalho: Option<&StringRecord>) -> Resultado<Self> {
+        let mut posicoes = BTreeMap::new();
+        for (campo, coluna) in mapa {
+            let posicao = match coluna {
+                Coluna::Posicao(p) => *p,
+                Coluna::Nome(nome) => cabecalho
+                    .and_then(|c| c.iter().position(|h| h.trim() == nome))
+                    .ok_or_else(|| Erro::Configuracao(format!("coluna {:?} ({}) não existe no cabeçalho", nome, campo)))?,
+            };
+            posicoes.insert(campo.clone(), posicao);
+        }
+        Ok(Colunas { posicoes })
+    }
+
+    pub fn posicao(&self, campo: &str) -> Option<usize> {
+        self.posicoes.get(campo).copied()
+    }
+
+    // Quantas colunas uma linha precisa ter para conter todos os campos mapeados
+    pub fn minimo(&self) -> usize {
+        self.posicoes.values().max().map_or(0, |p| p + 1)
+    }
+}
+
+// Arquivo de rejeitados ao lado do arquivo de dados: produtos.dat -> produtos.dat.rejeitados.csv
+pub fn caminho_rejeitados(caminho: &str) -> String {
+    format!("{}.rejeitados.csv", caminho)
+}
+
+// Lê o CSV linha a linha, converte cada uma com `converter` e grava os registros
+// válidos ordenados em `caminho`, usando no máximo `memoria` bytes para ordenar.
+// Os textos são ajustados aos campos conforme `regras`.
+// Linhas que falham na conversão, ou que repetem a chave de uma linha anterior
+// (fica a primeira), vão para o arquivo de rejeitados com o número da linha e o motivo.
+pub fn importar_csv<R, F>(
+    caminho_csv: &str,
+    caminho: &str,
+    mapeamento: &MapeamentoColunas,
+    memoria: usize,
+    regras: &RegrasTextos,
+    converter: F,
+) -> Resultado<RelatorioImportacao>
+where
+    R: Registro,
+    F: Fn(&StringRecord, &Colunas) -> Result<R, String>,
+{
+    let mut rdr = mapeamento.leitor(caminho_csv)?;
+    let cabecalho = if mapeamento.cabecalho { Some(rdr.headers()?.clone()) } else { None };
+    let colunas = Colunas::resolver(&mapeamento.colunas(R::TIPO), cabecalho.as_ref())?;
+    // O arquivo novo substitui o antigo pelo manifesto, como na reconstrução; a
+    // nova geração também invalida o WAL, que se referia ao arquivo antigo
+    let textos = caminho_textos(caminho);
+    manifesto::trocar(caminho, &[caminho, &textos], || {
+        let destino = manifesto::caminho_temporario(caminho);
+        ler_e_gravar(&mut rdr, &colunas, &caminho_rejeitados(caminho), &destino, memoria, regras, converter)
+    })
+}
+
+// Corpo da importação: lê as linhas e grava o arquivo ordenado em `destino`
+fn ler_e_gravar<R, F>(
+    rdr: &mut csv::Reader<File>,
+    colunas: &Colunas,
+    caminho_rej: &str,
+    destino: &str,
+    memoria: usize,
+    regras: &RegrasTextos,
+    converter: F,
+) -> Resultado<RelatorioImportacao>
+where
+    R: Registro,
+    F: Fn(&StringRecord, &Colunas) -> Result<R, String>,
+{
+    let mut rejeitados: Option<csv::Writer<File>> = None;
+    let mut relatorio = RelatorioImportacao::default();
+    let mut ordenador = OrdenadorExterno::<R>::novo(destino, memoria).com_formato_textos(regras.formato);
+
+    let mut record = StringRecord::new();
+    loop {
+        let (origem, resultado) = match rdr.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let resultado = converter(&record, colunas).and_then(|mut registro| {
+                    let truncamentos = regras.ajustar_registro(&mut registro)?;
+                    Ok((registro, truncamentos))
+                });
+                (origem_da_linha(record.position()), resultado)
+            }
+            // Linha ilegível (por exemplo, UTF-8 inválido): rejeita e segue
+            Err(e) if !matches!(e.kind(), csv::ErrorKind::Io(_)) => {
+                record.clear();
+                (origem_da_linha(e.position()), Err(format!("linha ilegível: {}", e)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let linha = origem.linha;
+        relatorio.linhas_lidas += 1;
+        match resultado {
+            Ok((registro, truncamentos)) => {
+                for truncamento in truncamentos {
+                    relatorio.total_truncados += 1;
+                    if truncamento.avisar && relatorio.truncamentos.len() < MAX_TRUNCAMENTOS_NO_RELATORIO {
+                        relatorio.truncamentos.push((linha, truncamento));
+                    }
+                }
+                ordenador.adicionar_com_origem(registro, origem)?
+            }
+            Err(motivo) => rejeitar(&mut rejeitados, caminho_rej, &mut relatorio, linha, motivo, &record)?,
+        }
+    }
+
+    // Chaves repetidas só ficam lado a lado na intercalação, que mantém a linha
+    // que veio primeiro; a recusada é relida do CSV para ir inteira aos rejeitados
+    relatorio.gravados = ordenador.finalizar_recusando_repetidas(|chave, recusada, aceita| {
+        let mut posicao = csv::Position::new();
+        posicao.set_byte(recusada.byte).set_line(recusada.linha);
+        rdr.seek(posicao)?;
+        rdr.read_record(&mut record)?;
+        let motivo = format!("chave {} repetida (já aceita na linha {})", chave, aceita.linha);
+        rejeitar(&mut rejeitados, caminho_rej, &mut relatorio, recusada.linha, motivo, &record)
+    })?;
+
+    match rejeitados {
+        Some(mut wtr) => {
+            wtr.flush()?;
+            relatorio.caminho_rejeitados = Some(caminho_rej.to_string());
+        }
+        // Não deixa para trás o arquivo de rejeitados de uma importação anterior
+        None => {
+            if std::path::Path::new(caminho_rej).exists() {
+                std::fs::remove_file(caminho_rej)?;
+            }
+        }
+    }
+
+    Ok(relatorio)
+}
+
+fn origem_da_linha(posicao: Option<&csv::Position>) -> Origem {
+    posicao.map_or(Origem::default(), |p| Origem { linha: p.line(), byte: p.byte() })
+}
+
+// Anota a linha no relatório e a grava no arquivo de rejeitados (criado na
+// primeira rejeição) com o número dela, o motivo e os campos como vieram
+fn rejeitar(
+    rejeitados: &mut Option<csv::Writer<File>>,
+    caminho_rej: &str,
+    relatorio: &mut RelatorioImportacao,
+    linha: u64,
+    motivo: String,
+    record: &StringRecord,
+) -> Resultado<()> {
+    if rejeitados.is_none() {
+        let mut wtr = csv::WriterBuilder::new().flexible(true).from_path(caminho_rej)?;
+        wtr.write_record(["linha", "motivo"])?;
+        *rejeitados = Some(wtr);
+    }
+    if let Some(wtr) = rejeitados.as_mut() {
+        let mut saida = StringRecord::new();
+        saida.push_field(&linha.to_string());
+        saida.push_field(&motivo);
+        for campo in record.iter() {
+            saida.push_field(campo);
+        }
+        wtr.write_record(&saida)?;
+    }
+    relatorio.total_rejeitadas += 1;
+    if relatorio.rejeicoes.len() < MAX_REJEICOES_NO_RELATORIO {
+        relatorio.rejeicoes.push(Rejeicao { linha, motivo });
+    }
+    Ok(())
+}
+
+// Validação dos campos de uma linha. Os erros são o motivo da rejeição.
+
+pub fn exigir_colunas(record: &StringRecord, colunas: &Colunas) -> Result<(), String> {
+    let minimo = colunas.minimo();
+    if record.len() < minimo {
+        return Err(format!("linha com {} colunas, esperadas pelo menos {}", record.len(), minimo));
+    }
+    Ok(())
+}
+
+// Valor do campo na linha; campos sem coluna no mapeamento ficam vazios
+fn valor<'a>(record: &'a StringRecord, colunas: &Colunas, campo: &str) -> &'a str {
+    colunas
+        .posicao(campo)
+        .and_then(|p| record.get(p))
+        .unwrap_or("")
+        .trim()
+}
+
+pub fn campo_id(record: &StringRecord, colunas: &Colunas, nome: &str) -> Result<i64, String> {
+    let valor = valor(record, colunas, nome);
+    if valor.is_empty() {
+        return Err(format!("{} vazio", nome));
+    }
+    valor
+        .parse::<i64>()
+        .map_err(|_| format!("{} não é um número inteiro: {:?}", nome, valor))
+}
+
+pub fn campo_preco(record: &StringRecord, colunas: &Colunas, nome: &str) -> Result<Preco, String> {
+    let valor = valor(record, colunas, nome);
+    if valor.is_empty() {
+        return Err(format!("{} vazio", nome));
+    }
+    let preco = valor.parse().map_err(|e| format!("{}: {}", nome, e))?;
+    validar_preco(nome, &preco)?;
+    Ok(preco)
+}
+
+// Texto do campo como veio; o tamanho é conferido depois, pelas regras de
+// textos do arquivo de destino
+pub fn campo_texto(record: &StringRecord, colunas: &Colunas, nome: &str) -> String {
+    valor(record, colunas, nome).to_string()
+}
+
+pub fn campo_data_hora(record: &StringRecord, colunas: &Colunas, nome: &str) -> Result<DataHora, String> {
+    let valor = valor(record, colunas, nome);
+    if valor.is_empty() {
+        return Err(format!("{} vazio", nome));
+    }
+    valor.parse().map_err(|e| format!("{}: {}", nome, e))
+}
+
+// Regras dos campos, compartilhadas pela importação e pela atualização de registros
+pub fn validar_preco(nome: &str, preco: &Preco) -> Result<(), String> {
+    if preco.negativo() {
+        return Err(format!("{} inválido: {}", nome, preco));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::ler_validos;
+    use crate::produto::{produto_de_csv, Produto};
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_importacao_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(nome).to_string_lossy().to_string()
+    }
+
+    fn importar(csv: &str, nome: &str, mapeamento: &MapeamentoColunas) -> Resultado<(RelatorioImportacao, String)> {
+        let caminho_csv = caminho_temporario(&format!("{}.csv", nome));
+        std::fs::write(&caminho_csv, csv).unwrap();
+        let caminho = caminho_temporario(&format!("{}.dat", nome));
+        let relatorio = importar_csv(&caminho_csv, &caminho, mapeamento, 1024 * 1024, &RegrasTextos::default(), produto_de_csv)?;
+        Ok((relatorio, caminho))
+    }
+
+    fn chaves(caminho: &str) -> Vec<i64> {
+        ler_validos::<Produto>(caminho).unwrap().iter().map(|p| p.product_id).collect()
+    }
+
+    // Preço inválido, linha curta e chave repetida vão para os rejeitados com a
+    // linha e o motivo; só as linhas válidas chegam ao arquivo
+    #[test]
+    fn importacao_rejeita_linhas_invalidas() {
+        let csv = "event_time,order_id,product_id,quantity,category_id,category_alias,brand_id,price,user_id,gender,color,metal,gem\n\
+                   t,1,30,1,1,jewelry.ring,0,10.50,9,f,red,gold,diamond\n\
+                   t,2,20,1,1,jewelry.ring,0,abc,9,f,red,gold,diamond\n\
+                   t,3,10,1\n\
+                   t,4,30,1,1,jewelry.pendant,0,1.00,9,f,red,silver,\n\
+                   t,5,10,1,1,jewelry.earring,0,2.00,9,f,red,gold,ruby\n";
+        let (relatorio, caminho) = importar(csv, "rejeicoes", &MapeamentoColunas::default()).unwrap();
+        assert_eq!((relatorio.linhas_lidas, relatorio.gravados, relatorio.total_rejeitadas), (5, 2, 3));
+        let rejeicoes: Vec<(u64, &str)> = relatorio.rejeicoes.iter().map(|r| (r.linha, r.motivo.as_str())).collect();
+        assert_eq!(rejeicoes[0].0, 3);
+        assert!(rejeicoes[0].1.starts_with("price"), "{}", rejeicoes[0].1);
+        assert_eq!(rejeicoes[1], (4, "linha com 4 colunas, esperadas pelo menos 13"));
+        assert_eq!(rejeicoes[2], (5, "chave 30 repetida (já aceita na linha 2)"));
+
+        let rejeitados = std::fs::read_to_string(relatorio.caminho_rejeitados.unwrap()).unwrap();
+        assert_eq!(rejeitados.lines().count(), 4);
+        assert!(rejeitados.lines().nth(2).unwrap().starts_with("4,"));
+        assert_eq!(chaves(&caminho), vec![10, 30]);
+        let produto = ler_validos::<Produto>(&caminho).unwrap().pop().unwrap();
+        assert_eq!((produto.material.as_str(), produto.price), ("gold", Preco::em_centavos(1050)));
+    }
+
+    // Com memória para dois registros as repetidas só se encontram na
+    // intercalação dos runs: fica a primeira linha de cada chave e a linha
+    // recusada vai inteira para os rejeitados
+    #[test]
+    fn repetidas_recusadas_na_intercalacao() {
+        let linhas = [(30, "gold"), (20, "silver"), (30, "platinum"), (10, "gold"), (20, "bronze"), (40, "gold")];
+        let mut csv = String::from("event_time,order_id,product_id,quantity,category_id,category_alias,brand_id,price,user_id,gender,color,metal,gem\n");
+        for (i, (chave, metal)) in linhas.iter().enumerate() {
+            csv.push_str(&format!("t,{},{},1,1,jewelry.ring,0,1.00,9,f,red,{},\n", i, chave, metal));
+        }
+        let caminho_csv = caminho_temporario("repetidas.csv");
+        std::fs::write(&caminho_csv, &csv).unwrap();
+        let caminho = caminho_temporario("repetidas.dat");
+        let memoria = 2 * Produto::TAMANHO_REGISTRO;
+        let mapeamento = MapeamentoColunas::default();
+        let relatorio = importar_csv(&caminho_csv, &caminho, &mapeamento, memoria, &RegrasTextos::default(), produto_de_csv).unwrap();
+        assert_eq!((relatorio.linhas_lidas, relatorio.gravados, relatorio.total_rejeitadas), (6, 4, 2));
+        let rejeicoes: Vec<(u64, &str)> = relatorio.rejeicoes.iter().map(|r| (r.linha, r.motivo.as_str())).collect();
+        assert_eq!(rejeicoes, [(6, "chave 20 repetida (já aceita na linha 3)"), (4, "chave 30 repetida (já aceita na linha 2)")]);
+
+        let rejeitados = std::fs::read_to_string(relatorio.caminho_rejeitados.unwrap()).unwrap();
+        let rejeitados: Vec<&str> = rejeitados.lines().skip(1).collect();
+        assert_eq!(
+            rejeitados,
+            [
+                "6,chave 20 repetida (já aceita na linha 3),t,4,20,1,1,jewelry.ring,0,1.00,9,f,red,bronze,",
+                "4,chave 30 repetida (já aceita na linha 2),t,2,30,1,1,jewelry.ring,0,1.00,9,f,red,platinum,",
+            ]
+        );
+        let produtos = ler_validos::<Produto>(&caminho).unwrap();
+        let lidos: Vec<(i64, &str)> = produtos.iter().map(|p| (p.product_id, p.material.as_str())).collect();
+        assert_eq!(lidos, [(10, "gold"), (20, "silver"), (30, "gold"), (40, "gold")]);
+    }
+
+    fn mapeamento(nome: &str, toml: &str) -> Resultado<MapeamentoColunas> {
+        let caminho = caminho_temporario(&format!("{}.toml", nome));
+        std::fs::write(&caminho, toml).unwrap();
+        MapeamentoColunas::carregar(&caminho)
+    }
+
+    // Colunas em outra ordem e com outros nomes, pelo nome no cabeçalho ou pela
+    // posição num arquivo sem cabeçalho, com outro delimitador e outras aspas
+    #[test]
+    fn importacao_com_colunas_mapeadas() {
+        let por_nome = mapeamento(
+            "por_nome",
+            "delimitador = \";\"\n[produto]\nproduct_id = \"sku\"\nprice = \"valor\"\nmaterial = \"metal\"\n",
+        )
+        .unwrap();
+        let csv = "valor;metal;extra;sku\n2,50;silver;x;7\n1.00;\"gold;white\";y;3\n";
+        let (relatorio, caminho) = importar(csv, "por_nome", &por_nome).unwrap();
+        assert_eq!((relatorio.gravados, relatorio.total_rejeitadas), (2, 0));
+        let produtos = ler_validos::<Produto>(&caminho).unwrap();
+        let lidos: Vec<(i64, &str, Preco)> = produtos.iter().map(|p| (p.product_id, p.material.as_str(), p.price)).collect();
+        assert_eq!(lidos, vec![(3, "gold;white", Preco::em_centavos(100)), (7, "silver", Preco::em_centavos(250))]);
+        assert!(produtos.iter().all(|p| p.category_alias.is_empty() && p.stone.is_empty()));
+
+        let por_posicao = mapeamento(
+            "por_posicao",
+            "delimitador = \"|\"\naspas = \"\"\ncabecalho = false\n[produto]\nproduct_id = 2\nprice = 0\nstone = 1\n",
+        )
+        .unwrap();
+        let (relatorio, caminho) = importar("5|\"ruby\"|11\n6|opal\n", "por_posicao", &por_posicao).unwrap();
+        assert_eq!((relatorio.gravados, relatorio.rejeicoes[0].linha), (1, 2));
+        let produto = ler_validos::<Produto>(&caminho).unwrap().pop().unwrap();
+        assert_eq!((produto.product_id, produto.stone.as_str()), (11, "\"ruby\""));
+    }
+
+    // Campo desconhecido, campo obrigatório sem coluna, nome sem cabeçalho e
+    // coluna que não existe no arquivo
+    #[test]
+    fn mapeamento_invalido_recusado() {
+        let invalidos = [
+            ("[produto]\nproduct_id = 0\nprice = 1\npeso = 2\n", "produto não tem o campo peso"),
+            ("[pedido]\norder_id = 0\nuser_id = 1\nprice = 2\n", "pedido.product_id precisa de uma coluna"),
+            ("cabecalho = false\n[produto]\nproduct_id = \"sku\"\nprice = 1\n", "usa nome de coluna"),
+            ("delimitador = \";;\"\n", "delimitador"),
+        ];
+        for (i, (toml, trecho)) in invalidos.iter().enumerate() {
+            match mapeamento(&format!("invalido_{}", i), toml) {
+                Err(Erro::Configuracao(msg)) => assert!(msg.contains(trecho), "{}", msg),
+                outro => panic!("esperado Erro::Configuracao para {:?}, veio {:?}", toml, outro),
+            }
+        }
+
+        let sem_coluna = mapeamento("sem_coluna", "[produto]\nproduct_id = \"sku\"\nprice = \"preco\"\n").unwrap();
+        match importar("sku,valor\n1,2.00\n", "sem_coluna", &sem_coluna) {
+            Err(Erro::Configuracao(msg)) => assert!(msg.contains("\"preco\""), "{}", msg),
+            outro => panic!("esperado Erro::Configuracao, veio {:?}", outro.map(|(r, _)| r)),
+        }
+    }
+}
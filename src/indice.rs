@@ -1,5 +1,7 @@
 use serde::{Serialize, Deserialize};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use crate::erro::{Erro, Resultado};
+use crate::cabecalho::{self, Cabecalho, TipoArquivo, TipoRegistro, TAMANHO_CABECALHO};
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,13 +31,16 @@ impl IndiceEntry {
 pub struct IndiceParcial {
     pub entradas: Vec<IndiceEntry>,
     pub fator_esparsidade: usize,
+    // Layout do arquivo de dados indexado, gravado no cabeçalho do índice
+    pub layout: LayoutRegistro,
 }
 
 impl IndiceParcial {
-    pub fn novo(fator_esparsidade: usize) -> Self {
+    pub fn novo(fator_esparsidade: usize, layout: LayoutRegistro) -> Self {
         IndiceParcial {
             entradas: Vec::new(),
             fator_esparsidade,
+            layout,
         }
     }
 
@@ -44,14 +49,17 @@ impl IndiceParcial {
     }
 
 
-    pub fn salvar_binario(&self, caminho: &str) -> std::io::Result<()> {
+    pub fn salvar_binario(&self, caminho: &str) -> Resultado<()> {
         let mut arquivo = std::fs::File::create(caminho)?;
         
-        // Escreve o fator de esparsidade no início (4 bytes)
-        arquivo.write_all(&(self.fator_esparsidade as u32).to_le_bytes())?;
-        
-        // Escreve o número de entradas (4 bytes)
-        arquivo.write_all(&(self.entradas.len() as u32).to_le_bytes())?;
+        // Cabeçalho com tipo de registro, layout, fator e número de entradas
+        let cabecalho = Cabecalho::novo(
+            TipoArquivo::Indice,
+            self.layout,
+            self.fator_esparsidade,
+            self.entradas.len() as u64,
+        );
+        cabecalho.escrever(&mut arquivo)?;
         
         // Escreve cada entrada
         for entrada in &self.entradas {
@@ -62,18 +70,21 @@ impl IndiceParcial {
     }
 
 
-    pub fn carregar_binario(caminho: &str) -> std::io::Result<Self> {
+    // Carrega o índice conferindo se ele foi construído para o layout esperado
+    pub fn carregar_binario(caminho: &str, layout: LayoutRegistro) -> Resultado<Self> {
         let mut arquivo = std::fs::File::open(caminho)?;
         
-        // Lê o fator de esparsidade (4 bytes)
-        let mut fator_bytes = [0u8; 4];
-        arquivo.read_exact(&mut fator_bytes)?;
-        let fator_esparsidade = u32::from_le_bytes(fator_bytes) as usize;
-        
-        // Lê o número de entradas (4 bytes)
-        let mut num_entradas_bytes = [0u8; 4];
-        arquivo.read_exact(&mut num_entradas_bytes)?;
-        let num_entradas = u32::from_le_bytes(num_entradas_bytes) as usize;
+        let cabecalho = Cabecalho::ler(&mut arquivo)?;
+        cabecalho.validar(TipoArquivo::Indice, layout)?;
+        validar_fator(cabecalho.fator_esparsidade as usize, caminho)?;
+        let num_entradas = cabecalho.num_registros as usize;
+        let tamanho_esperado = (TAMANHO_CABECALHO + num_entradas * IndiceEntry::TAMANHO_ENTRADA) as u64;
+        if arquivo.metadata()?.len() != tamanho_esperado {
+            return Err(Erro::IndiceInconsistente(format!(
+                "{} deveria ter {} bytes para {} entradas",
+                caminho, tamanho_esperado, num_entradas
+            )));
+        }
         
         // Lê as entradas
         let mut entradas = Vec::with_capacity(num_entradas);
@@ -86,7 +97,8 @@ impl IndiceParcial {
         
         Ok(IndiceParcial {
             entradas,
-            fator_esparsidade,
+            fator_esparsidade: cabecalho.fator_esparsidade as usize,
+            layout,
         })
     }
 
@@ -106,32 +118,138 @@ impl IndiceParcial {
         if esq > 0 {
             Some((esq - 1, self.entradas[esq - 1].posicao))
         } else {
-            Some((0, 0))
+            Some((0, TAMANHO_CABECALHO as u64))
         }
     }
 }
 
-pub fn construir_indice_parcial(caminho_arquivo: &str, fator: usize) 
-    -> std::io::Result<IndiceParcial> 
+// Como os registros de um arquivo de tamanho fixo estão dispostos: tipo do
+// registro, tamanho de cada um e posição da chave (i64) dentro dele
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutRegistro {
+    pub tipo: TipoRegistro,
+    pub tamanho_registro: usize,
+    pub offset_chave: usize,
+}
+
+impl LayoutRegistro {
+    pub fn chave(&self, registro: &[u8]) -> i64 {
+        let inicio = self.offset_chave;
+        i64::from_le_bytes(registro[inicio..inicio + 8].try_into().unwrap())
+    }
+}
+
+// Fator 0 não define entradas (e dividiria por zero na construção)
+fn validar_fator(fator: usize, caminho: &str) -> Resultado<()> {
+    if fator == 0 {
+        return Err(Erro::IndiceInconsistente(format!("fator de esparsidade 0 para {}", caminho)));
+    }
+    Ok(())
+}
+
+pub fn construir_indice_parcial(caminho_arquivo: &str, fator: usize, layout: LayoutRegistro) 
+    -> Resultado<IndiceParcial> 
 {
-    use crate::produto::Produto;
-    let mut indice = IndiceParcial::novo(fator);
+    validar_fator(fator, caminho_arquivo)?;
+    let mut indice = IndiceParcial::novo(fator, layout);
     let mut arquivo = std::fs::File::open(caminho_arquivo)?;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
+    cabecalho::ler_cabecalho_dados(&mut arquivo, layout)?;
+    arquivo.seek(SeekFrom::Start(TAMANHO_CABECALHO as u64))?;
+    let mut buffer = vec![0u8; layout.tamanho_registro];
     let mut contador = 0;
-    let mut posicao = 0u64;
-    loop {
-        match arquivo.read_exact(&mut buffer) {
-            Ok(_) => {
-                if contador % fator == 0 {
-                    let produto = Produto::from_bytes(&buffer);
-                    indice.adicionar_entrada(produto.product_id, posicao);
-                }
-                contador += 1;
-                posicao += Produto::TAMANHO_REGISTRO as u64;
-            }
-            Err(_) => break,
+    let mut posicao = TAMANHO_CABECALHO as u64;
+    while arquivo.read_exact(&mut buffer).is_ok() {
+        if contador % fator == 0 {
+            indice.adicionar_entrada(layout.chave(&buffer), posicao);
         }
+        contador += 1;
+        posicao += layout.tamanho_registro as u64;
     }
     Ok(indice)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::pedido::Pedido;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+    use crate::registro::Registro;
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_indice_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(nome).to_string_lossy().to_string()
+    }
+
+    // Cada entrada deve cair no início de um registro e conter a chave desse registro
+    fn conferir_indice<R: Registro>(caminho: &str, indice: &IndiceParcial) {
+        let bytes = std::fs::read(caminho).unwrap();
+        let mut textos = crate::textos::textos_do_arquivo(caminho, R::LAYOUT, Default::default()).unwrap();
+        assert!(!indice.entradas.is_empty());
+        for entrada in &indice.entradas {
+            let pos = entrada.posicao as usize;
+            assert!(pos >= TAMANHO_CABECALHO);
+            assert_eq!((pos - TAMANHO_CABECALHO) % R::TAMANHO_REGISTRO, 0);
+            let registro = R::from_bytes(&bytes[pos..pos + R::TAMANHO_REGISTRO], &mut textos).unwrap();
+            assert_eq!(registro.chave(), entrada.chave);
+        }
+    }
+
+    #[test]
+    fn indice_de_produtos_aponta_para_inicio_dos_registros() {
+        let caminho = caminho_temporario("produtos.dat");
+        let mut produtos: Vec<Produto> = (0..25)
+            .map(|i| Produto {
+                product_id: 100 - i * 3,
+                category_alias: "jewelry.ring".to_string(),
+                price: Preco::em_centavos(i * 100),
+                material: "gold".to_string(),
+                stone: "diamond".to_string(),
+            })
+            .collect();
+        gravar_ordenados(&mut produtos, &caminho).unwrap();
+
+        let indice = construir_indice_parcial(&caminho, 4, Produto::LAYOUT).unwrap();
+        assert_eq!(indice.entradas.len(), 7);
+        conferir_indice::<Produto>(&caminho, &indice);
+    }
+
+    #[test]
+    fn indice_de_pedidos_aponta_para_inicio_dos_registros() {
+        let caminho = caminho_temporario("pedidos.dat");
+        let mut pedidos: Vec<Pedido> = (0..25)
+            .map(|i| Pedido {
+                order_id: 1000 + i * 7,
+                user_id: i,
+                event_time: "2018-12-01 11:40:29+00:00".parse().unwrap(),
+                product_id: 5,
+                price: Preco::em_centavos(150),
+            })
+            .collect();
+        gravar_ordenados(&mut pedidos, &caminho).unwrap();
+
+        let indice = construir_indice_parcial(&caminho, 4, Pedido::LAYOUT).unwrap();
+        assert_eq!(indice.entradas.len(), 7);
+        conferir_indice::<Pedido>(&caminho, &indice);
+    }
+
+    // Fator 0 é recusado tanto na construção quanto num índice gravado com ele
+    #[test]
+    fn fator_zero_recusado() {
+        let caminho = caminho_temporario("fator_zero.dat");
+        let mut produtos: Vec<Produto> = (0..5).map(|i| Produto { product_id: i, ..Produto::default() }).collect();
+        gravar_ordenados(&mut produtos, &caminho).unwrap();
+        assert!(matches!(construir_indice_parcial(&caminho, 0, Produto::LAYOUT), Err(Erro::IndiceInconsistente(_))));
+
+        let caminho_indice = caminho_temporario("fator_zero.idx");
+        let mut indice = construir_indice_parcial(&caminho, 2, Produto::LAYOUT).unwrap();
+        indice.fator_esparsidade = 0;
+        indice.salvar_binario(&caminho_indice).unwrap();
+        assert!(matches!(
+            IndiceParcial::carregar_binario(&caminho_indice, Produto::LAYOUT),
+            Err(Erro::IndiceInconsistente(_))
+        ));
+    }
+}
@@ -0,0 +1,405 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::arquivo::ArquivoSequencial;
+use crate::arvore_b::{ponteiros_validos, Ponteiro};
+use crate::cabecalho::{Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::manifesto::caminho_temporario;
+use crate::registro::Registro;
+use crate::wal::ArquivoAlvo;
+
+// Índice hash extensível (produtos.dat.hash) para consultas pontuais: a chave
+// leva direto ao balde por um diretório, sem busca binária nem varredura de
+// bloco. Cobre o principal e o overflow.
+//
+// Layout: os primeiros TAMANHO_META bytes têm o cabeçalho comum (capacidade dos
+// baldes no campo do fator de esparsidade, número de chaves em num_registros)
+// seguido de [profundidade global u8][posição do diretório u64][suja u8]. O
+// diretório são 2^profundidade posições de balde (u64); cada balde é
+// [profundidade local u8][n u16][5 livres] e `capacidade` entradas
+// [chave i64][arquivo u8][posição u64].
+//
+// Quando um balde enche ele se divide em dois pelo próximo bit do hash; se a
+// profundidade local já é a global, o diretório dobra antes (a cópia nova vai
+// para o fim do arquivo e a antiga fica sem uso até a próxima reconstrução).
+// A remoção só tira a entrada do balde, sem juntar baldes.
+//
+// Como a árvore B+, o índice não passa pelo WAL: fica marcado como sujo
+// enquanto é alterado e é refeito a partir dos dados pelo `recuperar` se uma
+// queda o deixar assim. A consulta confere o registro apontado.
+const TAMANHO_META: u64 = 128;
+const TAMANHO_ENTRADA: usize = 17;
+const TAMANHO_CABECALHO_BALDE: usize = 8;
+// 2^24 posições de diretório (128 MiB) bastam para qualquer arquivo desta aplicação
+const PROFUNDIDADE_MAXIMA: u8 = 24;
+pub const CAPACIDADE_PADRAO: usize = 64;
+pub const CAPACIDADE_MINIMA: usize = 2;
+pub const CAPACIDADE_MAXIMA: usize = 4096;
+
+pub fn caminho_hash(caminho_dados: &str) -> String {
+    format!("{}.hash", caminho_dados)
+}
+
+// Mistura do splitmix64: bijetora, então chaves diferentes nunca têm o mesmo hash
+fn espalhar(chave: i64) -> u64 {
+    let mut x = chave as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, Clone)]
+struct Balde {
+    profundidade: u8,
+    entradas: Vec<(i64, Ponteiro)>,
+}
+
+#[derive(Debug)]
+pub struct IndiceHash {
+    arquivo: File,
+    layout: LayoutRegistro,
+    pub capacidade: usize,
+    pub profundidade: u8,
+    posicao_diretorio: u64,
+    pub num_chaves: u64,
+    suja: bool,
+}
+
+impl IndiceHash {
+    // Índice vazio: diretório de uma posição apontando para um balde vazio
+    pub fn criar(caminho: &str, layout: LayoutRegistro, capacidade: usize) -> Resultado<Self> {
+        validar_capacidade(capacidade)?;
+        let arquivo = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(caminho)?;
+        let mut hash = IndiceHash {
+            arquivo,
+            layout,
+            capacidade,
+            profundidade: 0,
+            posicao_diretorio: TAMANHO_META,
+            num_chaves: 0,
+            suja: false,
+        };
+        let balde = TAMANHO_META + 8;
+        hash.gravar_diretorio(&[balde], TAMANHO_META)?;
+        hash.gravar_balde(balde, &Balde { profundidade: 0, entradas: Vec::new() })?;
+        hash.gravar_meta()?;
+        hash.arquivo.sync_all()?;
+        Ok(hash)
+    }
+
+    pub fn abrir(caminho: &str, layout: LayoutRegistro) -> Resultado<Self> {
+        let mut arquivo = OpenOptions::new().read(true).write(true).open(caminho)?;
+        let cabecalho = Cabecalho::ler(&mut arquivo)?;
+        cabecalho.validar(TipoArquivo::Hash, layout)?;
+        let capacidade = cabecalho.fator_esparsidade as usize;
+        validar_capacidade(capacidade)?;
+        let mut meta = [0u8; 10];
+        arquivo.seek(SeekFrom::Start(TAMANHO_CABECALHO as u64))?;
+        arquivo.read_exact(&mut meta)?;
+        let profundidade = meta[0];
+        let posicao_diretorio = u64::from_le_bytes(meta[1..9].try_into().unwrap());
+        if meta[9] != 0 {
+            return Err(Erro::IndiceInconsistente(format!(
+                "{} foi interrompido no meio de uma alteração; reconstrua o índice",
+                caminho
+            )));
+        }
+        let fim_diretorio = posicao_diretorio + (8u64 << profundidade);
+        if profundidade > PROFUNDIDADE_MAXIMA || arquivo.metadata()?.len() < fim_diretorio {
+            return Err(Erro::IndiceInconsistente(format!("{}: diretório inválido", caminho)));
+        }
+        Ok(IndiceHash {
+            arquivo,
+            layout,
+            capacidade,
+            profundidade,
+            posicao_diretorio,
+            num_chaves: cabecalho.num_registros,
+            suja: false,
+        })
+    }
+
+    // Uma leitura do diretório e uma do balde
+    pub fn buscar(&mut self, chave: i64) -> Resultado<Option<Ponteiro>> {
+        let (_, balde) = self.balde_para(chave)?;
+        Ok(balde.entradas.iter().find(|(c, _)| *c == chave).map(|(_, p)| *p))
+    }
+
+    // Insere ou, se a chave já existir, troca o ponteiro
+    pub fn inserir(&mut self, chave: i64, ponteiro: Ponteiro) -> Resultado<()> {
+        self.marcar_suja()?;
+        loop {
+            let (posicao, mut balde) = self.balde_para(chave)?;
+            if let Some(entrada) = balde.entradas.iter_mut().find(|(c, _)| *c == chave) {
+                entrada.1 = ponteiro;
+                self.gravar_balde(posicao, &balde)?;
+                break;
+            }
+            if balde.entradas.len() < self.capacidade {
+                balde.entradas.push((chave, ponteiro));
+                self.gravar_balde(posicao, &balde)?;
+                self.num_chaves += 1;
+                break;
+            }
+            self.dividir(posicao, balde)?;
+        }
+        self.marcar_limpa()
+    }
+
+    // Tira a chave do balde; false se ela não estava no índice
+    pub fn remover(&mut self, chave: i64) -> Resultado<bool> {
+        let (posicao, mut balde) = self.balde_para(chave)?;
+        let Some(i) = balde.entradas.iter().position(|(c, _)| *c == chave) else {
+            return Ok(false);
+        };
+        self.marcar_suja()?;
+        balde.entradas.swap_remove(i);
+        self.gravar_balde(posicao, &balde)?;
+        self.num_chaves -= 1;
+        self.marcar_limpa()?;
+        Ok(true)
+    }
+
+    // Quantos baldes diferentes o diretório usa
+    pub fn num_baldes(&mut self) -> Resultado<usize> {
+        let mut baldes = self.ler_diretorio()?;
+        baldes.sort_unstable();
+        baldes.dedup();
+        Ok(baldes.len())
+    }
+
+    fn balde_para(&mut self, chave: i64) -> Resultado<(u64, Balde)> {
+        let i = espalhar(chave) & ((1u64 << self.profundidade) - 1);
+        let mut bytes = [0u8; 8];
+        self.arquivo.seek(SeekFrom::Start(self.posicao_diretorio + i * 8))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        let posicao = u64::from_le_bytes(bytes);
+        Ok((posicao, self.ler_balde(posicao)?))
+    }
+
+    // Divide um balde cheio pelo bit seguinte à sua profundidade local
+    fn dividir(&mut self, posicao: u64, balde: Balde) -> Resultado<()> {
+        let mut diretorio = self.ler_diretorio()?;
+        if balde.profundidade == self.profundidade {
+            if self.profundidade == PROFUNDIDADE_MAXIMA {
+                return Err(Erro::IndiceInconsistente("diretório do índice hash no tamanho máximo".to_string()));
+            }
+            // A posição i e a i + 2^g passam a apontar para o mesmo balde
+            diretorio.extend_from_within(..);
+            let nova_posicao = self.fim_do_arquivo()?;
+            self.gravar_diretorio(&diretorio, nova_posicao)?;
+            self.posicao_diretorio = nova_posicao;
+            self.profundidade += 1;
+            self.gravar_meta()?;
+        }
+        let bit = 1u64 << balde.profundidade;
+        let (um, zero): (Vec<_>, Vec<_>) = balde.entradas.into_iter().partition(|(c, _)| espalhar(*c) & bit != 0);
+        let profundidade = balde.profundidade + 1;
+        let novo = self.fim_do_arquivo()?;
+        // O balde novo é gravado antes de o diretório apontar para ele
+        self.gravar_balde(novo, &Balde { profundidade, entradas: um })?;
+        self.gravar_balde(posicao, &Balde { profundidade, entradas: zero })?;
+        for (i, entrada) in diretorio.iter_mut().enumerate() {
+            if *entrada == posicao && (i as u64) & bit != 0 {
+                *entrada = novo;
+            }
+        }
+        let posicao_diretorio = self.posicao_diretorio;
+        self.gravar_diretorio(&diretorio, posicao_diretorio)
+    }
+
+    fn tamanho_balde(&self) -> usize {
+        TAMANHO_CABECALHO_BALDE + TAMANHO_ENTRADA * self.capacidade
+    }
+
+    fn fim_do_arquivo(&mut self) -> Resultado<u64> {
+        Ok(self.arquivo.seek(SeekFrom::End(0))?)
+    }
+
+    fn ler_diretorio(&mut self) -> Resultado<Vec<u64>> {
+        let mut bytes = vec![0u8; 8 << self.profundidade];
+        self.arquivo.seek(SeekFrom::Start(self.posicao_diretorio))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        Ok(bytes.chunks_exact(8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).collect())
+    }
+
+    fn gravar_diretorio(&mut self, diretorio: &[u64], posicao: u64) -> Resultado<()> {
+        let bytes: Vec<u8> = diretorio.iter().flat_map(|p| p.to_le_bytes()).collect();
+        self.arquivo.seek(SeekFrom::Start(posicao))?;
+        self.arquivo.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn ler_balde(&mut self, posicao: u64) -> Resultado<Balde> {
+        let mut bytes = vec![0u8; self.tamanho_balde()];
+        self.arquivo.seek(SeekFrom::Start(posicao))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        let n = u16::from_le_bytes(bytes[1..3].try_into().unwrap()) as usize;
+        if n > self.capacidade || bytes[0] > self.profundidade {
+            return Err(Erro::IndiceInconsistente(format!("balde inválido na posição {} do índice hash", posicao)));
+        }
+        let entradas = bytes[TAMANHO_CABECALHO_BALDE..]
+            .chunks_exact(TAMANHO_ENTRADA)
+            .take(n)
+            .map(|e| {
+                let chave = i64::from_le_bytes(e[0..8].try_into().unwrap());
+                let alvo = if e[8] == ArquivoAlvo::Overflow as u8 { ArquivoAlvo::Overflow } else { ArquivoAlvo::Principal };
+                (chave, Ponteiro { alvo, posicao: u64::from_le_bytes(e[9..17].try_into().unwrap()) })
+            })
+            .collect();
+        Ok(Balde { profundidade: bytes[0], entradas })
+    }
+
+    fn gravar_balde(&mut self, posicao: u64, balde: &Balde) -> Resultado<()> {
+        let mut bytes = vec![0u8; self.tamanho_balde()];
+        bytes[0] = balde.profundidade;
+        bytes[1..3].copy_from_slice(&(balde.entradas.len() as u16).to_le_bytes());
+        for (i, (chave, ponteiro)) in balde.entradas.iter().enumerate() {
+            let e = TAMANHO_CABECALHO_BALDE + TAMANHO_ENTRADA * i;
+            bytes[e..e + 8].copy_from_slice(&chave.to_le_bytes());
+            bytes[e + 8] = ponteiro.alvo as u8;
+            bytes[e + 9..e + 17].copy_from_slice(&ponteiro.posicao.to_le_bytes());
+        }
+        self.arquivo.seek(SeekFrom::Start(posicao))?;
+        self.arquivo.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn gravar_meta(&mut self) -> Resultado<()> {
+        let mut bytes = vec![0u8; TAMANHO_META as usize];
+        let cabecalho = Cabecalho::novo(TipoArquivo::Hash, self.layout, self.capacidade, self.num_chaves);
+        bytes[..TAMANHO_CABECALHO].copy_from_slice(&cabecalho.to_bytes());
+        bytes[64] = self.profundidade;
+        bytes[65..73].copy_from_slice(&self.posicao_diretorio.to_le_bytes());
+        bytes[73] = self.suja as u8;
+        self.arquivo.seek(SeekFrom::Start(0))?;
+        self.arquivo.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn marcar_suja(&mut self) -> Resultado<()> {
+        self.suja = true;
+        self.gravar_meta()?;
+        self.arquivo.sync_data()?;
+        Ok(())
+    }
+
+    fn marcar_limpa(&mut self) -> Resultado<()> {
+        self.arquivo.sync_data()?;
+        self.suja = false;
+        self.gravar_meta()
+    }
+}
+
+fn validar_capacidade(capacidade: usize) -> Resultado<()> {
+    if !(CAPACIDADE_MINIMA..=CAPACIDADE_MAXIMA).contains(&capacidade) {
+        return Err(Erro::Configuracao(format!(
+            "capacidade do balde do índice hash deve estar entre {} e {} (recebido {})",
+            CAPACIDADE_MINIMA, CAPACIDADE_MAXIMA, capacidade
+        )));
+    }
+    Ok(())
+}
+
+// Índice com todos os registros válidos do principal e do overflow
+pub fn construir_hash<R: Registro>(caminho_principal: &str, caminho_overflow: &str, destino: &str, capacidade: usize) -> Resultado<IndiceHash> {
+    let entradas = ponteiros_validos::<R>(caminho_principal, caminho_overflow)?;
+    let mut hash = IndiceHash::criar(destino, R::LAYOUT, capacidade)?;
+    // Sem a marca de sujo a cada inserção: o arquivo só passa a valer depois do rename
+    for (chave, ponteiro) in entradas {
+        loop {
+            let (posicao, mut balde) = hash.balde_para(chave)?;
+            if balde.entradas.len() < capacidade {
+                balde.entradas.push((chave, ponteiro));
+                hash.gravar_balde(posicao, &balde)?;
+                hash.num_chaves += 1;
+                break;
+            }
+            hash.dividir(posicao, balde)?;
+        }
+    }
+    hash.gravar_meta()?;
+    hash.arquivo.sync_all()?;
+    Ok(hash)
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn caminho_hash(&self) -> String {
+        caminho_hash(&self.caminho_principal)
+    }
+
+    // Refaz o índice hash a partir dos dados (num temporário, trocado no fim)
+    pub fn construir_hash(&self, capacidade: usize) -> Resultado<IndiceHash> {
+        let caminho = self.caminho_hash();
+        let temporario = caminho_temporario(&caminho);
+        construir_hash::<R>(&self.caminho_principal, &self.caminho_overflow, &temporario, capacidade)?;
+        std::fs::rename(&temporario, &caminho)?;
+        IndiceHash::abrir(&caminho, R::LAYOUT)
+    }
+
+    // Índice hash da entidade, se existir. Como a árvore, é mantido em toda
+    // escrita, esteja ou não configurado para as consultas.
+    pub(crate) fn abrir_hash(&self) -> Resultado<Option<IndiceHash>> {
+        let caminho = self.caminho_hash();
+        if !Path::new(&caminho).exists() {
+            return Ok(None);
+        }
+        IndiceHash::abrir(&caminho, R::LAYOUT).map(Some)
+    }
+
+    // Consulta pontual pelo índice hash (principal e overflow). Sem o arquivo do
+    // índice, Erro::IndiceInconsistente: construa-o antes.
+    pub fn consultar_com_hash(&self, chave: i64) -> Resultado<Option<R>> {
+        let Some(mut hash) = self.abrir_hash()? else {
+            return Err(Erro::IndiceInconsistente(format!("{} não existe; construa o índice hash", self.caminho_hash())));
+        };
+        Ok(self.localizar_no_hash(&mut hash, chave)?.map(|(_, _, registro)| registro))
+    }
+
+    pub(crate) fn localizar_no_hash(&self, hash: &mut IndiceHash, chave: i64) -> Resultado<Option<(ArquivoAlvo, u64, R)>> {
+        match hash.buscar(chave)? {
+            Some(ponteiro) => self.registro_apontado(ponteiro, chave),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::produto::Produto;
+
+    // Baldes pequenos forçam várias divisões e dobras do diretório
+    #[test]
+    fn hash_insere_remove_e_reabre() {
+        let dir = std::env::temp_dir().join(format!("aed2_hash_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let caminho = dir.join("a.hash").to_string_lossy().to_string();
+        let ponteiro = |posicao| Ponteiro { alvo: ArquivoAlvo::Principal, posicao };
+
+        let mut hash = IndiceHash::criar(&caminho, Produto::LAYOUT, 3).unwrap();
+        for chave in -300..300 {
+            hash.inserir(chave, ponteiro(chave as u64)).unwrap();
+        }
+        hash.inserir(7, ponteiro(1)).unwrap();
+        for chave in (-300..300).step_by(4) {
+            assert!(hash.remover(chave).unwrap());
+        }
+        assert!(!hash.remover(-300).unwrap());
+        assert!(hash.profundidade > 6);
+
+        let mut hash = IndiceHash::abrir(&caminho, Produto::LAYOUT).unwrap();
+        assert_eq!(hash.num_chaves, 450);
+        for chave in -300..300i64 {
+            let esperado = match chave {
+                7 => Some(ponteiro(1)),
+                c if (c + 300) % 4 == 0 => None,
+                c => Some(ponteiro(c as u64)),
+            };
+            assert_eq!(hash.buscar(chave).unwrap(), esperado, "chave {}", chave);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::arquivo::{ler_registro_em, localizar_sequencial, percorrer_brutos, ArquivoSequencial};
+use crate::cabecalho::{self, Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::manifesto::caminho_temporario;
+use crate::registro::{Registro, CHAVE_REMOVIDA};
+
+// Índice ordenado do overflow (produtos_overflow.dat.idx). O overflow recebe
+// os registros na ordem de inserção; este arquivo guarda as chaves dele em
+// ordem, cada uma com a posição do registro, para que a consulta ao overflow
+// seja uma busca binária em vez de uma varredura com uma leitura por registro.
+//
+// Layout: o cabeçalho comum (número de entradas em num_registros), o número
+// de registros do overflow que o índice cobre (u64) e as entradas
+// [chave i64][posição u64] em ordem de chave; chaves repetidas ficam na ordem
+// de inserção.
+//
+// Como a árvore e o hash, a entrada nova é gravada antes do registro e a
+// removida sai depois dele. O índice só é usado se cobre exatamente os
+// registros que o cabeçalho do overflow indica: se uma queda (ou uma versão
+// antiga do programa) deixar os dois diferentes, a consulta volta à varredura
+// e a próxima inserção refaz o índice. Uma entrada que sobrar de uma remoção
+// aponta para um registro com outra chave e é ignorada.
+const TAMANHO_META: u64 = TAMANHO_CABECALHO as u64 + 8;
+const TAMANHO_ENTRADA: u64 = 16;
+
+pub fn caminho_indice_overflow(caminho_overflow: &str) -> String {
+    format!("{}.idx", caminho_overflow)
+}
+
+#[derive(Debug)]
+pub struct IndiceOverflow {
+    caminho: String,
+    arquivo: File,
+    layout: LayoutRegistro,
+    cabecalho: Cabecalho,
+    // Registros do overflow (removidos inclusive) que o índice cobre
+    pub cobertos: u64,
+}
+
+impl IndiceOverflow {
+    // Grava as entradas, já ordenadas, num arquivo novo
+    pub fn criar(caminho: &str, layout: LayoutRegistro, entradas: &[(i64, u64)], cobertos: u64) -> Resultado<Self> {
+        let cabecalho = Cabecalho::novo(TipoArquivo::IndiceOverflow, layout, 0, entradas.len() as u64);
+        let mut saida = BufWriter::new(File::create(caminho)?);
+        saida.write_all(&meta(&cabecalho, cobertos))?;
+        for &entrada in entradas {
+            saida.write_all(&bytes_entrada(entrada))?;
+        }
+        saida.flush()?;
+        saida.get_ref().sync_all()?;
+        drop(saida);
+        IndiceOverflow::abrir(caminho, layout)
+    }
+
+    pub fn abrir(caminho: &str, layout: LayoutRegistro) -> Resultado<Self> {
+        let mut arquivo = OpenOptions::new().read(true).write(true).open(caminho)?;
+        let cabecalho = Cabecalho::ler(&mut arquivo)?;
+        cabecalho.validar(TipoArquivo::IndiceOverflow, layout)?;
+        let mut cobertos = [0u8; 8];
+        arquivo.read_exact(&mut cobertos)?;
+        if arquivo.metadata()?.len() < TAMANHO_META + cabecalho.num_registros * TAMANHO_ENTRADA {
+            return Err(Erro::IndiceInconsistente(format!(
+                "{}: o cabeçalho indica {} entradas, mas o arquivo é menor",
+                caminho, cabecalho.num_registros
+            )));
+        }
+        Ok(IndiceOverflow {
+            caminho: caminho.to_string(),
+            arquivo,
+            layout,
+            cabecalho,
+            cobertos: u64::from_le_bytes(cobertos),
+        })
+    }
+
+    pub fn num_entradas(&self) -> u64 {
+        self.cabecalho.num_registros
+    }
+
+    fn entrada(&mut self, i: u64) -> Resultado<(i64, u64)> {
+        let mut bytes = [0u8; TAMANHO_ENTRADA as usize];
+        self.arquivo.seek(SeekFrom::Start(TAMANHO_META + i * TAMANHO_ENTRADA))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        Ok(ler_entrada(&bytes))
+    }
+
+    pub fn entradas(&mut self) -> Resultado<Vec<(i64, u64)>> {
+        let mut bytes = vec![0u8; (self.num_entradas() * TAMANHO_ENTRADA) as usize];
+        self.arquivo.seek(SeekFrom::Start(TAMANHO_META))?;
+        self.arquivo.read_exact(&mut bytes)?;
+        Ok(bytes.chunks_exact(TAMANHO_ENTRADA as usize).map(ler_entrada).collect())
+    }
+
+    // Posições de todas as entradas com a chave, a partir da primeira achada
+    // por busca binária (uma leitura de 16 bytes por passo)
+    pub fn buscar(&mut self, chave: i64) -> Resultado<Vec<u64>> {
+        let (mut esq, mut dir) = (0, self.num_entradas());
+        while esq < dir {
+            let meio = (esq + dir) / 2;
+            if self.entrada(meio)?.0 < chave {
+                esq = meio + 1;
+            } else {
+                dir = meio;
+            }
+        }
+        let mut posicoes = Vec::new();
+        for i in esq..self.num_entradas() {
+            let (encontrada, posicao) = self.entrada(i)?;
+            if encontrada != chave {
+                break;
+            }
+            posicoes.push(posicao);
+        }
+        Ok(posicoes)
+    }
+
+    // Entrada do registro que vai ser acrescentado ao overflow. Chave maior ou
+    // igual à última (o caso comum, com chaves crescentes) é gravada depois da
+    // última entrada e só então o cabeçalho passa a contá-la; as demais
+    // obrigam a reescrever o índice num temporário.
+    pub fn inserir(&mut self, chave: i64, posicao: u64) -> Resultado<()> {
+        let n = self.num_entradas();
+        if n == 0 || self.entrada(n - 1)?.0 <= chave {
+            self.arquivo.seek(SeekFrom::Start(TAMANHO_META + n * TAMANHO_ENTRADA))?;
+            self.arquivo.write_all(&bytes_entrada((chave, posicao)))?;
+            self.arquivo.sync_data()?;
+            self.cabecalho.num_registros += 1;
+            self.cobertos += 1;
+            // Cabeçalho e cobertos numa escrita só
+            self.arquivo.seek(SeekFrom::Start(0))?;
+            self.arquivo.write_all(&meta(&self.cabecalho, self.cobertos))?;
+            self.arquivo.sync_data()?;
+            return Ok(());
+        }
+        let mut entradas = self.entradas()?;
+        let lugar = entradas.partition_point(|&(c, _)| c <= chave);
+        entradas.insert(lugar, (chave, posicao));
+        let cobertos = self.cobertos + 1;
+        self.reescrever(&entradas, cobertos)
+    }
+
+    // Tira a entrada do registro removido logicamente; false se ela não existia
+    pub fn remover(&mut self, chave: i64, posicao: u64) -> Resultado<bool> {
+        let mut entradas = self.entradas()?;
+        let antes = entradas.len();
+        entradas.retain(|&entrada| entrada != (chave, posicao));
+        if entradas.len() == antes {
+            return Ok(false);
+        }
+        let cobertos = self.cobertos;
+        self.reescrever(&entradas, cobertos)?;
+        Ok(true)
+    }
+
+    fn reescrever(&mut self, entradas: &[(i64, u64)], cobertos: u64) -> Resultado<()> {
+        let temporario = caminho_temporario(&self.caminho);
+        IndiceOverflow::criar(&temporario, self.layout, entradas, cobertos)?;
+        std::fs::rename(&temporario, &self.caminho)?;
+        *self = IndiceOverflow::abrir(&self.caminho, self.layout)?;
+        Ok(())
+    }
+}
+
+fn meta(cabecalho: &Cabecalho, cobertos: u64) -> Vec<u8> {
+    let mut bytes = cabecalho.to_bytes().to_vec();
+    bytes.extend_from_slice(&cobertos.to_le_bytes());
+    bytes
+}
+
+fn bytes_entrada((chave, posicao): (i64, u64)) -> [u8; TAMANHO_ENTRADA as usize] {
+    let mut bytes = [0u8; TAMANHO_ENTRADA as usize];
+    bytes[..8].copy_from_slice(&chave.to_le_bytes());
+    bytes[8..].copy_from_slice(&posicao.to_le_bytes());
+    bytes
+}
+
+fn ler_entrada(bytes: &[u8]) -> (i64, u64) {
+    (
+        i64::from_le_bytes(bytes[..8].try_into().unwrap()),
+        u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+    )
+}
+
+// Índice com as chaves dos registros não removidos do overflow, cobrindo os
+// registros que o cabeçalho dele indica
+pub fn construir_indice_overflow<R: Registro>(caminho_overflow: &str, destino: &str) -> Resultado<IndiceOverflow> {
+    let mut entradas = Vec::new();
+    let mut cobertos = 0;
+    if !std::fs::metadata(caminho_overflow).map(|m| m.len() == 0).unwrap_or(true) {
+        cobertos = cabecalho::ler_cabecalho_dados(&mut File::open(caminho_overflow)?, R::LAYOUT)?.num_registros;
+        let fim = TAMANHO_CABECALHO as u64 + cobertos * R::TAMANHO_REGISTRO as u64;
+        percorrer_brutos::<R>(caminho_overflow, |posicao, bytes| {
+            let chave = R::chave_de_bytes(bytes);
+            if posicao < fim && chave != CHAVE_REMOVIDA {
+                entradas.push((chave, posicao));
+            }
+            Ok(())
+        })?;
+    }
+    // Estável: chaves repetidas continuam na ordem do overflow
+    entradas.sort_by_key(|&(chave, _)| chave);
+    IndiceOverflow::criar(destino, R::LAYOUT, &entradas, cobertos)
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn caminho_indice_overflow(&self) -> String {
+        caminho_indice_overflow(&self.caminho_overflow)
+    }
+
+    // Refaz o índice do overflow a partir dele (num temporário, trocado no fim)
+    pub fn construir_indice_overflow(&self) -> Resultado<IndiceOverflow> {
+        let caminho = self.caminho_indice_overflow();
+        let temporario = caminho_temporario(&caminho);
+        construir_indice_overflow::<R>(&self.caminho_overflow, &temporario)?;
+        std::fs::rename(&temporario, &caminho)?;
+        IndiceOverflow::abrir(&caminho, R::LAYOUT)
+    }
+
+    // Índice do overflow, se existir e estiver em dia com ele
+    pub(crate) fn abrir_indice_overflow(&self) -> Resultado<Option<IndiceOverflow>> {
+        let caminho = self.caminho_indice_overflow();
+        if !Path::new(&caminho).exists() {
+            return Ok(None);
+        }
+        let Ok(indice) = IndiceOverflow::abrir(&caminho, R::LAYOUT) else {
+            return Ok(None);
+        };
+        Ok((indice.cobertos == self.num_registros_overflow()?).then_some(indice))
+    }
+
+    // Registro com a chave no overflow e sua posição: busca binária pelo
+    // índice ordenado ou, sem ele em dia, varredura do overflow
+    pub(crate) fn localizar_no_overflow(&self, chave: i64) -> Resultado<Option<(u64, R)>> {
+        if !Path::new(&self.caminho_overflow).exists() {
+            return Ok(None);
+        }
+        let Some(mut indice) = self.abrir_indice_overflow()? else {
+            return match localizar_sequencial::<R>(&self.caminho_overflow, chave)? {
+                Some(posicao) => Ok(Some((posicao, ler_registro_em::<R>(&self.caminho_overflow, posicao)?))),
+                None => Ok(None),
+            };
+        };
+        let fim = TAMANHO_CABECALHO as u64 + indice.cobertos * R::TAMANHO_REGISTRO as u64;
+        for posicao in indice.buscar(chave)? {
+            if posicao >= fim {
+                continue;
+            }
+            let registro = ler_registro_em::<R>(&self.caminho_overflow, posicao)?;
+            if registro.chave() == chave {
+                return Ok(Some((posicao, registro)));
+            }
+        }
+        Ok(None)
+    }
+
+    // Antes de gravar um registro novo no overflow; um índice que não estiver
+    // em dia (ou que ainda não exista) é refeito antes
+    pub(crate) fn indice_overflow_antes_da_insercao(&self, chave: i64, posicao: u64) -> Resultado<()> {
+        let mut indice = match self.abrir_indice_overflow()? {
+            Some(indice) => indice,
+            None => self.construir_indice_overflow()?,
+        };
+        indice.inserir(chave, posicao)
+    }
+
+    pub(crate) fn indice_overflow_depois_da_remocao(&self, chave: i64, posicao: u64) -> Resultado<()> {
+        if let Some(mut indice) = self.abrir_indice_overflow()? {
+            indice.remover(chave, posicao)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::indice::construir_indice_parcial;
+    use crate::data_hora::DataHora;
+    use crate::pedido::Pedido;
+    use crate::preco::Preco;
+
+    fn pedido(order_id: i64, product_id: i64) -> Pedido {
+        Pedido { order_id, user_id: 0, event_time: DataHora::default(), product_id, price: Preco::em_centavos(100) }
+    }
+
+    // Chaves fora de ordem, repetidas depois de uma remoção e um índice apagado
+    // (refeito na inserção seguinte) devem continuar sendo achadas
+    #[test]
+    fn indice_overflow_acompanha_insercoes_e_remocoes() {
+        let dir = std::env::temp_dir().join(format!("aed2_overflow_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("pedidos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("pedidos_overflow.dat").to_string_lossy().to_string();
+        let mut pedidos: Vec<Pedido> = (0..1000).map(|i| pedido(i * 2, 0)).collect();
+        gravar_ordenados(&mut pedidos, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 10, Pedido::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<Pedido>::novo(&principal, &overflow);
+
+        let inseridas = [1999, 1, 1001, 3001, 3003, 5, 1003];
+        for chave in inseridas {
+            arquivo.inserir(&pedido(chave, 1), &mut indice).unwrap();
+        }
+        arquivo.remover(&indice, 1001).unwrap();
+        arquivo.inserir(&pedido(1001, 2), &mut indice).unwrap();
+
+        let mut idx = arquivo.abrir_indice_overflow().unwrap().unwrap();
+        let chaves: Vec<i64> = idx.entradas().unwrap().iter().map(|&(c, _)| c).collect();
+        assert_eq!(chaves, vec![1, 5, 1001, 1003, 1999, 3001, 3003]);
+        assert_eq!(idx.cobertos, 8);
+        assert_eq!(arquivo.buscar_no_overflow(1001).unwrap().unwrap().product_id, 2);
+        assert!(arquivo.buscar_no_overflow(4).unwrap().is_none());
+
+        std::fs::remove_file(arquivo.caminho_indice_overflow()).unwrap();
+        assert_eq!(arquivo.buscar_no_overflow(3003).unwrap().unwrap().order_id, 3003);
+        arquivo.inserir(&pedido(7, 1), &mut indice).unwrap();
+        let mut idx = arquivo.abrir_indice_overflow().unwrap().unwrap();
+        assert_eq!(idx.num_entradas(), 8);
+        for chave in inseridas.into_iter().chain([7]) {
+            assert_eq!(arquivo.buscar_no_overflow(chave).unwrap().unwrap().order_id, chave);
+            assert_eq!(idx.buscar(chave).unwrap().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::arquivo::{percorrer_validos, ArquivoSequencial, LIMITE_OVERFLOW};
+use crate::cabecalho::{Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::data_hora::DataHora;
+use crate::erro::{Erro, Resultado};
+use crate::indice::{IndiceParcial, LayoutRegistro};
+use crate::manifesto::caminho_temporario;
+use crate::registro::Registro;
+
+// Índice secundário de um campo: pares (valor, chave primária) ordenados, num
+// arquivo ao lado do de dados (pedidos.dat.user_id.idx) com o cabeçalho comum.
+//
+// O arquivo ordenado só é regravado na construção, na reconstrução da entidade
+// e na compactação. Inserções, remoções e atualizações vão para um log de
+// pendências (pedidos.dat.user_id.idx.pendentes) com entradas [op u8][valor]
+// [chave i64], op '+' ou '-', aplicadas em ordem por cima do arquivo ordenado
+// na consulta. Quando o log passa de LIMITE_OVERFLOW das entradas, os dois são
+// juntados num arquivo ordenado novo.
+//
+// A consulta confere cada chave encontrada no registro de dados, então uma
+// pendência que ficou para trás numa queda nunca devolve registro errado. O '+'
+// é gravado antes da escrita dos dados e o '-' depois, para que uma queda no
+// meio deixe no máximo entradas a mais (descartadas na conferência), não a menos.
+
+// Valor do campo codificado para que a ordem dos bytes seja a ordem dos valores
+pub const TAMANHO_VALOR: usize = 32;
+const TAMANHO_ENTRADA: usize = TAMANHO_VALOR + 8;
+const TAMANHO_PENDENCIA: usize = 1 + TAMANHO_ENTRADA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoValor {
+    Inteiro,
+    Texto,
+    DataHora,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValorSecundario {
+    Inteiro(i64),
+    Texto(String),
+    DataHora(DataHora),
+}
+
+impl fmt::Display for ValorSecundario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValorSecundario::Inteiro(v) => write!(f, "{}", v),
+            ValorSecundario::Texto(v) => write!(f, "{:?}", v),
+            ValorSecundario::DataHora(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl ValorSecundario {
+    // Valor digitado pelo usuário para um campo do tipo dado. Textos ficam como
+    // vieram: os registros guardam os espaços das pontas (textos.rs).
+    pub fn interpretar(tipo: TipoValor, texto: &str) -> Result<Self, String> {
+        match tipo {
+            TipoValor::Inteiro => texto
+                .trim()
+                .parse()
+                .map(ValorSecundario::Inteiro)
+                .map_err(|_| format!("esperado um número inteiro: {:?}", texto)),
+            TipoValor::Texto => Ok(ValorSecundario::Texto(texto.to_string())),
+            TipoValor::DataHora => texto.parse().map(ValorSecundario::DataHora),
+        }
+    }
+
+    // Inteiros em big-endian com o bit de sinal invertido; textos completados com
+    // zeros (e cortados em TAMANHO_VALOR - 1 bytes, acima de qualquer campo atual);
+    // datas pelo instante, como inteiros (o fuso não entra na ordem)
+    fn codificar(&self) -> [u8; TAMANHO_VALOR] {
+        let mut bytes = [0u8; TAMANHO_VALOR];
+        match self {
+            ValorSecundario::Inteiro(v) => {
+                bytes[0] = 1;
+                bytes[1..9].copy_from_slice(&((*v as u64) ^ (1 << 63)).to_be_bytes());
+            }
+            ValorSecundario::DataHora(v) => {
+                bytes[0] = 3;
+                bytes[1..9].copy_from_slice(&((v.millis as u64) ^ (1 << 63)).to_be_bytes());
+            }
+            ValorSecundario::Texto(v) => {
+                bytes[0] = 2;
+                let texto = v.as_bytes();
+                let n = texto.len().min(TAMANHO_VALOR - 1);
+                bytes[1..1 + n].copy_from_slice(&texto[..n]);
+            }
+        }
+        bytes
+    }
+}
+
+// Campos que aceitam índice secundário no tipo de registro
+pub fn tipo_do_campo<R: Registro>(campo: &str) -> Resultado<TipoValor> {
+    R::CAMPOS_SECUNDARIOS
+        .iter()
+        .find(|(nome, _)| *nome == campo)
+        .map(|(_, tipo)| *tipo)
+        .ok_or_else(|| {
+            let nomes: Vec<&str> = R::CAMPOS_SECUNDARIOS.iter().map(|(nome, _)| *nome).collect();
+            Erro::Configuracao(format!(
+                "{} não tem índice secundário para o campo {} (campos: {})",
+                R::TIPO.nome(),
+                campo,
+                nomes.join(", ")
+            ))
+        })
+}
+
+pub fn caminho_indice_secundario(caminho_dados: &str, campo: &str) -> String {
+    format!("{}.{}.idx", caminho_dados, campo)
+}
+
+pub fn caminho_pendencias(caminho_indice: &str) -> String {
+    format!("{}.pendentes", caminho_indice)
+}
+
+fn entrada(valor: &ValorSecundario, chave: i64) -> [u8; TAMANHO_ENTRADA] {
+    let mut bytes = [0u8; TAMANHO_ENTRADA];
+    bytes[..TAMANHO_VALOR].copy_from_slice(&valor.codificar());
+    bytes[TAMANHO_VALOR..].copy_from_slice(&chave.to_le_bytes());
+    bytes
+}
+
+fn chave_da_entrada(bytes: &[u8]) -> i64 {
+    i64::from_le_bytes(bytes[TAMANHO_VALOR..TAMANHO_ENTRADA].try_into().unwrap())
+}
+
+// Ordena por valor e, dentro do mesmo valor, pela chave
+fn ordenar(entradas: &mut Vec<[u8; TAMANHO_ENTRADA]>) {
+    entradas.sort_by(|a, b| a[..TAMANHO_VALOR].cmp(&b[..TAMANHO_VALOR]).then(chave_da_entrada(a).cmp(&chave_da_entrada(b))));
+    entradas.dedup();
+}
+
+fn gravar(caminho: &str, layout: LayoutRegistro, entradas: &[[u8; TAMANHO_ENTRADA]]) -> Resultado<()> {
+    let mut arquivo = File::create(caminho)?;
+    Cabecalho::novo(TipoArquivo::IndiceSecundario, layout, 0, entradas.len() as u64).escrever(&mut arquivo)?;
+    let mut saida = BufWriter::new(&mut arquivo);
+    for entrada in entradas {
+        saida.write_all(entrada)?;
+    }
+    saida.flush()?;
+    drop(saida);
+    arquivo.sync_all()?;
+    Ok(())
+}
+
+fn abrir(caminho: &str, layout: LayoutRegistro) -> Resultado<(File, u64)> {
+    let mut arquivo = File::open(caminho)?;
+    let cabecalho = Cabecalho::ler(&mut arquivo)?;
+    cabecalho.validar(TipoArquivo::IndiceSecundario, layout)?;
+    let esperado = TAMANHO_CABECALHO as u64 + cabecalho.num_registros * TAMANHO_ENTRADA as u64;
+    if arquivo.metadata()?.len() != esperado {
+        return Err(Erro::IndiceInconsistente(format!(
+            "{} deveria ter {} bytes para {} entradas",
+            caminho, esperado, cabecalho.num_registros
+        )));
+    }
+    Ok((arquivo, cabecalho.num_registros))
+}
+
+fn ler_entradas(caminho: &str, layout: LayoutRegistro) -> Resultado<Vec<[u8; TAMANHO_ENTRADA]>> {
+    let (arquivo, num_entradas) = abrir(caminho, layout)?;
+    let mut leitor = BufReader::new(arquivo);
+    let mut entradas = Vec::with_capacity(num_entradas as usize);
+    let mut buffer = [0u8; TAMANHO_ENTRADA];
+    for _ in 0..num_entradas {
+        leitor.read_exact(&mut buffer)?;
+        entradas.push(buffer);
+    }
+    Ok(entradas)
+}
+
+// Pendências completas, na ordem em que foram gravadas (uma entrada cortada no
+// fim, de uma queda durante a gravação, é ignorada)
+fn ler_pendencias(caminho_indice: &str) -> Resultado<Vec<(u8, [u8; TAMANHO_ENTRADA])>> {
+    let caminho = caminho_pendencias(caminho_indice);
+    if !Path::new(&caminho).exists() {
+        return Ok(Vec::new());
+    }
+    let bytes = std::fs::read(&caminho)?;
+    Ok(bytes
+        .chunks_exact(TAMANHO_PENDENCIA)
+        .map(|p| (p[0], p[1..].try_into().unwrap()))
+        .collect())
+}
+
+// Grava o índice de um campo a partir de arquivos de dados (principal e overflow)
+pub fn construir<R: Registro>(fontes: &[&str], campo: &str, destino: &str) -> Resultado<usize> {
+    tipo_do_campo::<R>(campo)?;
+    let mut entradas = Vec::new();
+    for fonte in fontes {
+        if !Path::new(fonte).exists() || std::fs::metadata(fonte)?.len() == 0 {
+            continue;
+        }
+        percorrer_validos::<R>(fonte, |registro| {
+            if let Some(valor) = registro.valor_secundario(campo) {
+                entradas.push(entrada(&valor, registro.chave()));
+            }
+            Ok(())
+        })?;
+    }
+    ordenar(&mut entradas);
+    gravar(destino, R::LAYOUT, &entradas)?;
+    Ok(entradas.len())
+}
+
+// Acrescenta uma pendência ('+' ou '-'). Sem o arquivo do índice não há o que
+// manter: a próxima construção parte dos dados.
+fn registrar(caminho_indice: &str, layout: LayoutRegistro, op: u8, valor: &ValorSecundario, chave: i64) -> Resultado<()> {
+    if !Path::new(caminho_indice).exists() {
+        return Ok(());
+    }
+    let caminho = caminho_pendencias(caminho_indice);
+    let mut log = OpenOptions::new().create(true).append(true).open(&caminho)?;
+    // Uma pendência cortada por uma queda anterior desalinharia as seguintes
+    let sobra = log.metadata()?.len() % TAMANHO_PENDENCIA as u64;
+    if sobra > 0 {
+        log.set_len(log.metadata()?.len() - sobra)?;
+    }
+    let mut bytes = Vec::with_capacity(TAMANHO_PENDENCIA);
+    bytes.push(op);
+    bytes.extend_from_slice(&entrada(valor, chave));
+    log.write_all(&bytes)?;
+    log.sync_data()?;
+
+    let (_, num_entradas) = abrir(caminho_indice, layout)?;
+    let num_pendencias = log.metadata()?.len() / TAMANHO_PENDENCIA as u64;
+    if num_pendencias as f64 > (num_entradas as f64 * LIMITE_OVERFLOW).max(64.0) {
+        compactar(caminho_indice, layout)?;
+    }
+    Ok(())
+}
+
+// Junta as pendências ao arquivo ordenado e esvazia o log
+pub fn compactar(caminho_indice: &str, layout: LayoutRegistro) -> Resultado<()> {
+    let mut entradas = ler_entradas(caminho_indice, layout)?;
+    for (op, pendencia) in ler_pendencias(caminho_indice)? {
+        entradas.retain(|e| *e != pendencia);
+        if op == b'+' {
+            entradas.push(pendencia);
+        }
+    }
+    ordenar(&mut entradas);
+    let temporario = caminho_temporario(caminho_indice);
+    gravar(&temporario, layout, &entradas)?;
+    std::fs::rename(&temporario, caminho_indice)?;
+    // Se cair antes disso, reaplicar as pendências no arquivo novo dá o mesmo resultado
+    let pendencias = caminho_pendencias(caminho_indice);
+    if Path::new(&pendencias).exists() {
+        std::fs::remove_file(&pendencias)?;
+    }
+    Ok(())
+}
+
+// Chaves com valor entre min e max no arquivo ordenado (busca binária pelo
+// primeiro) e nas pendências
+fn chaves_na_faixa(
+    caminho_indice: &str,
+    layout: LayoutRegistro,
+    min: &ValorSecundario,
+    max: &ValorSecundario,
+) -> Resultado<BTreeSet<i64>> {
+    let (min, max) = (min.codificar(), max.codificar());
+    let na_faixa = |entrada: &[u8]| min[..] <= entrada[..TAMANHO_VALOR] && entrada[..TAMANHO_VALOR] <= max[..];
+    let (mut arquivo, num_entradas) = abrir(caminho_indice, layout)?;
+    let mut buffer = [0u8; TAMANHO_ENTRADA];
+    let mut ler = |arquivo: &mut File, i: u64| -> Resultado<[u8; TAMANHO_ENTRADA]> {
+        arquivo.seek(SeekFrom::Start(TAMANHO_CABECALHO as u64 + i * TAMANHO_ENTRADA as u64))?;
+        arquivo.read_exact(&mut buffer)?;
+        Ok(buffer)
+    };
+    let (mut esq, mut dir) = (0, num_entradas);
+    while esq < dir {
+        let meio = (esq + dir) / 2;
+        if ler(&mut arquivo, meio)?[..TAMANHO_VALOR] < min[..] {
+            esq = meio + 1;
+        } else {
+            dir = meio;
+        }
+    }
+    let mut chaves = BTreeSet::new();
+    let mut leitor = BufReader::new(arquivo);
+    leitor.seek(SeekFrom::Start(TAMANHO_CABECALHO as u64 + esq * TAMANHO_ENTRADA as u64))?;
+    for _ in esq..num_entradas {
+        leitor.read_exact(&mut buffer)?;
+        if !na_faixa(&buffer) {
+            break;
+        }
+        chaves.insert(chave_da_entrada(&buffer));
+    }
+    for (op, pendencia) in ler_pendencias(caminho_indice)? {
+        if !na_faixa(&pendencia) {
+            continue;
+        }
+        if op == b'+' {
+            chaves.insert(chave_da_entrada(&pendencia));
+        } else {
+            chaves.remove(&chave_da_entrada(&pendencia));
+        }
+    }
+    Ok(chaves)
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn caminho_indice_secundario(&self, campo: &str) -> String {
+        caminho_indice_secundario(&self.caminho_principal, campo)
+    }
+
+    // (Re)constrói do zero os índices dos campos em indices_secundarios
+    pub fn construir_indices_secundarios(&self) -> Resultado<Vec<(String, usize)>> {
+        let mut construidos = Vec::new();
+        for campo in &self.indices_secundarios {
+            let caminho = self.caminho_indice_secundario(campo);
+            let temporario = caminho_temporario(&caminho);
+            let total = construir::<R>(&[&self.caminho_principal, &self.caminho_overflow], campo, &temporario)?;
+            std::fs::rename(&temporario, &caminho)?;
+            let pendencias = caminho_pendencias(&caminho);
+            if Path::new(&pendencias).exists() {
+                std::fs::remove_file(&pendencias)?;
+            }
+            construidos.push((campo.clone(), total));
+        }
+        Ok(construidos)
+    }
+
+    // Registros (do principal e do overflow) com o valor no campo, em ordem de
+    // chave. Usa o índice secundário do campo se ele existir; senão percorre os dados.
+    pub fn buscar_por_campo(&self, indice: &IndiceParcial, campo: &str, valor: &ValorSecundario) -> Resultado<Vec<R>> {
+        self.buscar_por_faixa(indice, campo, valor, valor)
+    }
+
+    // Registros com o valor do campo entre min e max (inclusive), em ordem do
+    // valor e, no mesmo valor, da chave
+    pub fn buscar_por_faixa(&self, indice: &IndiceParcial, campo: &str, min: &ValorSecundario, max: &ValorSecundario) -> Resultado<Vec<R>> {
+        tipo_do_campo::<R>(campo)?;
+        let na_faixa = |registro: &R| registro.valor_secundario(campo).is_some_and(|v| *min <= v && v <= *max);
+        let caminho = self.caminho_indice_secundario(campo);
+        let mut encontrados = Vec::new();
+        if !Path::new(&caminho).exists() {
+            for fonte in [&self.caminho_principal, &self.caminho_overflow] {
+                if Path::new(fonte).exists() && std::fs::metadata(fonte)?.len() > 0 {
+                    percorrer_validos::<R>(fonte, |registro| {
+                        if na_faixa(&registro) {
+                            encontrados.push(registro);
+                        }
+                        Ok(())
+                    })?;
+                }
+            }
+        } else {
+            for chave in chaves_na_faixa(&caminho, R::LAYOUT, min, max)? {
+                // Confere no registro: pendências de uma operação interrompida podem sobrar
+                if let Some((_, _, registro)) = self.localizar(indice, chave)?
+                    && na_faixa(&registro)
+                {
+                    encontrados.push(registro);
+                }
+            }
+        }
+        encontrados.sort_by_cached_key(|r| (r.valor_secundario(campo), r.chave()));
+        Ok(encontrados)
+    }
+
+    // Manutenção dos índices secundários em volta de uma escrita de dados: chamar
+    // com antes = None/depois = Some na inserção, o contrário na remoção e os
+    // dois na atualização. Os '+' são gravados por `antes_da_escrita`, os '-' por
+    // `depois_da_escrita`.
+    pub(crate) fn secundarios_antes_da_escrita(&self, antigo: Option<&R>, novo: Option<&R>) -> Resultado<()> {
+        self.registrar_secundarios(antigo, novo, b'+')
+    }
+
+    pub(crate) fn secundarios_depois_da_escrita(&self, antigo: Option<&R>, novo: Option<&R>) -> Resultado<()> {
+        self.registrar_secundarios(antigo, novo, b'-')
+    }
+
+    // Campos com índice a manter: os configurados e os que já têm arquivo (criados
+    // por outra configuração ou pelo menu) para nenhum ficar desatualizado
+    pub(crate) fn campos_com_indice_secundario(&self) -> Vec<String> {
+        R::CAMPOS_SECUNDARIOS
+            .iter()
+            .map(|(campo, _)| campo.to_string())
+            .filter(|campo| {
+                self.indices_secundarios.contains(campo) || Path::new(&self.caminho_indice_secundario(campo)).exists()
+            })
+            .collect()
+    }
+
+    fn registrar_secundarios(&self, antigo: Option<&R>, novo: Option<&R>, op: u8) -> Resultado<()> {
+        // registrar ignora os campos sem arquivo de índice
+        for (campo, _) in R::CAMPOS_SECUNDARIOS {
+            let valor_antigo = antigo.and_then(|r| r.valor_secundario(campo).map(|v| (v, r.chave())));
+            let valor_novo = novo.and_then(|r| r.valor_secundario(campo).map(|v| (v, r.chave())));
+            if valor_antigo == valor_novo {
+                continue;
+            }
+            let pendencia = if op == b'+' { valor_novo } else { valor_antigo };
+            if let Some((valor, chave)) = pendencia {
+                registrar(&self.caminho_indice_secundario(campo), R::LAYOUT, op, &valor, chave)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::indice::construir_indice_parcial;
+    use crate::pedido::Pedido;
+    use crate::preco::Preco;
+
+    fn pedido(order_id: i64, user_id: i64) -> Pedido {
+        Pedido { order_id, user_id, event_time: DataHora::default(), product_id: 0, price: Preco::em_centavos(100) }
+    }
+
+    fn chaves(arquivo: &ArquivoSequencial<Pedido>, indice: &IndiceParcial, user_id: i64) -> Vec<i64> {
+        arquivo
+            .buscar_por_campo(indice, "user_id", &ValorSecundario::Inteiro(user_id))
+            .unwrap()
+            .iter()
+            .map(|p| p.order_id)
+            .collect()
+    }
+
+    // Inserção, atualização e remoção passam pelas pendências; a compactação e
+    // uma pendência cortada no fim não mudam o resultado
+    #[test]
+    fn indice_secundario_acompanha_as_escritas() {
+        let dir = std::env::temp_dir().join(format!("aed2_secundario_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("pedidos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("pedidos_overflow.dat").to_string_lossy().to_string();
+        let mut pedidos: Vec<Pedido> = (0..100).map(|i| pedido(i, i % 3 - 1)).collect();
+        gravar_ordenados(&mut pedidos, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 10, Pedido::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<Pedido>::novo(&principal, &overflow)
+            .com_indices_secundarios(&["user_id".to_string()]);
+        arquivo.construir_indices_secundarios().unwrap();
+        let caminho = arquivo.caminho_indice_secundario("user_id");
+
+        // Negativos antes dos positivos na ordem dos bytes
+        assert_eq!(chaves(&arquivo, &indice, -1).len(), 34);
+        // Faixa em ordem de valor e, no mesmo valor, de chave
+        let faixa = arquivo
+            .buscar_por_faixa(&indice, "user_id", &ValorSecundario::Inteiro(-1), &ValorSecundario::Inteiro(0))
+            .unwrap();
+        assert_eq!(faixa.len(), 67);
+        assert!(faixa.windows(2).all(|p| (p[0].user_id, p[0].order_id) < (p[1].user_id, p[1].order_id)));
+        arquivo.inserir(&pedido(500, 42), &mut indice).unwrap();
+        arquivo.atualizar(&indice, 3, |p| {
+            p.user_id = 42;
+            Ok(())
+        })
+        .unwrap();
+        arquivo.remover(&indice, 500).unwrap();
+        assert_eq!(chaves(&arquivo, &indice, 42), vec![3]);
+        assert!(!chaves(&arquivo, &indice, -1).contains(&3));
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(caminho_pendencias(&caminho))
+            .unwrap()
+            .write_all(b"+\x01\x80")
+            .unwrap();
+        assert_eq!(chaves(&arquivo, &indice, 42), vec![3]);
+        compactar(&caminho, Pedido::LAYOUT).unwrap();
+        assert!(!Path::new(&caminho_pendencias(&caminho)).exists());
+        assert_eq!(chaves(&arquivo, &indice, 42), vec![3]);
+    }
+}
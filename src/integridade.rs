@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::arquivo::{percorrer_validos, ArquivoSequencial};
+use crate::config::CaminhosEntidade;
+use crate::erro::{Erro, Resultado};
+use crate::indice::IndiceParcial;
+use crate::indice_secundario::ValorSecundario;
+use crate::pedido::Pedido;
+use crate::produto::{consultar_com_indice_e_overflow, Produto};
+use crate::registro::Registro;
+
+// Integridade referencial entre pedidos e produtos: o product_id de um pedido
+// precisa existir em produtos.dat (ou no overflow dele).
+//   - a inserção de um pedido confere o produto (conferir_produto);
+//   - a remoção de um produto com pedidos segue a PoliticaRemocao;
+//   - pedidos_orfaos confere todos os pedidos de uma vez, para os que
+//     chegaram sem conferência (importação do CSV, atualização do product_id,
+//     remoções com PoliticaRemocao::Ignorar).
+// Pedidos e produtos ficam em arquivos separados, sem transação entre eles: a
+// cascata remove os pedidos antes do produto, e repeti-la depois de uma queda
+// termina o que faltou.
+
+// O que fazer ao remover um produto que ainda tem pedidos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoliticaRemocao {
+    // Recusa a remoção com Erro::ProdutoComPedidos
+    #[default]
+    Restringir,
+    // Remove os pedidos do produto junto
+    Cascata,
+    // Remove só o produto; os pedidos ficam órfãos
+    Ignorar,
+}
+
+impl PoliticaRemocao {
+    pub fn from_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "restringir" => Some(PoliticaRemocao::Restringir),
+            "cascata" => Some(PoliticaRemocao::Cascata),
+            "ignorar" => Some(PoliticaRemocao::Ignorar),
+            _ => None,
+        }
+    }
+}
+
+// Principal, overflow e índice parcial da outra entidade da relação
+#[derive(Debug, Clone, Copy)]
+pub struct ArquivosRelacionados<'a> {
+    pub caminho_principal: &'a str,
+    pub caminho_overflow: &'a str,
+    pub indice: &'a IndiceParcial,
+}
+
+impl<'a> ArquivosRelacionados<'a> {
+    pub fn novo(caminhos: &'a CaminhosEntidade, indice: &'a IndiceParcial) -> Self {
+        ArquivosRelacionados { caminho_principal: &caminhos.dados, caminho_overflow: &caminhos.overflow, indice }
+    }
+
+    fn existe_produto(&self, product_id: i64) -> Resultado<bool> {
+        if !Path::new(self.caminho_principal).exists() {
+            return Ok(false);
+        }
+        Ok(consultar_com_indice_e_overflow(self.caminho_principal, self.caminho_overflow, self.indice, product_id)?.is_some())
+    }
+}
+
+// Recusa com Erro::ProdutoInexistente o pedido cujo produto não existe
+pub fn conferir_produto(produtos: &ArquivosRelacionados, pedido: &Pedido) -> Resultado<()> {
+    if !produtos.existe_produto(pedido.product_id)? {
+        return Err(Erro::ProdutoInexistente { order_id: pedido.order_id, product_id: pedido.product_id });
+    }
+    Ok(())
+}
+
+// Como terminou a remoção de um produto
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemocaoProduto {
+    // order_id dos pedidos removidos junto (PoliticaRemocao::Cascata)
+    pub pedidos_removidos: Vec<i64>,
+    // order_id dos pedidos que ficaram sem o produto (PoliticaRemocao::Ignorar)
+    pub pedidos_orfaos: Vec<i64>,
+}
+
+impl fmt::Display for RemocaoProduto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Produto removido")?;
+        if !self.pedidos_removidos.is_empty() {
+            write!(f, "\nPedidos removidos junto: {}", juntar(&self.pedidos_removidos))?;
+        }
+        if !self.pedidos_orfaos.is_empty() {
+            write!(f, "\nPedidos que ficaram sem o produto: {}", juntar(&self.pedidos_orfaos))?;
+        }
+        Ok(())
+    }
+}
+
+// Remove o produto seguindo a política para os pedidos dele; None se o
+// produto não existe. Os pedidos são achados pelo índice secundário de
+// product_id, se houver, ou varrendo os pedidos.
+pub fn remover_produto(
+    produtos: &ArquivoSequencial<Produto>,
+    indice_produtos: &IndiceParcial,
+    pedidos: &ArquivoSequencial<Pedido>,
+    indice_pedidos: &IndiceParcial,
+    product_id: i64,
+    politica: PoliticaRemocao,
+) -> Resultado<Option<RemocaoProduto>> {
+    if produtos.buscar(product_id)?.is_none() {
+        return Ok(None);
+    }
+    let do_produto: Vec<i64> = pedidos
+        .buscar_por_campo(indice_pedidos, "product_id", &ValorSecundario::Inteiro(product_id))?
+        .iter()
+        .map(Pedido::chave)
+        .collect();
+    let mut remocao = RemocaoProduto::default();
+    match politica {
+        PoliticaRemocao::Restringir if !do_produto.is_empty() => {
+            return Err(Erro::ProdutoComPedidos { product_id, pedidos: do_produto.len() });
+        }
+        PoliticaRemocao::Restringir => {}
+        PoliticaRemocao::Cascata => {
+            for order_id in do_produto {
+                pedidos.remover(indice_pedidos, order_id)?;
+                remocao.pedidos_removidos.push(order_id);
+            }
+        }
+        PoliticaRemocao::Ignorar => remocao.pedidos_orfaos = do_produto,
+    }
+    produtos.remover(indice_produtos, product_id)?;
+    Ok(Some(remocao))
+}
+
+// Quantos pedidos órfãos aparecem ao mostrar o relatório
+pub const MAX_ORFAOS_NA_TELA: usize = 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct RelatorioOrfaos {
+    pub pedidos_conferidos: u64,
+    // Pedidos cujo product_id não existe, na ordem dos arquivos (principal e overflow)
+    pub orfaos: Vec<Pedido>,
+}
+
+impl RelatorioOrfaos {
+    pub fn sem_orfaos(&self) -> bool {
+        self.orfaos.is_empty()
+    }
+}
+
+impl fmt::Display for RelatorioOrfaos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pedidos conferidos: {}", self.pedidos_conferidos)?;
+        write!(f, "\nPedidos órfãos (product_id sem produto): {}", self.orfaos.len())?;
+        for pedido in self.orfaos.iter().take(MAX_ORFAOS_NA_TELA) {
+            write!(f, "\n   pedido {}: produto {}", pedido.order_id, pedido.product_id)?;
+        }
+        if self.orfaos.len() > MAX_ORFAOS_NA_TELA {
+            write!(f, "\n   ... e mais {}", self.orfaos.len() - MAX_ORFAOS_NA_TELA)?;
+        }
+        Ok(())
+    }
+}
+
+// Confere o produto de cada pedido do principal e do overflow. Cada product_id
+// é consultado uma vez só.
+pub fn pedidos_orfaos(caminho_pedidos: &str, caminho_overflow_pedidos: &str, produtos: &ArquivosRelacionados) -> Resultado<RelatorioOrfaos> {
+    let mut relatorio = RelatorioOrfaos::default();
+    let mut existentes: HashMap<i64, bool> = HashMap::new();
+    for caminho in [caminho_pedidos, caminho_overflow_pedidos] {
+        if !Path::new(caminho).exists() {
+            continue;
+        }
+        percorrer_validos::<Pedido>(caminho, |pedido| {
+            relatorio.pedidos_conferidos += 1;
+            let existe = match existentes.get(&pedido.product_id) {
+                Some(existe) => *existe,
+                None => {
+                    let existe = produtos.existe_produto(pedido.product_id)?;
+                    existentes.insert(pedido.product_id, existe);
+                    existe
+                }
+            };
+            if !existe {
+                relatorio.orfaos.push(pedido);
+            }
+            Ok(())
+        })?;
+    }
+    Ok(relatorio)
+}
+
+fn juntar(chaves: &[i64]) -> String {
+    chaves.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::indice::construir_indice_parcial;
+    use crate::preco::Preco;
+
+    fn produto(product_id: i64) -> Produto {
+        Produto { product_id, price: Preco::em_centavos(100), ..Produto::default() }
+    }
+
+    fn pedido(order_id: i64, product_id: i64) -> Pedido {
+        Pedido { order_id, product_id, ..Pedido::default() }
+    }
+
+    // Inserção conferida, as três políticas de remoção e a lista de órfãos
+    #[test]
+    fn pedidos_so_com_produtos_existentes() {
+        let dir = std::env::temp_dir().join(format!("aed2_integridade_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let caminho = |nome: &str| dir.join(nome).to_string_lossy().to_string();
+        let (produtos_dat, produtos_overflow) = (caminho("produtos.dat"), caminho("produtos_overflow.dat"));
+        let (pedidos_dat, pedidos_overflow) = (caminho("pedidos.dat"), caminho("pedidos_overflow.dat"));
+
+        gravar_ordenados(&mut [produto(1), produto(2), produto(3), produto(4)], &produtos_dat).unwrap();
+        gravar_ordenados(&mut [pedido(10, 1), pedido(11, 2), pedido(12, 2), pedido(13, 3), pedido(14, 9)], &pedidos_dat).unwrap();
+        let indice_produtos = construir_indice_parcial(&produtos_dat, 2, Produto::LAYOUT).unwrap();
+        let indice_pedidos = construir_indice_parcial(&pedidos_dat, 2, Pedido::LAYOUT).unwrap();
+        let relacionados = ArquivosRelacionados {
+            caminho_principal: &produtos_dat,
+            caminho_overflow: &produtos_overflow,
+            indice: &indice_produtos,
+        };
+
+        assert!(conferir_produto(&relacionados, &pedido(20, 4)).is_ok());
+        assert!(matches!(
+            conferir_produto(&relacionados, &pedido(20, 5)),
+            Err(Erro::ProdutoInexistente { order_id: 20, product_id: 5 })
+        ));
+
+        let produtos = ArquivoSequencial::<Produto>::novo(&produtos_dat, &produtos_overflow);
+        let pedidos = ArquivoSequencial::<Pedido>::novo(&pedidos_dat, &pedidos_overflow);
+        let remover = |product_id, politica| remover_produto(&produtos, &indice_produtos, &pedidos, &indice_pedidos, product_id, politica);
+        assert!(matches!(
+            remover(2, PoliticaRemocao::Restringir),
+            Err(Erro::ProdutoComPedidos { product_id: 2, pedidos: 2 })
+        ));
+        assert!(remover(4, PoliticaRemocao::Restringir).unwrap().is_some());
+        assert_eq!(remover(2, PoliticaRemocao::Cascata).unwrap().unwrap().pedidos_removidos, [11, 12]);
+        assert_eq!(remover(3, PoliticaRemocao::Ignorar).unwrap().unwrap().pedidos_orfaos, [13]);
+        assert!(remover(2, PoliticaRemocao::Cascata).unwrap().is_none());
+
+        let relatorio = pedidos_orfaos(&pedidos_dat, &pedidos_overflow, &relacionados).unwrap();
+        let orfaos: Vec<i64> = relatorio.orfaos.iter().map(|p| p.order_id).collect();
+        assert_eq!((relatorio.pedidos_conferidos, orfaos), (3, vec![13, 14]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::arquivo::{percorrer_validos, ArquivoSequencial};
+use crate::cabecalho::{self, TAMANHO_CABECALHO};
+use crate::erro::{Erro, Resultado};
+use crate::indice::IndiceParcial;
+use crate::registro::Registro;
+use crate::textos::Textos;
+
+// Registros com chave entre min e max (inclusive), em ordem de chave. O principal
+// é lido sob demanda a partir da posição que o índice dá para min, até passar de
+// max; os registros do overflow que caem no intervalo são separados na criação
+// (o overflow é pequeno) e intercalados com os do principal.
+//
+// Depois de um erro de leitura o iterador devolve o erro e termina.
+pub struct Intervalo<R: Registro> {
+    principal: Option<BufReader<File>>,
+    textos: Option<Textos>,
+    posicao: u64,
+    fim: u64,
+    min: i64,
+    max: i64,
+    // Próximo registro do principal já lido e ainda não devolvido
+    proximo_principal: Option<R>,
+    overflow: std::iter::Peekable<std::vec::IntoIter<R>>,
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    pub fn buscar_intervalo(&self, indice: &IndiceParcial, min: i64, max: i64) -> Resultado<Intervalo<R>> {
+        let mut do_overflow = Vec::new();
+        if min <= max && Path::new(&self.caminho_overflow).exists() {
+            percorrer_validos::<R>(&self.caminho_overflow, |registro| {
+                if (min..=max).contains(&registro.chave()) {
+                    do_overflow.push(registro);
+                }
+                Ok(())
+            })?;
+        }
+        // Estável: chaves iguais no overflow saem na ordem em que foram inseridas
+        do_overflow.sort_by_key(|r| r.chave());
+
+        let mut intervalo = Intervalo {
+            principal: None,
+            textos: None,
+            posicao: 0,
+            fim: 0,
+            min,
+            max,
+            proximo_principal: None,
+            overflow: do_overflow.into_iter().peekable(),
+        };
+        if min > max || !Path::new(&self.caminho_principal).exists() {
+            return Ok(intervalo);
+        }
+
+        let mut arquivo = File::open(&self.caminho_principal)?;
+        let cabecalho = cabecalho::ler_cabecalho_dados(&mut arquivo, R::LAYOUT)?;
+        let num_registros = cabecalho.num_registros;
+        let fim = TAMANHO_CABECALHO as u64 + num_registros * R::TAMANHO_REGISTRO as u64;
+        let inicio = match indice.buscar_posicao(min) {
+            Some((_, posicao)) => posicao,
+            None => TAMANHO_CABECALHO as u64,
+        };
+        if inicio < TAMANHO_CABECALHO as u64
+            || !(inicio - TAMANHO_CABECALHO as u64).is_multiple_of(R::TAMANHO_REGISTRO as u64)
+            || inicio > fim
+        {
+            return Err(Erro::IndiceInconsistente(format!(
+                "posição {} do índice fora dos registros de {}",
+                inicio, self.caminho_principal
+            )));
+        }
+        arquivo.seek(SeekFrom::Start(inicio))?;
+        intervalo.principal = Some(BufReader::new(arquivo));
+        intervalo.textos = Some(Textos::do_arquivo(&self.caminho_principal, R::LAYOUT, &cabecalho));
+        intervalo.posicao = inicio;
+        intervalo.fim = fim;
+        Ok(intervalo)
+    }
+}
+
+impl<R: Registro> Intervalo<R> {
+    // Lê o próximo registro do principal dentro do intervalo, pulando removidos
+    // e os que ficam antes de min; None quando passa de max ou do fim do arquivo
+    fn ler_principal(&mut self) -> Resultado<Option<R>> {
+        let (Some(leitor), Some(textos)) = (self.principal.as_mut(), self.textos.as_mut()) else {
+            return Ok(None);
+        };
+        let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+        while self.posicao < self.fim {
+            leitor.read_exact(&mut buffer)?;
+            let registro = R::ler_em(&buffer, self.posicao, textos)?;
+            self.posicao += R::TAMANHO_REGISTRO as u64;
+            if registro.removido() || registro.chave() < self.min {
+                continue;
+            }
+            if registro.chave() > self.max {
+                break;
+            }
+            return Ok(Some(registro));
+        }
+        self.principal = None;
+        Ok(None)
+    }
+}
+
+impl<R: Registro> Iterator for Intervalo<R> {
+    type Item = Resultado<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.proximo_principal.is_none() {
+            match self.ler_principal() {
+                Ok(registro) => self.proximo_principal = registro,
+                Err(e) => {
+                    self.principal = None;
+                    self.overflow = Vec::new().into_iter().peekable();
+                    return Some(Err(e));
+                }
+            }
+        }
+        // Em chaves iguais o do principal sai primeiro
+        let usar_overflow = match (&self.proximo_principal, self.overflow.peek()) {
+            (Some(principal), Some(overflow)) => overflow.chave() < principal.chave(),
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        if usar_overflow {
+            self.overflow.next().map(Ok)
+        } else {
+            self.proximo_principal.take().map(Ok)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::indice::construir_indice_parcial;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+
+    fn produto(product_id: i64) -> Produto {
+        Produto {
+            product_id,
+            category_alias: String::new(),
+            price: Preco::em_centavos(100),
+            material: String::new(),
+            stone: String::new(),
+        }
+    }
+
+    // Intervalo que começa no meio de um bloco do índice, com registros do
+    // overflow intercalados e um removido no caminho
+    #[test]
+    fn intervalo_intercala_principal_e_overflow() {
+        let dir = std::env::temp_dir().join(format!("aed2_intervalo_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("produtos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("produtos_overflow.dat").to_string_lossy().to_string();
+        let mut produtos: Vec<Produto> = (0..100).map(|i| produto(i * 10)).collect();
+        gravar_ordenados(&mut produtos, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 7, Produto::LAYOUT).unwrap();
+
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow);
+        for chave in [255, 201, 999, 5] {
+            arquivo.inserir(&produto(chave), &mut indice).unwrap();
+        }
+        arquivo.remover(&indice, 230).unwrap();
+
+        let chaves: Vec<i64> = arquivo
+            .buscar_intervalo(&indice, 201, 260)
+            .unwrap()
+            .map(|r| r.unwrap().product_id)
+            .collect();
+        assert_eq!(chaves, vec![201, 210, 220, 240, 250, 255, 260]);
+        assert_eq!(arquivo.buscar_intervalo(&indice, 261, 200).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,33 @@
+pub mod arquivo;
+pub mod arvore_b;
+pub mod cabecalho;
+pub mod config;
+pub mod crc;
+pub mod data_hora;
+pub mod desempenho;
+pub mod erro;
+pub mod importacao;
+pub mod indice;
+pub mod indice_hash;
+pub mod indice_overflow;
+pub mod indice_secundario;
+pub mod integridade;
+pub mod intervalo;
+pub mod manifesto;
+pub mod migracao;
+pub mod ordenacao_externa;
+pub mod pedido;
+pub mod preco;
+pub mod produto;
+pub mod registro;
+pub mod textos;
+pub mod utils;
+pub mod verificar;
+pub mod wal;
+
+pub use erro::{Erro, Resultado};
+pub use arquivo::ArquivoSequencial;
+pub use indice::{IndiceParcial, LayoutRegistro};
+pub use pedido::Pedido;
+pub use produto::Produto;
+pub use registro::Registro;
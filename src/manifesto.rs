@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::erro::{Erro, Resultado};
+
+// Troca atômica dos arquivos de uma entidade (dados, overflow e índice) na
+// reconstrução. Os arquivos novos são gravados com o sufixo ".novo" e o
+// manifesto (produtos.dat.manifesto) registra em que fase a troca está:
+//
+//   Preparando  os arquivos novos ainda estão sendo gravados; numa queda eles
+//               são descartados e os antigos continuam valendo
+//   Renomeando  os arquivos novos estão completos e sincronizados; numa queda
+//               as renomeações que faltam são refeitas
+//   Concluido   nada pendente
+//
+// A geração aumenta a cada troca concluída.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Estado {
+    Preparando,
+    Renomeando,
+    Concluido,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Troca {
+    pub temporario: String,
+    pub definitivo: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifesto {
+    pub geracao: u64,
+    pub estado: Estado,
+    pub trocas: Vec<Troca>,
+}
+
+pub fn caminho_manifesto(caminho_dados: &str) -> String {
+    format!("{}.manifesto", caminho_dados)
+}
+
+pub const SUFIXO_TEMPORARIO: &str = ".novo";
+
+pub fn caminho_temporario(caminho: &str) -> String {
+    format!("{}{}", caminho, SUFIXO_TEMPORARIO)
+}
+
+impl Manifesto {
+    // Manifesto atual da entidade; sem arquivo, geração 0 sem nada pendente
+    pub fn ler(caminho_dados: &str) -> Resultado<Self> {
+        let caminho = caminho_manifesto(caminho_dados);
+        if !Path::new(&caminho).exists() {
+            return Ok(Manifesto { geracao: 0, estado: Estado::Concluido, trocas: Vec::new() });
+        }
+        let bytes = std::fs::read(&caminho)?;
+        serde_json::from_slice(&bytes).map_err(|e| Erro::Manifesto(format!("{}: {}", caminho, e)))
+    }
+
+    // Grava em um temporário e renomeia, para o manifesto nunca ficar pela metade
+    fn gravar(&self, caminho_dados: &str) -> Resultado<()> {
+        let caminho = caminho_manifesto(caminho_dados);
+        let temporario = caminho_temporario(&caminho);
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| Erro::Manifesto(e.to_string()))?;
+        let mut arquivo = File::create(&temporario)?;
+        arquivo.write_all(&bytes)?;
+        arquivo.sync_all()?;
+        std::fs::rename(&temporario, &caminho)?;
+        sincronizar_diretorio(&caminho)
+    }
+}
+
+// Garante no disco a entrada de diretório de um arquivo recém-criado ou renomeado
+fn sincronizar_diretorio(caminho: &str) -> Resultado<()> {
+    #[cfg(unix)]
+    {
+        let dir = match Path::new(caminho).parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = caminho;
+    Ok(())
+}
+
+// Troca completa: anota os temporários, chama `gravar` para criá-los e confirma.
+// Se `gravar` falhar, os temporários são descartados e os arquivos atuais ficam;
+// se o descarte também falhar, os dois erros voltam em Erro::TrocaNaoDescartada.
+pub fn trocar<T>(caminho_dados: &str, definitivos: &[&str], gravar: impl FnOnce() -> Resultado<T>) -> Resultado<T> {
+    let pendente = preparar(caminho_dados, definitivos)?;
+    match gravar() {
+        Ok(valor) => {
+            confirmar(caminho_dados, pendente)?;
+            Ok(valor)
+        }
+        Err(e) => match recuperar(caminho_dados) {
+            Ok(_) => Err(e),
+            Err(descarte) => Err(Erro::TrocaNaoDescartada { erro: Box::new(e), descarte: Box::new(descarte) }),
+        },
+    }
+}
+
+// Anota no manifesto quais temporários vão ser gravados, antes de gravá-los
+pub fn preparar(caminho_dados: &str, definitivos: &[&str]) -> Resultado<Manifesto> {
+    let mut manifesto = Manifesto::ler(caminho_dados)?;
+    if manifesto.estado != Estado::Concluido {
+        return Err(Erro::Manifesto(format!(
+            "{} tem uma troca pendente; execute a recuperação antes",
+            caminho_manifesto(caminho_dados)
+        )));
+    }
+    manifesto.estado = Estado::Preparando;
+    manifesto.trocas = definitivos
+        .iter()
+        .map(|d| Troca { temporario: caminho_temporario(d), definitivo: d.to_string() })
+        .collect();
+    manifesto.gravar(caminho_dados)?;
+    Ok(manifesto)
+}
+
+// Sincroniza os temporários, passa o manifesto para Renomeando (o ponto a partir
+// do qual a troca vale) e renomeia todos. Retorna a nova geração.
+pub fn confirmar(caminho_dados: &str, mut manifesto: Manifesto) -> Resultado<u64> {
+    for troca in &manifesto.trocas {
+        File::open(&troca.temporario)?.sync_all()?;
+    }
+    manifesto.estado = Estado::Renomeando;
+    manifesto.geracao += 1;
+    manifesto.gravar(caminho_dados)?;
+    concluir_renomeacoes(caminho_dados, manifesto)
+}
+
+fn concluir_renomeacoes(caminho_dados: &str, mut manifesto: Manifesto) -> Resultado<u64> {
+    for troca in &manifesto.trocas {
+        // Depois de uma queda, parte das renomeações pode já ter sido feita
+        if Path::new(&troca.temporario).exists() {
+            std::fs::rename(&troca.temporario, &troca.definitivo)?;
+            sincronizar_diretorio(&troca.definitivo)?;
+        }
+    }
+    manifesto.estado = Estado::Concluido;
+    manifesto.trocas.clear();
+    manifesto.gravar(caminho_dados)?;
+    Ok(manifesto.geracao)
+}
+
+// Deixa a entidade num estado consistente depois de uma queda no meio da
+// reconstrução. Retorna a fase que estava pendente, se havia alguma.
+pub fn recuperar(caminho_dados: &str) -> Resultado<Option<Estado>> {
+    let mut manifesto = Manifesto::ler(caminho_dados)?;
+    match manifesto.estado {
+        Estado::Concluido => Ok(None),
+        Estado::Preparando => {
+            for troca in &manifesto.trocas {
+                if Path::new(&troca.temporario).exists() {
+                    std::fs::remove_file(&troca.temporario)?;
+                }
+            }
+            manifesto.estado = Estado::Concluido;
+            manifesto.trocas.clear();
+            manifesto.gravar(caminho_dados)?;
+            Ok(Some(Estado::Preparando))
+        }
+        Estado::Renomeando => {
+            concluir_renomeacoes(caminho_dados, manifesto)?;
+            Ok(Some(Estado::Renomeando))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Diretório vazio com a.dat e a.idx na versão "antiga"
+    fn preparar_diretorio(nome: &str) -> (std::path::PathBuf, String, String) {
+        let dir = std::env::temp_dir().join(format!("aed2_manifesto_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dados = dir.join("a.dat").to_string_lossy().to_string();
+        let indice = dir.join("a.idx").to_string_lossy().to_string();
+        std::fs::write(&dados, "dados antigos").unwrap();
+        std::fs::write(&indice, "indice antigo").unwrap();
+        (dir, dados, indice)
+    }
+
+    fn arquivos(dir: &Path) -> Vec<String> {
+        let mut nomes: Vec<String> =
+            std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        nomes.sort();
+        nomes
+    }
+
+    fn ler(caminho: &str) -> String {
+        std::fs::read_to_string(caminho).unwrap()
+    }
+
+    // Queda antes da renomeação (Preparando): os temporários somem e os antigos ficam
+    #[test]
+    fn queda_antes_da_renomeacao_descarta_a_troca() {
+        let (dir, dados, indice) = preparar_diretorio("preparando");
+        preparar(&dados, &[&dados, &indice]).unwrap();
+        std::fs::write(caminho_temporario(&dados), "dados novos").unwrap();
+        assert_eq!(arquivos(&dir), ["a.dat", "a.dat.manifesto", "a.dat.novo", "a.idx"]);
+
+        assert_eq!(recuperar(&dados).unwrap(), Some(Estado::Preparando));
+        assert_eq!(arquivos(&dir), ["a.dat", "a.dat.manifesto", "a.idx"]);
+        assert_eq!((ler(&dados), ler(&indice)), ("dados antigos".to_string(), "indice antigo".to_string()));
+        let manifesto = Manifesto::ler(&dados).unwrap();
+        assert_eq!((manifesto.geracao, manifesto.estado, manifesto.trocas.len()), (0, Estado::Concluido, 0));
+        assert_eq!(recuperar(&dados).unwrap(), None);
+    }
+
+    // Queda no meio das renomeações (Renomeando): as que faltam são feitas
+    #[test]
+    fn queda_durante_a_renomeacao_conclui_a_troca() {
+        let (dir, dados, indice) = preparar_diretorio("renomeando");
+        let mut manifesto = preparar(&dados, &[&dados, &indice]).unwrap();
+        std::fs::write(caminho_temporario(&dados), "dados novos").unwrap();
+        std::fs::write(caminho_temporario(&indice), "indice novo").unwrap();
+        // Como confirmar deixaria tudo se caísse depois de renomear só os dados
+        manifesto.estado = Estado::Renomeando;
+        manifesto.geracao += 1;
+        manifesto.gravar(&dados).unwrap();
+        std::fs::rename(caminho_temporario(&dados), &dados).unwrap();
+
+        assert_eq!(recuperar(&dados).unwrap(), Some(Estado::Renomeando));
+        assert_eq!(arquivos(&dir), ["a.dat", "a.dat.manifesto", "a.idx"]);
+        assert_eq!((ler(&dados), ler(&indice)), ("dados novos".to_string(), "indice novo".to_string()));
+        let manifesto = Manifesto::ler(&dados).unwrap();
+        assert_eq!((manifesto.geracao, manifesto.estado), (1, Estado::Concluido));
+    }
+
+    // Falha ao gravar: o erro original volta, junto com o do descarte se ele também falhar
+    #[test]
+    fn falha_na_gravacao_descarta_ou_avisa() {
+        let (dir, dados, indice) = preparar_diretorio("falha");
+        let resultado: Resultado<()> = trocar(&dados, &[&dados, &indice], || {
+            std::fs::write(caminho_temporario(&dados), "dados novos")?;
+            Err(Erro::ChaveDuplicada(7))
+        });
+        assert!(matches!(resultado, Err(Erro::ChaveDuplicada(7))));
+        assert_eq!(arquivos(&dir), ["a.dat", "a.dat.manifesto", "a.idx"]);
+        assert_eq!(ler(&dados), "dados antigos");
+
+        let resultado: Resultado<()> = trocar(&dados, &[&dados, &indice], || {
+            std::fs::write(caminho_temporario(&dados), "dados novos")?;
+            std::fs::write(caminho_manifesto(&dados), "ilegível")?;
+            Err(Erro::ChaveDuplicada(8))
+        });
+        match resultado {
+            Err(Erro::TrocaNaoDescartada { erro, descarte }) => {
+                assert!(matches!(*erro, Erro::ChaveDuplicada(8)));
+                assert!(matches!(*descarte, Erro::Manifesto(_)));
+            }
+            outro => panic!("esperado TrocaNaoDescartada, veio {:?}", outro),
+        }
+        assert_eq!(arquivos(&dir), ["a.dat", "a.dat.manifesto", "a.dat.novo", "a.idx"]);
+    }
+}
@@ -0,0 +1,379 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::arquivo::{caminho_quarentena, ArquivoSequencial, RelatorioReconstrucao};
+use crate::cabecalho::{self, Cabecalho, TipoArquivo};
+use crate::erro::{Erro, Resultado};
+use crate::indice::{IndiceParcial, LayoutRegistro};
+use crate::manifesto::{self, caminho_temporario};
+use crate::registro::{bytes_removido, conferir_crc, LayoutAnterior, Registro, CHAVE_REMOVIDA, TAMANHO_CRC};
+use crate::textos::{self, Textos};
+
+// Migração dos arquivos de uma entidade para o formato e o layout atuais:
+//   1. arquivos sem cabeçalho ou sem CRC (versões 0 e 1) passam para a versão 2
+//      no layout original do registro (cabecalho::migrar_dados_legado), cada um
+//      gravado num temporário e trocado pelo manifesto;
+//   2. arquivos num layout anterior (R::LAYOUTS_ANTERIORES, reconhecido pelo
+//      tamanho do registro no cabeçalho) são regravados no atual, registro a
+//      registro. Registro que não converte vai para a quarentena e fica
+//      removido no lugar;
+//   3. com algum arquivo convertido, a reconstrução refaz o principal e todos
+//      os índices, que ainda estão no layout antigo.
+// Os arquivos convertidos e os heaps de textos deles são trocados juntos pelo
+// manifesto: uma queda no meio deixa tudo no layout antigo ou tudo no novo, e
+// rodar a migração de novo continua de onde parou.
+
+#[derive(Debug, Clone, Default)]
+pub struct RelatorioMigracao {
+    // Arquivos que mudaram de formato ou de layout
+    pub migrados: Vec<String>,
+    pub convertidos: u64,
+    // Registros que não puderam ser convertidos (um event_time inválido, por exemplo)
+    pub quarentenados: u64,
+    pub reconstrucao: Option<RelatorioReconstrucao>,
+}
+
+impl fmt::Display for RelatorioMigracao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.migrados.is_empty() {
+            return write!(f, "Arquivos já estão no formato atual");
+        }
+        write!(f, "Arquivos migrados: {}", self.migrados.join(", "))?;
+        if let Some(reconstrucao) = &self.reconstrucao {
+            write!(f, "\nRegistros convertidos para o layout atual: {}", self.convertidos)?;
+            if self.quarentenados > 0 {
+                write!(f, "\nRegistros que não converteram, movidos para a quarentena: {}", self.quarentenados)?;
+            }
+            write!(f, "\n{}", reconstrucao)?;
+        }
+        Ok(())
+    }
+}
+
+// Layout dos arquivos da versão 2 do formato: o primeiro que o registro teve
+pub fn layout_original<R: Registro>() -> LayoutRegistro {
+    match R::LAYOUTS_ANTERIORES.first() {
+        Some(anterior) => layout_de::<R>(anterior),
+        None => R::LAYOUT,
+    }
+}
+
+fn layout_de<R: Registro>(anterior: &LayoutAnterior<R>) -> LayoutRegistro {
+    LayoutRegistro { tamanho_registro: anterior.tamanho_registro, ..R::LAYOUT }
+}
+
+// Layout anterior em que o arquivo de dados está; None se já está no atual (ou vazio)
+fn layout_do_arquivo<R: Registro>(caminho: &str) -> Resultado<Option<&'static LayoutAnterior<R>>> {
+    if !Path::new(caminho).exists() || std::fs::metadata(caminho)?.len() == 0 {
+        return Ok(None);
+    }
+    let cabecalho = Cabecalho::ler(&mut File::open(caminho)?)?;
+    if cabecalho.tamanho_registro as usize == R::TAMANHO_REGISTRO {
+        return Ok(None);
+    }
+    let anterior = R::LAYOUTS_ANTERIORES
+        .iter()
+        .find(|l| l.tamanho_registro == cabecalho.tamanho_registro as usize)
+        .ok_or_else(|| {
+            Erro::CabecalhoInvalido(format!(
+                "{} tem registros de {} bytes, que não é um layout conhecido de {}",
+                caminho,
+                cabecalho.tamanho_registro,
+                R::TIPO.nome()
+            ))
+        })?;
+    cabecalho.validar(TipoArquivo::Dados, layout_de::<R>(anterior))?;
+    Ok(Some(anterior))
+}
+
+// Regrava em `destino`, no layout atual, o arquivo de dados gravado no layout
+// `anterior`, mantendo a posição de cada registro
+fn converter<R: Registro>(
+    caminho: &str,
+    anterior: &LayoutAnterior<R>,
+    destino: &str,
+    quarentena: &str,
+    relatorio: &mut RelatorioMigracao,
+) -> Resultado<()> {
+    let layout_antigo = layout_de::<R>(anterior);
+    let mut arquivo = File::open(caminho)?;
+    let cabecalho = cabecalho::ler_cabecalho_dados(&mut arquivo, layout_antigo)?;
+    let mut textos_antigos = Textos::do_arquivo(caminho, layout_antigo, &cabecalho);
+    let (mut novo, mut textos) = textos::criar_arquivo_dados(destino, R::LAYOUT, cabecalho.formato_textos)?;
+    let mut saida = BufWriter::new(&mut novo);
+    let mut leitor = BufReader::new(arquivo);
+    let mut buffer = vec![0u8; anterior.tamanho_registro];
+    let mut rejeitados: Option<File> = None;
+    for _ in 0..cabecalho.num_registros {
+        leitor.read_exact(&mut buffer)?;
+        let registro = if layout_antigo.chave(&buffer) == CHAVE_REMOVIDA {
+            None
+        } else {
+            match conferir_crc(&buffer).and_then(|_| (anterior.decodificar)(&buffer[..buffer.len() - TAMANHO_CRC], &mut textos_antigos)) {
+                Ok(registro) => Some(registro),
+                Err(_) => {
+                    if rejeitados.is_none() {
+                        rejeitados = Some(OpenOptions::new().create(true).append(true).open(quarentena)?);
+                    }
+                    if let Some(rejeitados) = rejeitados.as_mut() {
+                        rejeitados.write_all(&buffer)?;
+                    }
+                    relatorio.quarentenados += 1;
+                    None
+                }
+            }
+        };
+        let bytes = match registro {
+            Some(registro) => {
+                relatorio.convertidos += 1;
+                registro.to_bytes(&mut textos)?
+            }
+            // No lugar do que não converteu, um registro vazio e removido
+            None => bytes_removido(R::LAYOUT, &R::default().to_bytes(&mut textos)?),
+        };
+        saida.write_all(&bytes)?;
+    }
+    saida.flush()?;
+    drop(saida);
+    cabecalho::atualizar_num_registros(&mut novo, cabecalho.num_registros)?;
+    textos.sincronizar()?;
+    novo.sync_all()?;
+    if let Some(rejeitados) = rejeitados.as_mut() {
+        rejeitados.sync_all()?;
+    }
+    Ok(())
+}
+
+impl<R: Registro> ArquivoSequencial<R> {
+    // Principal ou overflow ainda num layout anterior, à espera da migração
+    pub fn migracao_pendente(&self) -> bool {
+        [&self.caminho_principal, &self.caminho_overflow]
+            .iter()
+            .any(|caminho| matches!(layout_do_arquivo::<R>(caminho), Ok(Some(_))))
+    }
+
+    // Leva o principal, o overflow e os índices ao formato e ao layout atuais.
+    // Sem nada a converter, só o índice parcial de versões antigas é migrado.
+    pub fn migrar(&self, indice: &mut IndiceParcial) -> Resultado<RelatorioMigracao> {
+        let mut relatorio = RelatorioMigracao::default();
+        let mut a_converter = Vec::new();
+        for caminho in [&self.caminho_principal, &self.caminho_overflow] {
+            if !Path::new(caminho).exists() || std::fs::metadata(caminho)?.len() == 0 {
+                continue;
+            }
+            if cabecalho::migrar_dados_legado(&self.caminho_principal, caminho, layout_original::<R>())? {
+                relatorio.migrados.push(caminho.clone());
+            }
+            if let Some(anterior) = layout_do_arquivo::<R>(caminho)? {
+                a_converter.push((caminho.as_str(), anterior));
+            }
+        }
+        if a_converter.is_empty() {
+            if let Some(caminho_indice) = &self.caminho_indice
+                && Path::new(caminho_indice).exists()
+                && cabecalho::migrar_indice_legado(&self.caminho_principal, caminho_indice, R::LAYOUT)?
+            {
+                relatorio.migrados.push(caminho_indice.clone());
+            }
+            return Ok(relatorio);
+        }
+
+        let heaps: Vec<String> = a_converter.iter().map(|(caminho, _)| textos::caminho_textos(caminho)).collect();
+        let mut definitivos: Vec<&str> = a_converter.iter().map(|(caminho, _)| *caminho).collect();
+        definitivos.extend(heaps.iter().map(String::as_str));
+        let quarentena = caminho_quarentena(&self.caminho_principal);
+        manifesto::trocar(&self.caminho_principal, &definitivos, || {
+            for (caminho, anterior) in &a_converter {
+                converter(caminho, anterior, &caminho_temporario(caminho), &quarentena, &mut relatorio)?;
+            }
+            Ok(())
+        })?;
+        for (caminho, _) in &a_converter {
+            if !relatorio.migrados.iter().any(|m| m == caminho) {
+                relatorio.migrados.push(caminho.to_string());
+            }
+        }
+        relatorio.reconstrucao = Some(self.reconstruir(indice)?);
+        Ok(relatorio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::ler_validos;
+    use crate::cabecalho::TAMANHO_CABECALHO;
+    use crate::crc::crc32;
+    use crate::indice::construir_indice_parcial;
+    use crate::pedido::Pedido;
+    use crate::produto::Produto;
+
+    // Pedido no layout de 66 bytes, com o event_time em texto de 30 bytes
+    fn pedido_antigo(order_id: i64, event_time: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&order_id.to_le_bytes());
+        bytes.extend_from_slice(&7i64.to_le_bytes());
+        bytes.extend_from_slice(format!("{:<30}", event_time).as_bytes());
+        bytes.extend_from_slice(&5000i64.to_le_bytes());
+        bytes.extend_from_slice(&1.5f64.to_le_bytes());
+        let crc = crc32(&bytes);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    // Os pedidos convertem com o fuso original e o preço em centavos, o de data
+    // inválida vai para a quarentena e uma segunda migração não tem o que fazer
+    #[test]
+    fn pedidos_antigos_ganham_event_time_tipado() {
+        let dir = std::env::temp_dir().join(format!("aed2_migracao_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("pedidos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("pedidos_overflow.dat").to_string_lossy().to_string();
+        let indice_path = dir.join("indice_pedidos.bin").to_string_lossy().to_string();
+
+        let layout_antigo = layout_original::<Pedido>();
+        assert_eq!(layout_antigo.tamanho_registro, 66);
+        let linhas = [(1, "2018-12-01 11:40:29+00:00"), (2, "ontem"), (3, "2018-12-01 09:00:00-03:00")];
+        let mut bytes = Cabecalho::novo(TipoArquivo::Dados, layout_antigo, 0, linhas.len() as u64).to_bytes().to_vec();
+        for (order_id, event_time) in linhas {
+            bytes.extend_from_slice(&pedido_antigo(order_id, event_time));
+        }
+        std::fs::write(&principal, &bytes).unwrap();
+        std::fs::write(&overflow, Cabecalho::novo(TipoArquivo::Dados, layout_antigo, 0, 0).to_bytes()).unwrap();
+
+        let arquivo = ArquivoSequencial::<Pedido>::novo(&principal, &overflow).com_indice(&indice_path);
+        let mut indice = IndiceParcial::novo(2, Pedido::LAYOUT);
+        let relatorio = arquivo.migrar(&mut indice).unwrap();
+        assert_eq!((relatorio.convertidos, relatorio.quarentenados), (2, 1));
+        assert_eq!(std::fs::metadata(caminho_quarentena(&principal)).unwrap().len(), 66);
+
+        let pedidos: Vec<Pedido> = ler_validos(&principal).unwrap();
+        let datas: Vec<String> = pedidos.iter().map(|p| p.event_time.to_string()).collect();
+        assert_eq!(datas, ["2018-12-01 11:40:29+00:00", "2018-12-01 09:00:00-03:00"]);
+        assert!(pedidos.iter().all(|p| p.price.to_string() == "1.50 USD"));
+        assert_eq!(
+            std::fs::metadata(&principal).unwrap().len(),
+            (TAMANHO_CABECALHO + 2 * Pedido::TAMANHO_REGISTRO) as u64
+        );
+        let reconstruido = construir_indice_parcial(&principal, 2, Pedido::LAYOUT).unwrap();
+        assert_eq!(indice.entradas.len(), reconstruido.entradas.len());
+
+        assert!(arquivo.migrar(&mut indice).unwrap().migrados.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Produto como a primeira versão gravava: 87 bytes sem cabeçalho nem CRC,
+    // textos completados com espaços, price em f64 e '\n' no fim
+    fn produto_inicial(product_id: i64, category_alias: &str, price: f64) -> Vec<u8> {
+        let mut bytes = product_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(format!("{:<30}", category_alias).as_bytes());
+        bytes.extend_from_slice(&price.to_le_bytes());
+        bytes.extend_from_slice(format!("{:<20}", "gold").as_bytes());
+        bytes.extend_from_slice(format!("{:<20}", "diamond").as_bytes());
+        bytes.push(b'\n');
+        assert_eq!(bytes.len(), 87);
+        bytes
+    }
+
+    // Pedido da primeira versão: 62 bytes, event_time em texto e price em f64
+    fn pedido_inicial(order_id: i64, event_time: &str, price: f64) -> Vec<u8> {
+        let mut bytes = order_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(order_id * 10).to_le_bytes());
+        bytes.extend_from_slice(format!("{:<30}", event_time).as_bytes());
+        bytes.extend_from_slice(&5000i64.to_le_bytes());
+        bytes.extend_from_slice(&price.to_le_bytes());
+        assert_eq!(bytes.len(), 62);
+        bytes
+    }
+
+    // Principal, overflow e índice ([fator u32][n u32][(chave, posição)]) como a
+    // primeira versão os deixava, migrados de uma vez; devolve o arquivo e o índice
+    fn migrar_da_versao_inicial<R: Registro>(
+        nome: &str,
+        principal: &[Vec<u8>],
+        overflow: &[Vec<u8>],
+    ) -> (ArquivoSequencial<R>, IndiceParcial) {
+        let dir = std::env::temp_dir().join(format!("aed2_migracao_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let caminho = |arquivo: &str| dir.join(arquivo).to_string_lossy().to_string();
+        let caminho_principal = caminho("a.dat");
+        let caminho_overflow = caminho("a_overflow.dat");
+        let caminho_indice = caminho("a.idx");
+        std::fs::write(&caminho_principal, principal.concat()).unwrap();
+        std::fs::write(&caminho_overflow, overflow.concat()).unwrap();
+        let mut indice_antigo = 2u32.to_le_bytes().to_vec();
+        indice_antigo.extend((principal.len().div_ceil(2) as u32).to_le_bytes());
+        for (i, registro) in principal.iter().enumerate().step_by(2) {
+            indice_antigo.extend_from_slice(&registro[0..8]);
+            indice_antigo.extend(((i * registro.len()) as u64).to_le_bytes());
+        }
+        std::fs::write(&caminho_indice, indice_antigo).unwrap();
+
+        let arquivo = ArquivoSequencial::<R>::novo(&caminho_principal, &caminho_overflow).com_indice(&caminho_indice);
+        let mut indice = IndiceParcial::novo(2, R::LAYOUT);
+        let relatorio = arquivo.migrar(&mut indice).unwrap();
+        assert_eq!(relatorio.migrados, [caminho_principal.clone(), caminho_overflow.clone()]);
+        assert_eq!((relatorio.convertidos, relatorio.quarentenados), ((principal.len() + overflow.len()) as u64, 0));
+        assert!(arquivo.migrar(&mut indice).unwrap().migrados.is_empty());
+        let indice = IndiceParcial::carregar_binario(&caminho_indice, R::LAYOUT).unwrap();
+        assert!(!std::fs::read_dir(&dir).unwrap().any(|e| e.unwrap().file_name().to_string_lossy().ends_with(".novo")));
+        (arquivo, indice)
+    }
+
+    // Arquivos da primeira versão (Produto de 87 e Pedido de 62 bytes, sem
+    // cabeçalho) chegam ao layout atual com todos os campos, o overflow
+    // intercalado no principal e um índice que acha cada registro
+    #[test]
+    fn arquivos_da_primeira_versao() {
+        let principal = [
+            produto_inicial(10, "jewelry.ring", 12.5),
+            produto_inicial(20, "jewelry.earring", 0.1),
+            produto_inicial(30, "jewelry.pendant", 1999.99),
+        ];
+        let overflow = [produto_inicial(25, "jewelry.brooch", 7.0)];
+        let (arquivo, indice) = migrar_da_versao_inicial::<Produto>("produtos", &principal, &overflow);
+        let produtos: Vec<Produto> = ler_validos(&arquivo.caminho_principal).unwrap();
+        let lidos: Vec<(i64, &str, String, &str, &str)> = produtos
+            .iter()
+            .map(|p| (p.product_id, p.category_alias.as_str(), p.price.to_string(), p.material.as_str(), p.stone.as_str()))
+            .collect();
+        assert_eq!(
+            lidos,
+            [
+                (10, "jewelry.ring", "12.50 USD".to_string(), "gold", "diamond"),
+                (20, "jewelry.earring", "0.10 USD".to_string(), "gold", "diamond"),
+                (25, "jewelry.brooch", "7.00 USD".to_string(), "gold", "diamond"),
+                (30, "jewelry.pendant", "1999.99 USD".to_string(), "gold", "diamond"),
+            ]
+        );
+        for produto in &produtos {
+            assert_eq!(arquivo.consultar(&indice, produto.product_id).unwrap().unwrap().price, produto.price);
+        }
+
+        let principal = [
+            pedido_inicial(1, "2018-12-01 11:40:29+00:00", 212.33),
+            pedido_inicial(3, "2018-12-02 09:00:00-03:00", 0.5),
+        ];
+        let overflow = [pedido_inicial(2, "2019-01-31 23:59:59+00:00", 10.0)];
+        let (arquivo, indice) = migrar_da_versao_inicial::<Pedido>("pedidos", &principal, &overflow);
+        let pedidos: Vec<Pedido> = ler_validos(&arquivo.caminho_principal).unwrap();
+        let lidos: Vec<(i64, i64, String, i64, String)> = pedidos
+            .iter()
+            .map(|p| (p.order_id, p.user_id, p.event_time.to_string(), p.product_id, p.price.to_string()))
+            .collect();
+        assert_eq!(
+            lidos,
+            [
+                (1, 10, "2018-12-01 11:40:29+00:00".to_string(), 5000, "212.33 USD".to_string()),
+                (2, 20, "2019-01-31 23:59:59+00:00".to_string(), 5000, "10.00 USD".to_string()),
+                (3, 30, "2018-12-02 09:00:00-03:00".to_string(), 5000, "0.50 USD".to_string()),
+            ]
+        );
+        for pedido in &pedidos {
+            assert_eq!(arquivo.consultar(&indice, pedido.order_id).unwrap().unwrap().event_time, pedido.event_time);
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::cabecalho;
+use crate::erro::Resultado;
+use crate::registro::Registro;
+use crate::textos::{FormatoTextos, Textos};
+
+// Memória usada para ordenar quando nada for configurado (64 MiB)
+pub const MEMORIA_ORDENACAO_PADRAO: usize = 64 * 1024 * 1024;
+
+// Máximo de runs abertos ao mesmo tempo durante a intercalação
+const MAX_VIAS: usize = 64;
+
+// Ordenação externa: os registros chegam em qualquer ordem, são acumulados até
+// o limite de memória, ordenados e gravados em runs temporários; no fim os runs
+// são intercalados (k-way merge) no arquivo de destino. Registros com a mesma
+// chave saem na ordem em que chegaram, como no sort_by_key em memória, então o
+// resultado é idêntico ao de gravar_ordenados. Como as chaves iguais ficam
+// juntas na intercalação, é nela que as repetidas são descartadas ou recusadas.
+pub struct OrdenadorExterno<R: Registro> {
+    caminho_destino: String,
+    max_em_memoria: usize,
+    buffer: Vec<(R, Origem)>,
+    runs: Vec<String>,
+    proximo_run: usize,
+    formato_textos: FormatoTextos,
+    // Heap de textos do destino, criado com o primeiro run: os runs já guardam
+    // os registros codificados para o destino e a intercalação só copia os bytes
+    textos: Option<Textos>,
+}
+
+// De onde veio o registro (na importação, a linha do CSV e o byte em que ela
+// começa). Vai junto com ele nos runs, para as repetidas serem relatadas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Origem {
+    pub linha: u64,
+    pub byte: u64,
+}
+
+const TAMANHO_ORIGEM: usize = 16;
+
+impl Origem {
+    fn to_bytes(self) -> [u8; TAMANHO_ORIGEM] {
+        let mut bytes = [0u8; TAMANHO_ORIGEM];
+        bytes[0..8].copy_from_slice(&self.linha.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.byte.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Origem {
+            linha: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            byte: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+        }
+    }
+}
+
+// O que fazer com registros de mesma chave ao gravar o destino
+enum Repetidas<'a> {
+    // Todos são gravados, na ordem de chegada
+    Manter,
+    // Fica o último; as chaves dos descartados vão para o vetor
+    ManterUltima(&'a mut Vec<i64>),
+    // Fica o primeiro; cada outro é passado a `recusar` com a sua origem e a do aceito
+    ManterPrimeira(&'a mut dyn FnMut(i64, Origem, Origem) -> Resultado<()>),
+}
+
+impl<R: Registro> OrdenadorExterno<R> {
+    pub fn novo(caminho_destino: &str, memoria: usize) -> Self {
+        OrdenadorExterno {
+            caminho_destino: caminho_destino.to_string(),
+            max_em_memoria: (memoria / (R::TAMANHO_REGISTRO + TAMANHO_ORIGEM)).max(1),
+            buffer: Vec::new(),
+            runs: Vec::new(),
+            proximo_run: 0,
+            formato_textos: FormatoTextos::default(),
+            textos: None,
+        }
+    }
+
+    pub fn com_formato_textos(mut self, formato: FormatoTextos) -> Self {
+        self.formato_textos = formato;
+        self
+    }
+
+    pub fn adicionar(&mut self, registro: R) -> Resultado<()> {
+        self.adicionar_com_origem(registro, Origem::default())
+    }
+
+    pub fn adicionar_com_origem(&mut self, registro: R, origem: Origem) -> Resultado<()> {
+        self.buffer.push((registro, origem));
+        if self.buffer.len() >= self.max_em_memoria {
+            self.gravar_run()?;
+        }
+        Ok(())
+    }
+
+    // Grava o arquivo de destino (com cabeçalho) e apaga os runs. Retorna quantos registros foram gravados.
+    pub fn finalizar(self) -> Resultado<usize> {
+        self.gravar_destino(Repetidas::Manter)
+    }
+
+    // Como finalizar, mas de cada grupo de registros com a mesma chave só grava
+    // o que chegou por último. Retorna também as chaves dos descartados (uma por
+    // registro descartado).
+    pub fn finalizar_sem_duplicatas(self) -> Resultado<(usize, Vec<i64>)> {
+        let mut descartadas = Vec::new();
+        let total = self.gravar_destino(Repetidas::ManterUltima(&mut descartadas))?;
+        Ok((total, descartadas))
+    }
+
+    // Como finalizar, mas de cada chave só grava o registro que chegou primeiro.
+    // Os outros vão para `recusar(chave, origem do recusado, origem do aceito)`,
+    // um de cada vez, em ordem de chave.
+    pub fn finalizar_recusando_repetidas(self, mut recusar: impl FnMut(i64, Origem, Origem) -> Resultado<()>) -> Resultado<usize> {
+        self.gravar_destino(Repetidas::ManterPrimeira(&mut recusar))
+    }
+
+    fn gravar_destino(mut self, mut repetidas: Repetidas) -> Resultado<usize> {
+        // Tudo coube na memória: não precisa de arquivo temporário
+        if self.runs.is_empty() {
+            self.buffer.sort_by_key(|(r, _)| r.chave());
+            let mut unicos: Vec<R> = Vec::with_capacity(self.buffer.len());
+            let mut origem_aceita = Origem::default();
+            for (registro, origem) in self.buffer.drain(..) {
+                let repetida = unicos.last().is_some_and(|anterior| anterior.chave() == registro.chave());
+                match &mut repetidas {
+                    Repetidas::ManterUltima(descartadas) if repetida => {
+                        descartadas.push(registro.chave());
+                        *unicos.last_mut().unwrap() = registro;
+                    }
+                    Repetidas::ManterPrimeira(recusar) if repetida => recusar(registro.chave(), origem, origem_aceita)?,
+                    _ => {
+                        origem_aceita = origem;
+                        unicos.push(registro);
+                    }
+                }
+            }
+            let total = unicos.len();
+            crate::arquivo::gravar_ordenados_com(&mut unicos, &self.caminho_destino, self.formato_textos)?;
+            return Ok(total);
+        }
+        if !self.buffer.is_empty() {
+            self.gravar_run()?;
+        }
+        // Intercala em grupos até sobrar no máximo MAX_VIAS runs. Os grupos são de
+        // runs consecutivos, o que mantém a ordem de chegada entre chaves iguais.
+        while self.runs.len() > MAX_VIAS {
+            let grupos: Vec<Vec<String>> = self.runs.chunks(MAX_VIAS).map(|g| g.to_vec()).collect();
+            let mut novos = Vec::new();
+            for grupo in grupos {
+                let caminho = self.novo_caminho_run();
+                let mut saida = BufWriter::new(File::create(&caminho)?);
+                intercalar::<R>(&grupo, &mut saida, true, &mut Repetidas::Manter)?;
+                saida.flush()?;
+                novos.push(caminho);
+                for run in &grupo {
+                    std::fs::remove_file(run)?;
+                }
+            }
+            self.runs = novos;
+        }
+
+        if let Some(textos) = self.textos.as_mut() {
+            textos.sincronizar()?;
+        }
+        let mut arquivo = cabecalho::criar_arquivo_dados(&self.caminho_destino, R::LAYOUT, self.formato_textos)?;
+        let mut saida = BufWriter::new(&mut arquivo);
+        let total = intercalar::<R>(&self.runs, &mut saida, false, &mut repetidas)?;
+        saida.flush()?;
+        drop(saida);
+        cabecalho::atualizar_num_registros(&mut arquivo, total)?;
+        Ok(total as usize)
+    }
+
+    fn novo_caminho_run(&mut self) -> String {
+        let caminho = format!("{}.run{}.tmp", self.caminho_destino, self.proximo_run);
+        self.proximo_run += 1;
+        caminho
+    }
+
+    // Ordena o que está na memória e grava como um run: cada registro seguido da
+    // sua origem, sem cabeçalho
+    fn gravar_run(&mut self) -> Resultado<()> {
+        self.buffer.sort_by_key(|(r, _)| r.chave());
+        let caminho = self.novo_caminho_run();
+        // Registra antes de criar, para o Drop apagar o arquivo mesmo se a escrita falhar
+        self.runs.push(caminho.clone());
+        let mut saida = BufWriter::new(File::create(&caminho)?);
+        if self.textos.is_none() {
+            self.textos = Some(Textos::criar(&self.caminho_destino, R::LAYOUT, self.formato_textos)?);
+        }
+        let Some(textos) = self.textos.as_mut() else {
+            unreachable!("heap criado logo acima");
+        };
+        for (registro, origem) in self.buffer.drain(..) {
+            saida.write_all(&registro.to_bytes(textos)?)?;
+            saida.write_all(&origem.to_bytes())?;
+        }
+        saida.flush()?;
+        Ok(())
+    }
+}
+
+// Runs que sobraram de uma ordenação interrompida por erro não ficam no disco
+impl<R: Registro> Drop for OrdenadorExterno<R> {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = std::fs::remove_file(run);
+        }
+    }
+}
+
+// Intercala runs já ordenados em `saida`: outro run (`com_origem`, registro e
+// origem) ou o destino (só os bytes dos registros). Empates na chave são
+// resolvidos pelo número do run (o mais antigo primeiro), e as repetidas seguem
+// `repetidas`.
+fn intercalar<R: Registro>(runs: &[String], saida: &mut impl Write, com_origem: bool, repetidas: &mut Repetidas) -> Resultado<u64> {
+    let mut leitores = Vec::with_capacity(runs.len());
+    for run in runs {
+        leitores.push(BufReader::new(File::open(run)?));
+    }
+    let tamanho = R::TAMANHO_REGISTRO + TAMANHO_ORIGEM;
+    let mut atuais: Vec<Vec<u8>> = vec![vec![0u8; tamanho]; runs.len()];
+    let mut heap = BinaryHeap::new();
+    for (i, leitor) in leitores.iter_mut().enumerate() {
+        if ler_registro(leitor, &mut atuais[i])? {
+            heap.push(Reverse((R::chave_de_bytes(&atuais[i]), i)));
+        }
+    }
+
+    let mut total = 0;
+    let gravado = if com_origem { tamanho } else { R::TAMANHO_REGISTRO };
+    // Com ManterUltima, o registro que espera para ver se o próximo tem a mesma chave
+    let mut pendente: Option<(i64, Vec<u8>)> = None;
+    // Com ManterPrimeira, a chave e a origem do último registro gravado
+    let mut aceita: Option<(i64, Origem)> = None;
+    while let Some(Reverse((chave, i))) = heap.pop() {
+        let origem = Origem::from_bytes(&atuais[i][R::TAMANHO_REGISTRO..]);
+        match repetidas {
+            Repetidas::Manter => {
+                saida.write_all(&atuais[i][..gravado])?;
+                total += 1;
+            }
+            Repetidas::ManterUltima(descartadas) => {
+                if let Some((chave_pendente, bytes)) = pendente.take() {
+                    if chave_pendente == chave {
+                        descartadas.push(chave);
+                    } else {
+                        saida.write_all(&bytes)?;
+                        total += 1;
+                    }
+                }
+                pendente = Some((chave, atuais[i][..gravado].to_vec()));
+            }
+            Repetidas::ManterPrimeira(recusar) => match aceita {
+                Some((chave_aceita, origem_aceita)) if chave_aceita == chave => recusar(chave, origem, origem_aceita)?,
+                _ => {
+                    saida.write_all(&atuais[i][..gravado])?;
+                    total += 1;
+                    aceita = Some((chave, origem));
+                }
+            },
+        }
+        if ler_registro(&mut leitores[i], &mut atuais[i])? {
+            heap.push(Reverse((R::chave_de_bytes(&atuais[i]), i)));
+        }
+    }
+    if let Some((_, bytes)) = pendente {
+        saida.write_all(&bytes)?;
+        total += 1;
+    }
+    Ok(total)
+}
+
+// Lê o próximo registro do run; false no fim do arquivo
+fn ler_registro(leitor: &mut BufReader<File>, buffer: &mut [u8]) -> Resultado<bool> {
+    match leitor.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::gravar_ordenados;
+    use crate::cabecalho::TAMANHO_CABECALHO;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_ordenacao_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(nome).to_string_lossy().to_string()
+    }
+
+    // Com memória para poucos registros (muitos runs e mais de uma passada de
+    // intercalação) o corpo do arquivo deve sair igual ao da ordenação em memória
+    #[test]
+    fn ordenacao_externa_igual_a_ordenacao_em_memoria() {
+        let produtos: Vec<Produto> = (0..2000)
+            .map(|i| Produto {
+                product_id: (i * 37) % 101,
+                category_alias: format!("cat{}", i),
+                price: Preco::em_centavos(i * 100),
+                material: "gold".to_string(),
+                stone: String::new(),
+            })
+            .collect();
+
+        let em_memoria = caminho_temporario("em_memoria.dat");
+        gravar_ordenados(&mut produtos.clone(), &em_memoria).unwrap();
+
+        let externo = caminho_temporario("externo.dat");
+        let mut ordenador = OrdenadorExterno::<Produto>::novo(&externo, 10 * Produto::TAMANHO_REGISTRO);
+        for produto in produtos {
+            ordenador.adicionar(produto).unwrap();
+        }
+        assert_eq!(ordenador.finalizar().unwrap(), 2000);
+
+        let a = std::fs::read(&em_memoria).unwrap();
+        let b = std::fs::read(&externo).unwrap();
+        assert_eq!(a[TAMANHO_CABECALHO..], b[TAMANHO_CABECALHO..]);
+        assert_eq!(a[24..32], b[24..32]);
+        // Nenhum run temporário fica para trás
+        let dir = std::path::Path::new(&externo).parent().unwrap();
+        assert!(std::fs::read_dir(dir).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().ends_with(".tmp")));
+    }
+
+    // Sem duplicatas, fica o último registro de cada chave, tanto com tudo em
+    // memória quanto com runs em disco
+    #[test]
+    fn sem_duplicatas_mantem_o_mais_recente() {
+        let produtos: Vec<Produto> = (0..300)
+            .map(|i| Produto {
+                product_id: i % 100,
+                category_alias: String::new(),
+                price: Preco::em_centavos(i * 100),
+                material: String::new(),
+                stone: String::new(),
+            })
+            .collect();
+        for memoria in [usize::MAX / 2, 7 * Produto::TAMANHO_REGISTRO] {
+            let destino = caminho_temporario(&format!("sem_duplicatas_{}.dat", memoria));
+            let mut ordenador = OrdenadorExterno::<Produto>::novo(&destino, memoria);
+            for produto in produtos.clone() {
+                ordenador.adicionar(produto).unwrap();
+            }
+            let (gravados, descartadas) = ordenador.finalizar_sem_duplicatas().unwrap();
+            assert_eq!(gravados, 100);
+            assert_eq!(descartadas.len(), 200);
+            let lidos = crate::arquivo::ler_validos::<Produto>(&destino).unwrap();
+            assert!(lidos.iter().enumerate().all(|(i, p)| p.product_id == i as i64 && p.price == Preco::em_centavos((i + 200) as i64 * 100)));
+        }
+    }
+
+    // Recusando as repetidas, fica o primeiro registro de cada chave e cada
+    // outro é relatado com a sua origem e a do aceito, com ou sem runs em disco
+    #[test]
+    fn recusando_repetidas_mantem_o_primeiro() {
+        let produtos: Vec<Produto> = (0..300)
+            .map(|i| Produto { product_id: (i * 7) % 100, price: Preco::em_centavos(i), ..Produto::default() })
+            .collect();
+        for memoria in [usize::MAX / 2, 7 * Produto::TAMANHO_REGISTRO] {
+            let destino = caminho_temporario(&format!("recusando_{}.dat", memoria));
+            let mut ordenador = OrdenadorExterno::<Produto>::novo(&destino, memoria);
+            for (i, produto) in produtos.iter().enumerate() {
+                ordenador.adicionar_com_origem(produto.clone(), Origem { linha: i as u64, byte: 10 * i as u64 }).unwrap();
+            }
+            let mut recusadas = Vec::new();
+            let gravados = ordenador
+                .finalizar_recusando_repetidas(|chave, recusada, aceita| {
+                    recusadas.push((chave, recusada.linha, aceita.linha));
+                    assert_eq!(recusada.byte, 10 * recusada.linha);
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!((gravados, recusadas.len()), (100, 200));
+            let primeira = |chave: i64| produtos.iter().position(|p| p.product_id == chave).unwrap() as u64;
+            for (chave, recusada, aceita) in &recusadas {
+                assert_eq!(*aceita, primeira(*chave));
+                assert!(recusada > aceita && produtos[*recusada as usize].product_id == *chave);
+            }
+            let lidos = crate::arquivo::ler_validos::<Produto>(&destino).unwrap();
+            assert!(lidos.iter().enumerate().all(|(i, p)| p.product_id == i as i64 && p.price == produtos[primeira(i as i64) as usize].price));
+        }
+    }
+}
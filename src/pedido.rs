@@ -1,352 +1,272 @@
-use std::io::{Write, Read, Seek, SeekFrom};
-use std::fs::OpenOptions;
 use std::convert::TryInto;
+use crate::arquivo::{self, ArquivoSequencial, Insercao, RastroConsulta, RelatorioReconstrucao};
 use crate::indice::IndiceParcial;
+use crate::integridade::{self, ArquivosRelacionados};
+use crate::cabecalho::TipoRegistro;
+use crate::registro::{LayoutAnterior, Registro};
+use crate::data_hora::DataHora;
+use crate::preco::Preco;
+use crate::textos::{RegrasTextos, Textos, Truncamento};
+use crate::erro::{Erro, Resultado};
+use crate::importacao::{self, Colunas, MapeamentoColunas, RelatorioImportacao};
+use crate::intervalo::Intervalo;
+use crate::indice_secundario::{TipoValor, ValorSecundario};
+use csv::StringRecord;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Pedido {
     pub order_id: i64,
     pub user_id: i64,
-    pub event_time: String, // 30 bytes, sempre fixo!
+    pub event_time: DataHora,
     pub product_id: i64,
-    pub price: f64,
+    pub price: Preco,
 }
 
-impl Pedido {
-    pub const TAMANHO_REGISTRO: usize = 62; // 8+8+30+8+8 = 62
+impl Registro for Pedido {
+    const TIPO: TipoRegistro = TipoRegistro::Pedido;
+    const TAMANHO_REGISTRO: usize = 49; // 8+8+10+8+11 = 45, mais 4 de CRC
+    const CAMPOS_SECUNDARIOS: &'static [(&'static str, TipoValor)] = &[
+        ("user_id", TipoValor::Inteiro),
+        ("event_time", TipoValor::DataHora),
+        ("product_id", TipoValor::Inteiro),
+    ];
+    // event_time como texto de 30 bytes, até virar DataHora; depois price em
+    // f64, até virar Preco
+    const LAYOUTS_ANTERIORES: &'static [LayoutAnterior<Self>] = &[
+        LayoutAnterior { tamanho_registro: 66, decodificar: decodificar_com_event_time_texto },
+        LayoutAnterior { tamanho_registro: 46, decodificar: decodificar_com_price_f64 },
+    ];
 
-    pub fn to_bytes(&self) -> Vec<u8> {
+    fn chave(&self) -> i64 {
+        self.order_id
+    }
+
+    fn codificar(&self, _textos: &mut Textos) -> Resultado<Vec<u8>> {
         let mut bytes = Vec::with_capacity(Self::TAMANHO_REGISTRO);
         bytes.extend_from_slice(&self.order_id.to_le_bytes());
         bytes.extend_from_slice(&self.user_id.to_le_bytes());
-        let t = format!("{:<30}", self.event_time);
-        bytes.extend_from_slice(&t.as_bytes()[..30]);
+        bytes.extend_from_slice(&self.event_time.to_bytes());
         bytes.extend_from_slice(&self.product_id.to_le_bytes());
-        bytes.extend_from_slice(&self.price.to_le_bytes());
-        bytes
+        bytes.extend_from_slice(&self.price.to_bytes());
+        Ok(bytes)
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Self {
+    fn decodificar(bytes: &[u8], _textos: &mut Textos) -> Result<Self, String> {
         let order_id = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
         let user_id = i64::from_le_bytes(bytes[8..16].try_into().unwrap());
-        let event_time = String::from_utf8_lossy(&bytes[16..46]).trim().to_string();
-        let product_id = i64::from_le_bytes(bytes[46..54].try_into().unwrap());
-        let price = f64::from_le_bytes(bytes[54..62].try_into().unwrap());
-        Pedido { order_id, user_id, event_time, product_id, price }
+        let event_time = DataHora::from_bytes(&bytes[16..26]).map_err(|e| format!("event_time: {}", e))?;
+        let product_id = i64::from_le_bytes(bytes[26..34].try_into().unwrap());
+        let price = Preco::from_bytes(&bytes[34..45]).map_err(|e| format!("price: {}", e))?;
+        Ok(Pedido { order_id, user_id, event_time, product_id, price })
     }
-}
 
-pub fn inserir_pedidos_ordenados(pedidos: &mut Vec<Pedido>, caminho: &str) -> std::io::Result<()> {
-    pedidos.sort_by_key(|p| p.order_id);
-    let mut arquivo = std::fs::File::create(caminho)?;
-    for pedido in pedidos {
-        let bytes = pedido.to_bytes();
-        arquivo.write_all(&bytes)?;
+    fn valor_secundario(&self, campo: &str) -> Option<ValorSecundario> {
+        match campo {
+            "user_id" => Some(ValorSecundario::Inteiro(self.user_id)),
+            "event_time" => Some(ValorSecundario::DataHora(self.event_time)),
+            "product_id" => Some(ValorSecundario::Inteiro(self.product_id)),
+            _ => None,
+        }
     }
-    Ok(())
 }
 
-pub fn mostrar_pedidos(caminho: &str, limite: usize) -> std::io::Result<Vec<Pedido>> {
-    let mut arquivo = std::fs::File::open(caminho)?;
-    let mut pedidos = Vec::new();
-    let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-    for _ in 0..limite {
-        match arquivo.read_exact(&mut buffer) {
-            Ok(_) => pedidos.push(Pedido::from_bytes(&buffer)),
-            Err(_) => break,
-        }
-    }
-    Ok(pedidos)
+// Layout de 66 bytes, com o event_time como veio do CSV; texto que não é uma
+// data válida impede a conversão (o registro vai para a quarentena)
+fn decodificar_com_event_time_texto(bytes: &[u8], textos: &mut Textos) -> Result<Pedido, String> {
+    let order_id = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
+    let user_id = i64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    let event_time = textos.decodificar(&bytes[16..46], "event_time")?.parse()?;
+    let product_id = i64::from_le_bytes(bytes[46..54].try_into().unwrap());
+    let price = Preco::de_f64(f64::from_le_bytes(bytes[54..62].try_into().unwrap()))?;
+    Ok(Pedido { order_id, user_id, event_time, product_id, price })
+}
+
+// Layout de 46 bytes, com o event_time já tipado e o price em f64
+fn decodificar_com_price_f64(bytes: &[u8], _textos: &mut Textos) -> Result<Pedido, String> {
+    let order_id = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
+    let user_id = i64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    let event_time = DataHora::from_bytes(&bytes[16..26]).map_err(|e| format!("event_time: {}", e))?;
+    let product_id = i64::from_le_bytes(bytes[26..34].try_into().unwrap());
+    let price = Preco::de_f64(f64::from_le_bytes(bytes[34..42].try_into().unwrap()))?;
+    Ok(Pedido { order_id, user_id, event_time, product_id, price })
 }
 
-pub fn busca_binaria_arquivo_pedido(caminho: &str, chave: i64) -> std::io::Result<Option<Pedido>> {
-    let mut arquivo = std::fs::File::open(caminho)?;
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Pedido::TAMANHO_REGISTRO as u64;
-    let mut esq = 0i64;
-    let mut dir = num_registros as i64 - 1;
-    let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-    while esq <= dir {
-        let meio = (esq + dir) / 2;
-        let posicao = meio as u64 * Pedido::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(posicao))?;
-        arquivo.read_exact(&mut buffer)?;
-        let pedido = Pedido::from_bytes(&buffer);
-        if pedido.order_id < chave {
-            esq = meio + 1;
-        } else if pedido.order_id > chave {
-            dir = meio - 1;
-        } else {
-            return Ok(Some(pedido));
+// Alteração parcial de um pedido: só os campos preenchidos mudam. O order_id
+// fica de fora, porque é ele que define a posição do registro.
+#[derive(Debug, Clone, Default)]
+pub struct AlteracaoPedido {
+    pub user_id: Option<i64>,
+    pub event_time: Option<DataHora>,
+    pub product_id: Option<i64>,
+    pub price: Option<Preco>,
+}
+
+impl AlteracaoPedido {
+    // Valida com as mesmas regras da importação antes de mudar qualquer campo
+    // (o event_time já chega interpretado)
+    pub fn aplicar(&self, pedido: &mut Pedido) -> Result<(), String> {
+        if let Some(price) = &self.price {
+            importacao::validar_preco("price", price)?;
+        }
+        if let Some(user_id) = self.user_id {
+            pedido.user_id = user_id;
+        }
+        if let Some(event_time) = self.event_time {
+            pedido.event_time = event_time;
+        }
+        if let Some(product_id) = self.product_id {
+            pedido.product_id = product_id;
         }
+        if let Some(price) = self.price {
+            pedido.price = price;
+        }
+        Ok(())
     }
-    Ok(None)
+}
+
+pub fn inserir_pedidos_ordenados(pedidos: &mut [Pedido], caminho: &str) -> Resultado<()> {
+    arquivo::gravar_ordenados(pedidos, caminho)
+}
+
+// Converte uma linha do CSV (colunas já resolvidas pelo mapeamento) em pedido, validando cada campo
+pub fn pedido_de_csv(record: &StringRecord, colunas: &Colunas) -> Result<Pedido, String> {
+    importacao::exigir_colunas(record, colunas)?;
+    Ok(Pedido {
+        order_id: importacao::campo_id(record, colunas, "order_id")?,
+        user_id: importacao::campo_id(record, colunas, "user_id")?,
+        event_time: importacao::campo_data_hora(record, colunas, "event_time")?,
+        product_id: importacao::campo_id(record, colunas, "product_id")?,
+        price: importacao::campo_preco(record, colunas, "price")?,
+    })
+}
+
+// Lê os pedidos do CSV e grava o arquivo ordenado. Linhas inválidas vão para
+// o arquivo de rejeitados e aparecem no relatório.
+pub fn importar_pedidos_csv(
+    caminho_csv: &str,
+    caminho: &str,
+    mapeamento: &MapeamentoColunas,
+    memoria_ordenacao: usize,
+    regras: &RegrasTextos,
+) -> Resultado<RelatorioImportacao> {
+    importacao::importar_csv(caminho_csv, caminho, mapeamento, memoria_ordenacao, regras, pedido_de_csv)
+}
+
+pub fn mostrar_pedidos(caminho: &str, limite: usize) -> Resultado<Vec<Pedido>> {
+    arquivo::ler_primeiros(caminho, limite)
+}
+
+pub fn busca_binaria_arquivo_pedido(caminho: &str, chave: i64) -> Resultado<Option<Pedido>> {
+    arquivo::busca_binaria(caminho, chave)
 }
 
 pub fn consultar_com_indice_pedido(
     caminho_arquivo: &str,
     indice: &IndiceParcial,
     chave: i64,
-) -> std::io::Result<Option<Pedido>> {
-    if let Some((idx, posicao_inicial)) = indice.buscar_posicao(chave) {
-        let mut arquivo = std::fs::File::open(caminho_arquivo)?;
-        let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-        arquivo.seek(SeekFrom::Start(posicao_inicial))?;
-        let posicao_final = if idx + 1 < indice.entradas.len() {
-            indice.entradas[idx + 1].posicao
-        } else {
-            arquivo.metadata()?.len()
-        };
-        let mut pos_atual = posicao_inicial;
-        while pos_atual < posicao_final {
-            match arquivo.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let pedido = Pedido::from_bytes(&buffer);
-                    if pedido.order_id == chave {
-                        return Ok(Some(pedido));
-                    }
-                    if pedido.order_id > chave {
-                        break;
-                    }
-                    pos_atual += Pedido::TAMANHO_REGISTRO as u64;
-                }
-                Err(_) => break,
-            }
-        }
-    }
-    Ok(None)
-}
-
-pub fn remover_pedido(caminho_arquivo: &str, chave: i64) -> std::io::Result<bool> {
-    let mut arquivo = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(caminho_arquivo)?;
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Pedido::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-    for i in 0..num_registros {
-        let pos = i * Pedido::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        arquivo.read_exact(&mut buffer)?;
-        let pedido = Pedido::from_bytes(&buffer);
-        if pedido.order_id == chave {
-            arquivo.seek(SeekFrom::Start(pos))?;
-            arquivo.write_all(&(-1i64).to_le_bytes())?;
-            return Ok(true);
-        }
-    }
-    Ok(false)
+) -> Resultado<Option<Pedido>> {
+    arquivo::consultar_com_indice(caminho_arquivo, indice, chave)
 }
 
+// Insere em `pedidos`, já configurado (índice, memória, duplicatas, formato dos
+// textos). Recusa com Erro::ProdutoInexistente o pedido cujo product_id não
+// está em `produtos`.
 pub fn inserir_novo_pedido(
-    caminho_arquivo: &str,
-    caminho_overflow: &str,
+    pedidos: &ArquivoSequencial<Pedido>,
     pedido: Pedido,
     indice: &mut IndiceParcial,
-) -> std::io::Result<()> {
-    let mut arquivo_overflow = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(caminho_overflow)?;
-    let bytes = pedido.to_bytes();
-    arquivo_overflow.write_all(&bytes)?;
+    produtos: &ArquivosRelacionados,
+) -> Resultado<Insercao> {
+    integridade::conferir_produto(produtos, &pedido)?;
+    pedidos.inserir(&pedido, indice)
+}
 
-    // Critério: reconstruir quando overflow > 10% do principal
-    let tam_principal = std::fs::File::open(caminho_arquivo)?.metadata()?.len();
-    let tam_overflow = arquivo_overflow.metadata()?.len();
-    if tam_overflow as f64 > tam_principal as f64 * 0.1 {
-        reconstruir_arquivo_e_indice_pedido(caminho_arquivo, caminho_overflow, indice)?;
-    }
-    Ok(())
+// Atualiza no lugar (no principal ou no overflow) os campos preenchidos em `alteracao`
+pub fn atualizar_pedido(
+    pedidos: &ArquivoSequencial<Pedido>,
+    indice: &IndiceParcial,
+    chave: i64,
+    alteracao: &AlteracaoPedido,
+    regras: &RegrasTextos,
+) -> Resultado<(Pedido, Vec<Truncamento>)> {
+    let mut truncamentos = Vec::new();
+    let pedido = pedidos.atualizar(indice, chave, |pedido| {
+        alteracao.aplicar(pedido).map_err(Erro::AlteracaoInvalida)?;
+        truncamentos = regras.ajustar_registro(pedido).map_err(Erro::AlteracaoInvalida)?;
+        Ok(())
+    })?;
+    Ok((pedido, truncamentos))
 }
 
-pub fn reconstruir_arquivo_e_indice_pedido(
+// Pedidos com order_id entre min e max (inclusive), do principal e do overflow, em ordem
+pub fn buscar_intervalo_pedidos(
     caminho_principal: &str,
     caminho_overflow: &str,
-    indice: &mut IndiceParcial,
-) -> std::io::Result<()> {
-    let mut todos_pedidos: Vec<Pedido> = Vec::new();
+    indice: &IndiceParcial,
+    min: i64,
+    max: i64,
+) -> Resultado<Intervalo<Pedido>> {
+    ArquivoSequencial::<Pedido>::novo(caminho_principal, caminho_overflow).buscar_intervalo(indice, min, max)
+}
 
-    {
-        let mut arquivo = std::fs::File::open(caminho_principal)?;
-        let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-        loop {
-            match arquivo.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let pedido = Pedido::from_bytes(&buffer);
-                    if pedido.order_id != -1 { // ignorar removidos
-                        todos_pedidos.push(pedido);
-                    }
-                }
-                Err(_) => break,
-            }
-        }
-    }
-    {
-        let mut arquivo = std::fs::File::open(caminho_overflow)?;
-        let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-        loop {
-            match arquivo.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let pedido = Pedido::from_bytes(&buffer);
-                    if pedido.order_id != -1 {
-                        todos_pedidos.push(pedido);
-                    }
-                }
-                Err(_) => break,
-            }
-        }
-    }
-    inserir_pedidos_ordenados(&mut todos_pedidos, caminho_principal)?;
-    *indice = crate::indice::construir_indice_parcial(caminho_principal, indice.fator_esparsidade)?;
-    std::fs::write(caminho_overflow, "")?;
+// Pedidos de um usuário, pelo índice secundário de user_id (ou varrendo os dados, sem ele)
+pub fn pedidos_por_usuario(
+    caminho_principal: &str,
+    caminho_overflow: &str,
+    indice: &IndiceParcial,
+    user_id: i64,
+) -> Resultado<Vec<Pedido>> {
+    ArquivoSequencial::<Pedido>::novo(caminho_principal, caminho_overflow).buscar_por_campo(
+        indice,
+        "user_id",
+        &ValorSecundario::Inteiro(user_id),
+    )
+}
 
-    Ok(())
+// Pedidos com event_time entre inicio e fim (inclusive), em ordem de tempo, pelo
+// índice secundário de event_time (ou varrendo os dados, sem ele)
+pub fn pedidos_no_periodo(
+    caminho_principal: &str,
+    caminho_overflow: &str,
+    indice: &IndiceParcial,
+    inicio: DataHora,
+    fim: DataHora,
+) -> Resultado<Vec<Pedido>> {
+    ArquivoSequencial::<Pedido>::novo(caminho_principal, caminho_overflow).buscar_por_faixa(
+        indice,
+        "event_time",
+        &ValorSecundario::DataHora(inicio),
+        &ValorSecundario::DataHora(fim),
+    )
 }
 
+// Consulta pontual pelo índice hash (pedidos.dat.hash), que cobre principal e overflow
+pub fn consultar_com_hash_pedido(caminho_principal: &str, caminho_overflow: &str, order_id: i64) -> Resultado<Option<Pedido>> {
+    ArquivoSequencial::<Pedido>::novo(caminho_principal, caminho_overflow).consultar_com_hash(order_id)
+}
+
+pub fn reconstruir_arquivo_e_indice_pedido(
+    pedidos: &ArquivoSequencial<Pedido>,
+    indice: &mut IndiceParcial,
+) -> Resultado<RelatorioReconstrucao> {
+    pedidos.reconstruir(indice)
+}
+
+// Consulta pelo índice no principal, com cada passo no rastro
 pub fn consultar_com_indice_pedido_debug(
     caminho_arquivo: &str,
     indice: &IndiceParcial,
     chave: i64,
-) -> std::io::Result<Option<Pedido>> {
-    println!("\n=== DEBUG: CONSULTA POR INDICE PEDIDO ===");
-    println!("Chave buscada: {}", chave);
-    println!("Total de entradas no indice: {}", indice.entradas.len());
-    println!("Fator de esparsidade: {}", indice.fator_esparsidade);
-    println!();
-    
-    // Passo 1: Busca binária no índice
-    println!(" PASSO 1: Busca binária no índice");
-    println!("   Procurando entrada no índice que contenha a chave {}...", chave);
-    
-    if let Some((idx, posicao_inicial)) = indice.buscar_posicao(chave) {
-        println!("    Entrada encontrada no índice!");
-        println!("   📍 Índice da entrada: {}", idx);
-        println!("   🔑 Chave da entrada: {}", indice.entradas[idx].chave);
-        println!("   📍 Posição no arquivo: {}", posicao_inicial);
-        
-        // Mostra contexto das entradas próximas
-        println!();
-        println!(" Contexto das entradas do índice:");
-        let inicio = if idx >= 2 { idx - 2 } else { 0 };
-        let fim = if idx + 3 < indice.entradas.len() { idx + 3 } else { indice.entradas.len() };
-        
-        for i in inicio..fim {
-            let marcador = if i == idx { "👉" } else { "  " };
-            println!("   {} [{}] Chave: {}, Posição: {}", 
-                marcador, i, indice.entradas[i].chave, indice.entradas[i].posicao);
-        }
-        
-        // Passo 2: Determinar intervalo de busca
-        println!();
-        println!(" PASSO 2: Determinar intervalo de busca");
-        let posicao_final = if idx + 1 < indice.entradas.len() {
-            indice.entradas[idx + 1].posicao
-        } else {
-            std::fs::File::open(caminho_arquivo)?.metadata()?.len()
-        };
-        
-        println!("   📍 Posição inicial: {}", posicao_inicial);
-        println!("   📍 Posição final: {}", posicao_final);
-        println!("   📏 Tamanho do intervalo: {} bytes", posicao_final - posicao_inicial);
-        println!("    Número de registros no intervalo: {}", 
-                (posicao_final - posicao_inicial) / Pedido::TAMANHO_REGISTRO as u64);
-        
-        // Passo 3: Busca sequencial no intervalo
-        println!();
-        println!(" PASSO 3: Busca sequencial no intervalo");
-        let mut arquivo = std::fs::File::open(caminho_arquivo)?;
-        let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-        arquivo.seek(SeekFrom::Start(posicao_inicial))?;
-        
-        let mut pos_atual = posicao_inicial;
-        let mut contador_registros = 0;
-        
-        println!("   🔍 Iniciando busca sequencial...");
-        
-        while pos_atual < posicao_final {
-            match arquivo.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let pedido = Pedido::from_bytes(&buffer);
-                    contador_registros += 1;
-                    
-                    println!("    Registro {}: ID={}, Posição={}", 
-                            contador_registros, pedido.order_id, pos_atual);
-                    
-                    if pedido.order_id == chave {
-                        println!("    SUCESSO! Pedido encontrado!");
-                        println!("    Pedido: {:?}", pedido);
-                        return Ok(Some(pedido));
-                    }
-                    
-                    if pedido.order_id > chave {
-                        println!("     Chave {} maior que a buscada {}, parando busca", 
-                                pedido.order_id, chave);
-                        break;
-                    }
-                    
-                    pos_atual += Pedido::TAMANHO_REGISTRO as u64;
-                }
-                Err(_) => {
-                    println!("    Erro ao ler registro na posição {}", pos_atual);
-                    break;
-                }
-            }
-        }
-        
-        println!("    Total de registros verificados: {}", contador_registros);
-        println!("    Pedido não encontrado no intervalo");
-        
-    } else {
-        println!("    Nenhuma entrada encontrada no índice para a chave {}", chave);
-    }
-    
-    println!("\n === FIM DO DEBUG ===");
-    Ok(None)
+) -> Resultado<RastroConsulta<Pedido>> {
+    arquivo::rastrear_consulta(caminho_arquivo, None, indice, chave)
 }
 
 
-pub fn remover_pedido_com_overflow(caminho_principal: &str, caminho_overflow: &str, chave: i64) -> std::io::Result<bool> {
-    // Primeiro tenta remover do arquivo principal
-    if remover_pedido(caminho_principal, chave)? {
-        return Ok(true);
-    }
-    
-    // Se não encontrou no principal, tenta remover do overflow
-    if remover_pedido_do_overflow(caminho_overflow, chave)? {
-        return Ok(true);
+pub fn remover_pedido_com_overflow(pedidos: &ArquivoSequencial<Pedido>, indice: &IndiceParcial, chave: i64) -> Resultado<bool> {
+    match pedidos.remover(indice, chave) {
+        Ok(()) => Ok(true),
+        Err(Erro::ChaveNaoEncontrada(_)) => Ok(false),
+        Err(e) => Err(e),
     }
-    
-    Ok(false)
 }
 
-pub fn remover_pedido_do_overflow(caminho_overflow: &str, chave: i64) -> std::io::Result<bool> {
-    if !std::path::Path::new(caminho_overflow).exists() {
-        return Ok(false);
-    }
-    
-    let mut arquivo = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(caminho_overflow)?;
-
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Pedido::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Pedido::TAMANHO_REGISTRO];
-
-    for i in 0..num_registros {
-        let pos = i * Pedido::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        arquivo.read_exact(&mut buffer)?;
-
-        let pedido = Pedido::from_bytes(&buffer);
-        if pedido.order_id == chave {
-            // Marca como removido: sobrescreve order_id para -1
-            arquivo.seek(SeekFrom::Start(pos))?;
-            arquivo.write_all(&(-1i64).to_le_bytes())?;
-            return Ok(true);
-        }
-    }
-    Ok(false)
-}
\ No newline at end of file
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Valor em dinheiro, em ponto fixo: centavos (i64) e o código da moeda (ISO
+// 4217, três letras). Somas e comparações são exatas, ao contrário do f64 que
+// os registros usavam. No registro ocupa TAMANHO_PRECO bytes: centavos e moeda.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Preco {
+    pub centavos: i64,
+    pub moeda: [u8; 3],
+}
+
+pub const TAMANHO_PRECO: usize = 11;
+
+// Moeda dos valores sem código, como os do CSV da loja
+pub const MOEDA_PADRAO: [u8; 3] = *b"USD";
+
+impl Preco {
+    pub fn em_centavos(centavos: i64) -> Self {
+        Preco { centavos, moeda: MOEDA_PADRAO }
+    }
+
+    // Preço gravado como f64 nos layouts anteriores, arredondado para o centavo
+    pub fn de_f64(valor: f64) -> Result<Self, String> {
+        let centavos = (valor * 100.0).round();
+        if !centavos.is_finite() || centavos.abs() >= i64::MAX as f64 {
+            return Err(format!("preço {} não cabe em centavos", valor));
+        }
+        Ok(Preco::em_centavos(centavos as i64))
+    }
+
+    pub fn codigo_moeda(&self) -> &str {
+        std::str::from_utf8(&self.moeda).unwrap_or("???")
+    }
+
+    pub fn negativo(&self) -> bool {
+        self.centavos < 0
+    }
+
+    // Soma de valores da mesma moeda; moedas diferentes ou estouro são erro
+    pub fn somar(&self, outro: &Preco) -> Result<Preco, String> {
+        if self.moeda != outro.moeda {
+            return Err(format!("não dá para somar {} com {}", self.codigo_moeda(), outro.codigo_moeda()));
+        }
+        let centavos = self
+            .centavos
+            .checked_add(outro.centavos)
+            .ok_or_else(|| format!("soma estoura o limite de {}", self.codigo_moeda()))?;
+        Ok(Preco { centavos, moeda: self.moeda })
+    }
+
+    pub fn to_bytes(&self) -> [u8; TAMANHO_PRECO] {
+        let mut bytes = [0u8; TAMANHO_PRECO];
+        bytes[0..8].copy_from_slice(&self.centavos.to_le_bytes());
+        bytes[8..11].copy_from_slice(&self.moeda);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let centavos = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let moeda: [u8; 3] = bytes[8..11].try_into().unwrap();
+        if !moeda.iter().all(u8::is_ascii_uppercase) {
+            return Err(format!("código de moeda inválido: {:?}", String::from_utf8_lossy(&moeda)));
+        }
+        Ok(Preco { centavos, moeda })
+    }
+}
+
+impl Default for Preco {
+    fn default() -> Self {
+        Preco::em_centavos(0)
+    }
+}
+
+// Totais de uma lista de preços, um por moeda, na ordem em que cada moeda aparece
+pub fn totais<'a>(precos: impl IntoIterator<Item = &'a Preco>) -> Result<Vec<Preco>, String> {
+    let mut totais: Vec<Preco> = Vec::new();
+    for preco in precos {
+        match totais.iter_mut().find(|total| total.moeda == preco.moeda) {
+            Some(total) => *total = total.somar(preco)?,
+            None => totais.push(*preco),
+        }
+    }
+    Ok(totais)
+}
+
+// "212.14 USD"
+impl fmt::Display for Preco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sinal = if self.negativo() { "-" } else { "" };
+        let absoluto = self.centavos.unsigned_abs();
+        write!(f, "{}{}.{:02} {}", sinal, absoluto / 100, absoluto % 100, self.codigo_moeda())
+    }
+}
+
+// Nos {:?} dos registros aparece como no Display
+impl fmt::Debug for Preco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Aceita "212.14", "212,14", "212" ou "-3.5", com o código da moeda opcional
+// depois de um espaço ("212.14 BRL"); sem código, vale MOEDA_PADRAO. Mais de
+// duas casas decimais é erro, para não arredondar o valor escondido.
+impl FromStr for Preco {
+    type Err = String;
+
+    fn from_str(texto: &str) -> Result<Self, String> {
+        let invalido = || format!("preço inválido: {:?} (esperado 123.45, com a moeda opcional: 123.45 BRL)", texto);
+        let t = texto.trim();
+        let (valor, moeda) = match t.split_once(' ') {
+            Some((valor, codigo)) => {
+                let codigo = codigo.trim().to_ascii_uppercase();
+                let moeda: [u8; 3] = codigo.as_bytes().try_into().map_err(|_| invalido())?;
+                if !moeda.iter().all(u8::is_ascii_uppercase) {
+                    return Err(invalido());
+                }
+                (valor, moeda)
+            }
+            None => (t, MOEDA_PADRAO),
+        };
+        let (negativo, valor) = match valor.strip_prefix('-') {
+            Some(resto) => (true, resto),
+            None => (false, valor),
+        };
+        let (inteiro, fracao) = valor.split_once(['.', ',']).unwrap_or((valor, ""));
+        let digitos = |parte: &str| parte.bytes().all(|b| b.is_ascii_digit());
+        if inteiro.is_empty() || !digitos(inteiro) || !digitos(fracao) || fracao.len() > 2 {
+            return Err(invalido());
+        }
+        let centavos = inteiro
+            .parse::<i64>()
+            .ok()
+            .and_then(|i| i.checked_mul(100))
+            .and_then(|c| c.checked_add(format!("{:0<2}", fracao).parse::<i64>().ok()?))
+            .ok_or_else(invalido)?;
+        Ok(Preco { centavos: if negativo { -centavos } else { centavos }, moeda })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preco(texto: &str) -> Preco {
+        texto.parse().unwrap()
+    }
+
+    // Valores voltam como vieram, as somas são exatas e só juntam a mesma moeda
+    #[test]
+    fn preco_em_centavos_soma_sem_perder_nada() {
+        for texto in ["212.14 USD", "0.10 BRL", "-3.50 EUR", "7.00 USD"] {
+            let lido = preco(texto);
+            assert_eq!(lido.to_string(), texto);
+            assert_eq!(Preco::from_bytes(&lido.to_bytes()).unwrap(), lido);
+        }
+        assert_eq!(preco("212,1"), preco("212.10 usd"));
+        assert_eq!(preco("7").centavos, 700);
+
+        let dez_centavos = vec![preco("0.10"); 10];
+        assert_eq!(totais(&dez_centavos).unwrap(), [preco("1.00")]);
+        assert_eq!(totais(&[preco("1.5"), preco("2 BRL"), preco("0.25")]).unwrap(), [preco("1.75"), preco("2 BRL")]);
+        assert!(preco("1 USD").somar(&preco("1 BRL")).is_err());
+        assert!(Preco::em_centavos(i64::MAX).somar(&preco("0.01")).is_err());
+
+        assert_eq!(Preco::de_f64(0.1 + 0.2).unwrap(), preco("0.30"));
+        assert!(Preco::de_f64(f64::NAN).is_err());
+
+        for invalido in ["", "abc", "1.234", "1.2.3", "1 US", "1 U$D", ".5", "--1"] {
+            assert!(invalido.parse::<Preco>().is_err(), "{:?} deveria ser recusado", invalido);
+        }
+        assert!(Preco::from_bytes(&[0, 0, 0, 0, 0, 0, 0, 0, b'u', b's', b'd']).is_err());
+    }
+}
@@ -1,309 +1,238 @@
-use std::io::{Write, Read, Seek, SeekFrom};
-
-#[derive(Debug, Clone)]
+use std::convert::TryInto;
+use crate::arquivo::{self, ArquivoSequencial, RastroConsulta};
+use crate::indice::IndiceParcial;
+use crate::cabecalho::TipoRegistro;
+use crate::preco::Preco;
+use crate::registro::{LayoutAnterior, Registro};
+use crate::textos::{RegrasTextos, Textos, Truncamento};
+use crate::erro::{Erro, Resultado};
+use crate::integridade::{self, PoliticaRemocao, RemocaoProduto};
+use crate::pedido::Pedido;
+use crate::importacao::{self, Colunas, MapeamentoColunas, RelatorioImportacao};
+use crate::intervalo::Intervalo;
+use crate::indice_secundario::{TipoValor, ValorSecundario};
+use csv::StringRecord;
+
+#[derive(Debug, Clone, Default)]
 pub struct Produto {
     pub product_id: i64,
     pub category_alias: String,
-    pub price: f64,
+    pub price: Preco,
     pub material: String,
     pub stone: String,
 }
 
-impl Produto {
-    pub const TAMANHO_REGISTRO: usize = 87;
+impl Registro for Produto {
+    const TIPO: TipoRegistro = TipoRegistro::Produto;
+    const TAMANHO_REGISTRO: usize = 94; // 90 de campos + 4 de CRC
+    const CAMPOS_SECUNDARIOS: &'static [(&'static str, TipoValor)] =
+        &[("category_alias", TipoValor::Texto), ("material", TipoValor::Texto), ("stone", TipoValor::Texto)];
+    const CAMPOS_TEXTO: &'static [(&'static str, usize)] = &[("category_alias", 30), ("material", 20), ("stone", 20)];
+    // price em f64, até virar Preco
+    const LAYOUTS_ANTERIORES: &'static [LayoutAnterior<Self>] =
+        &[LayoutAnterior { tamanho_registro: 91, decodificar: decodificar_com_price_f64 }];
+
+    fn chave(&self) -> i64 {
+        self.product_id
+    }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
+    fn codificar(&self, textos: &mut Textos) -> Resultado<Vec<u8>> {
         let mut bytes = Vec::with_capacity(Self::TAMANHO_REGISTRO);
         bytes.extend_from_slice(&self.product_id.to_le_bytes());
-        let cat = format!("{:<30}", self.category_alias);
-        bytes.extend_from_slice(&cat.as_bytes()[..30]);
-        bytes.extend_from_slice(&self.price.to_le_bytes());
-        let mat = format!("{:<20}", self.material);
-        bytes.extend_from_slice(&mat.as_bytes()[..20]);
-        let st = format!("{:<20}", self.stone);
-        bytes.extend_from_slice(&st.as_bytes()[..20]);
+        textos.codificar("category_alias", &self.category_alias, 30, &mut bytes)?;
+        bytes.extend_from_slice(&self.price.to_bytes());
+        textos.codificar("material", &self.material, 20, &mut bytes)?;
+        textos.codificar("stone", &self.stone, 20, &mut bytes)?;
         bytes.push(b'\n');
-        bytes
+        Ok(bytes)
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Self {
+    fn decodificar(bytes: &[u8], textos: &mut Textos) -> Result<Self, String> {
         let product_id = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
-        let category_alias = String::from_utf8_lossy(&bytes[8..38]).trim().to_string();
-        let price = f64::from_le_bytes(bytes[38..46].try_into().unwrap());
-        let material = String::from_utf8_lossy(&bytes[46..66]).trim().to_string();
-        let stone = String::from_utf8_lossy(&bytes[66..86]).trim().to_string();
-        Produto { product_id, category_alias, price, material, stone }
-    }
-}
-
-// Funções relacionadas a inserção, busca, mostrar e consulta via índice parcial
-use crate::indice::{IndiceParcial};
-
-pub fn inserir_produtos_ordenados(produtos: &mut Vec<Produto>, caminho: &str) -> std::io::Result<()> {
-    produtos.sort_by_key(|p| p.product_id);
-    let mut arquivo = std::fs::File::create(caminho)?;
-    for produto in produtos {
-        let bytes = produto.to_bytes();
-        arquivo.write_all(&bytes)?;
+        let category_alias = textos.decodificar(&bytes[8..38], "category_alias")?;
+        let price = Preco::from_bytes(&bytes[38..49]).map_err(|e| format!("price: {}", e))?;
+        let material = textos.decodificar(&bytes[49..69], "material")?;
+        let stone = textos.decodificar(&bytes[69..89], "stone")?;
+        Ok(Produto { product_id, category_alias, price, material, stone })
+    }
+
+    fn valor_secundario(&self, campo: &str) -> Option<ValorSecundario> {
+        let valor = match campo {
+            "category_alias" => &self.category_alias,
+            "material" => &self.material,
+            "stone" => &self.stone,
+            _ => return None,
+        };
+        Some(ValorSecundario::Texto(valor.clone()))
     }
-    Ok(())
-}
 
-pub fn mostrar_produtos(caminho: &str, limite: usize) -> std::io::Result<Vec<Produto>> {
-    let mut arquivo = std::fs::File::open(caminho)?;
-    let mut produtos = Vec::new();
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
-    for _ in 0..limite {
-        match arquivo.read_exact(&mut buffer) {
-            Ok(_) => produtos.push(Produto::from_bytes(&buffer)),
-            Err(_) => break,
+    fn texto_mut(&mut self, campo: &str) -> Option<&mut String> {
+        match campo {
+            "category_alias" => Some(&mut self.category_alias),
+            "material" => Some(&mut self.material),
+            "stone" => Some(&mut self.stone),
+            _ => None,
         }
     }
-    Ok(produtos)
 }
 
-pub fn busca_binaria_arquivo(caminho: &str, chave: i64) -> std::io::Result<Option<Produto>> {
-    let mut arquivo = std::fs::File::open(caminho)?;
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Produto::TAMANHO_REGISTRO as u64;
-    let mut esq = 0i64;
-    let mut dir = num_registros as i64 - 1;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
-    while esq <= dir {
-        let meio = (esq + dir) / 2;
-        let posicao = meio as u64 * Produto::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(posicao))?;
-        arquivo.read_exact(&mut buffer)?;
-        let produto = Produto::from_bytes(&buffer);
-        if produto.product_id < chave {
-            esq = meio + 1;
-        } else if produto.product_id > chave {
-            dir = meio - 1;
-        } else {
-            return Ok(Some(produto));
-        }
-    }
-    Ok(None)
+// Layout de 91 bytes, com o price em f64; valor que não cabe em centavos impede
+// a conversão (o registro vai para a quarentena)
+fn decodificar_com_price_f64(bytes: &[u8], textos: &mut Textos) -> Result<Produto, String> {
+    let product_id = i64::from_le_bytes(bytes[0..8].try_into().unwrap());
+    let category_alias = textos.decodificar(&bytes[8..38], "category_alias")?;
+    let price = Preco::de_f64(f64::from_le_bytes(bytes[38..46].try_into().unwrap()))?;
+    let material = textos.decodificar(&bytes[46..66], "material")?;
+    let stone = textos.decodificar(&bytes[66..86], "stone")?;
+    Ok(Produto { product_id, category_alias, price, material, stone })
 }
 
-pub fn remover_produto(caminho_arquivo: &str, chave: i64) -> std::io::Result<bool> {
-    let mut arquivo = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(caminho_arquivo)?;
+// Funções relacionadas a inserção, busca, mostrar e consulta via índice parcial
+
+pub fn inserir_produtos_ordenados(produtos: &mut [Produto], caminho: &str) -> Resultado<()> {
+    arquivo::gravar_ordenados(produtos, caminho)
+}
 
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Produto::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
+// Converte uma linha do CSV (colunas já resolvidas pelo mapeamento) em produto, validando cada campo
+pub fn produto_de_csv(record: &StringRecord, colunas: &Colunas) -> Result<Produto, String> {
+    importacao::exigir_colunas(record, colunas)?;
+    Ok(Produto {
+        product_id: importacao::campo_id(record, colunas, "product_id")?,
+        category_alias: importacao::campo_texto(record, colunas, "category_alias"),
+        price: importacao::campo_preco(record, colunas, "price")?,
+        material: importacao::campo_texto(record, colunas, "material"),
+        stone: importacao::campo_texto(record, colunas, "stone"),
+    })
+}
 
-    for i in 0..num_registros {
-        let pos = i * Produto::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        arquivo.read_exact(&mut buffer)?;
+// Alteração parcial de um produto: só os campos preenchidos mudam. O
+// product_id fica de fora, porque é ele que define a posição do registro.
+#[derive(Debug, Clone, Default)]
+pub struct AlteracaoProduto {
+    pub category_alias: Option<String>,
+    pub price: Option<Preco>,
+    pub material: Option<String>,
+    pub stone: Option<String>,
+}
 
-        let produto = Produto::from_bytes(&buffer);
-        if produto.product_id == chave {
-            // Marca como removido: sobrescreve product_id para -1
-            arquivo.seek(SeekFrom::Start(pos))?;
-            arquivo.write_all(&(-1i64).to_le_bytes())?;
-            return Ok(true);
+impl AlteracaoProduto {
+    // Valida com as mesmas regras da importação antes de mudar qualquer campo. O
+    // tamanho dos textos fica para as regras de textos (RegrasTextos::ajustar_registro).
+    pub fn aplicar(&self, produto: &mut Produto) -> Result<(), String> {
+        if let Some(price) = &self.price {
+            importacao::validar_preco("price", price)?;
+        }
+        if let Some(category_alias) = &self.category_alias {
+            produto.category_alias = category_alias.clone();
         }
+        if let Some(price) = self.price {
+            produto.price = price;
+        }
+        if let Some(material) = &self.material {
+            produto.material = material.clone();
+        }
+        if let Some(stone) = &self.stone {
+            produto.stone = stone.clone();
+        }
+        Ok(())
     }
-    Ok(false) // produto não encontrado
 }
 
+// Lê os produtos do CSV e grava o arquivo ordenado. Linhas inválidas vão para
+// o arquivo de rejeitados e aparecem no relatório.
+pub fn importar_produtos_csv(
+    caminho_csv: &str,
+    caminho: &str,
+    mapeamento: &MapeamentoColunas,
+    memoria_ordenacao: usize,
+    regras: &RegrasTextos,
+) -> Resultado<RelatorioImportacao> {
+    importacao::importar_csv(caminho_csv, caminho, mapeamento, memoria_ordenacao, regras, produto_de_csv)
+}
 
-pub fn consultar_com_indice(caminho_arquivo: &str, indice: &IndiceParcial, chave: i64) -> std::io::Result<Option<Produto>>  {
-    if let Some((idx, posicao_inicial)) = indice.buscar_posicao(chave) {
-        let mut arquivo = std::fs::File::open(caminho_arquivo)?;
-        let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
-        arquivo.seek(SeekFrom::Start(posicao_inicial))?;
-        let posicao_final = if idx + 1 < indice.entradas.len() {
-            indice.entradas[idx + 1].posicao
-        } else {
-            arquivo.metadata()?.len()
-        };
-        let mut pos_atual = posicao_inicial;
-        while pos_atual < posicao_final {
-            match arquivo.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let produto = Produto::from_bytes(&buffer);
-                    if produto.product_id == chave {
-                        return Ok(Some(produto));
-                    }
-                    if produto.product_id > chave {
-                        break;
-                    }
-                    pos_atual += Produto::TAMANHO_REGISTRO as u64;
-                }
-                Err(_) => break,
-            }
-        }
-    }
-    Ok(None)
+pub fn mostrar_produtos(caminho: &str, limite: usize) -> Resultado<Vec<Produto>> {
+    arquivo::ler_primeiros(caminho, limite)
 }
 
+pub fn consultar_com_indice(caminho_arquivo: &str, indice: &IndiceParcial, chave: i64) -> Resultado<Option<Produto>>  {
+    arquivo::consultar_com_indice(caminho_arquivo, indice, chave)
+}
 
 // Função para buscar produto considerando overflow
-pub fn buscar_produto_com_overflow(caminho_principal: &str, caminho_overflow: &str, chave: i64) -> std::io::Result<Option<Produto>> {
-    // Primeiro busca no arquivo principal
-    if let Some(produto) = busca_binaria_arquivo(caminho_principal, chave)? {
-        return Ok(Some(produto));
-    }
-    
-    // Se não encontrou no principal, busca no overflow
-    if let Some(produto) = buscar_no_overflow(caminho_overflow, chave)? {
-        return Ok(Some(produto));
-    }
-    
-    Ok(None)
+pub fn buscar_produto_com_overflow(caminho_principal: &str, caminho_overflow: &str, chave: i64) -> Resultado<Option<Produto>> {
+    ArquivoSequencial::<Produto>::novo(caminho_principal, caminho_overflow).buscar(chave)
 }
 
-// Função para buscar no arquivo de overflow
-pub fn buscar_no_overflow(caminho_overflow: &str, chave: i64) -> std::io::Result<Option<Produto>> {
-    if !std::path::Path::new(caminho_overflow).exists() {
-        return Ok(None);
-    }
-    
-    let mut arquivo = std::fs::File::open(caminho_overflow)?;
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Produto::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
-    
-    for i in 0..num_registros {
-        let pos = i * Produto::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        match arquivo.read_exact(&mut buffer) {
-            Ok(_) => {
-                let produto = Produto::from_bytes(&buffer);
-                if produto.product_id == chave {
-                    return Ok(Some(produto));
-                }
-            }
-            Err(_) => break,
-        }
-    }
-    Ok(None)
+// Função para remover produto considerando overflow; os pedidos do produto
+// seguem a política (integridade.rs). None se o produto não existe.
+pub fn remover_produto_com_overflow(
+    produtos: &ArquivoSequencial<Produto>,
+    indice: &IndiceParcial,
+    chave: i64,
+    pedidos: &ArquivoSequencial<Pedido>,
+    indice_pedidos: &IndiceParcial,
+    politica: PoliticaRemocao,
+) -> Resultado<Option<RemocaoProduto>> {
+    integridade::remover_produto(produtos, indice, pedidos, indice_pedidos, chave, politica)
 }
 
-// Função para remover produto considerando overflow
-pub fn remover_produto_com_overflow(caminho_principal: &str, caminho_overflow: &str, chave: i64) -> std::io::Result<bool> {
-    // Primeiro tenta remover do arquivo principal
-    if remover_produto(caminho_principal, chave)? {
-        return Ok(true);
-    }
-    
-    // Se não encontrou no principal, tenta remover do overflow
-    if remover_do_overflow(caminho_overflow, chave)? {
-        return Ok(true);
-    }
-    
-    Ok(false)
+// Atualiza no lugar (no principal ou no overflow) os campos preenchidos em `alteracao`
+pub fn atualizar_produto(
+    produtos: &ArquivoSequencial<Produto>,
+    indice: &IndiceParcial,
+    chave: i64,
+    alteracao: &AlteracaoProduto,
+    regras: &RegrasTextos,
+) -> Resultado<(Produto, Vec<Truncamento>)> {
+    let mut truncamentos = Vec::new();
+    let produto = produtos.atualizar(indice, chave, |produto| {
+        alteracao.aplicar(produto).map_err(Erro::AlteracaoInvalida)?;
+        truncamentos = regras.ajustar_registro(produto).map_err(Erro::AlteracaoInvalida)?;
+        Ok(())
+    })?;
+    Ok((produto, truncamentos))
 }
 
-// Função para remover do arquivo de overflow
-pub fn remover_do_overflow(caminho_overflow: &str, chave: i64) -> std::io::Result<bool> {
-    if !std::path::Path::new(caminho_overflow).exists() {
-        return Ok(false);
-    }
-    
-    let mut arquivo = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(caminho_overflow)?;
-
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Produto::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
+// Produtos com product_id entre min e max (inclusive), do principal e do overflow, em ordem
+pub fn buscar_intervalo_produtos(
+    caminho_principal: &str,
+    caminho_overflow: &str,
+    indice: &IndiceParcial,
+    min: i64,
+    max: i64,
+) -> Resultado<Intervalo<Produto>> {
+    ArquivoSequencial::<Produto>::novo(caminho_principal, caminho_overflow).buscar_intervalo(indice, min, max)
+}
 
-    for i in 0..num_registros {
-        let pos = i * Produto::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        arquivo.read_exact(&mut buffer)?;
+// Produtos de um material, pelo índice secundário de material (ou varrendo os dados, sem ele)
+pub fn produtos_por_material(
+    caminho_principal: &str,
+    caminho_overflow: &str,
+    indice: &IndiceParcial,
+    material: &str,
+) -> Resultado<Vec<Produto>> {
+    ArquivoSequencial::<Produto>::novo(caminho_principal, caminho_overflow).buscar_por_campo(
+        indice,
+        "material",
+        &ValorSecundario::Texto(material.to_string()),
+    )
+}
 
-        let produto = Produto::from_bytes(&buffer);
-        if produto.product_id == chave {
-            // Marca como removido: sobrescreve product_id para -1
-            arquivo.seek(SeekFrom::Start(pos))?;
-            arquivo.write_all(&(-1i64).to_le_bytes())?;
-            return Ok(true);
-        }
-    }
-    Ok(false)
+// Consulta pontual pelo índice hash (produtos.dat.hash), que cobre principal e overflow
+pub fn consultar_com_hash(caminho_principal: &str, caminho_overflow: &str, product_id: i64) -> Resultado<Option<Produto>> {
+    ArquivoSequencial::<Produto>::novo(caminho_principal, caminho_overflow).consultar_com_hash(product_id)
 }
 
-// Função para consultar com índice considerando overflow
-pub fn consultar_com_indice_e_overflow(caminho_principal: &str, caminho_overflow: &str, indice: &IndiceParcial, chave: i64) -> std::io::Result<Option<Produto>> {
-    // Primeiro busca usando o índice no arquivo principal
-    if let Some(produto) = consultar_com_indice(caminho_principal, indice, chave)? {
-        return Ok(Some(produto));
-    }
-    
-    // Se não encontrou no principal, busca no overflow
-    if let Some(produto) = buscar_no_overflow(caminho_overflow, chave)? {
-        return Ok(Some(produto));
-    }
-    
-    Ok(None)
+pub fn consultar_com_indice_e_overflow(caminho_principal: &str, caminho_overflow: &str, indice: &IndiceParcial, chave: i64) -> Resultado<Option<Produto>> {
+    ArquivoSequencial::<Produto>::novo(caminho_principal, caminho_overflow).consultar(indice, chave)
 }
 
-// Função para consultar com índice e overflow (com debug)
-pub fn consultar_com_indice_e_overflow_debug(caminho_principal: &str, caminho_overflow: &str, indice: &IndiceParcial, chave: i64) -> std::io::Result<Option<Produto>> {
-    println!("\n🔍 === DEBUG: CONSULTA COM ÍNDICE E OVERFLOW ===");
-    println!("Chave buscada: {}", chave);
-    println!("Total de entradas no indice: {}", indice.entradas.len());
-    println!("Fator de esparsidade: {}", indice.fator_esparsidade);
-    println!();
-    
-    // Passo 1: Busca usando índice no arquivo principal
-    println!(" PASSO 1: Busca usando índice no arquivo principal");
-    if let Some(produto) = consultar_com_indice(caminho_principal, indice, chave)? {
-        println!(" Produto encontrado no arquivo principal via índice!");
-        println!(" Produto: {:?}", produto);
-        return Ok(Some(produto));
-    }
-    println!(" Produto não encontrado no arquivo principal via índice");
-    
-    // Passo 2: Busca no arquivo de overflow
-    println!();
-    println!(" PASSO 2: Busca no arquivo de overflow");
-    if !std::path::Path::new(caminho_overflow).exists() {
-        println!("  Arquivo de overflow não existe");
-        return Ok(None);
-    }
-    
-    let mut arquivo = std::fs::File::open(caminho_overflow)?;
-    let tamanho = arquivo.metadata()?.len();
-    let num_registros = tamanho / Produto::TAMANHO_REGISTRO as u64;
-    let mut buffer = vec![0u8; Produto::TAMANHO_REGISTRO];
-    
-    println!(" Total de registros no overflow: {}", num_registros);
-    println!("🔍 Iniciando busca sequencial no overflow...");
-    
-    for i in 0..num_registros {
-        let pos = i * Produto::TAMANHO_REGISTRO as u64;
-        arquivo.seek(SeekFrom::Start(pos))?;
-        match arquivo.read_exact(&mut buffer) {
-            Ok(_) => {
-                let produto = Produto::from_bytes(&buffer);
-                println!("    Registro {}: ID={}, Posição={}", i + 1, produto.product_id, pos);
-                
-                if produto.product_id == chave {
-                    println!("    SUCESSO! Produto encontrado no overflow!");
-                    println!("    Produto: {:?}", produto);
-                    return Ok(Some(produto));
-                }
-            }
-            Err(_) => {
-                println!("    Erro ao ler registro na posição {}", pos);
-                break;
-            }
-        }
-    }
-    
-    println!("    Total de registros verificados no overflow: {}", num_registros);
-    println!("    Produto não encontrado no overflow");
-    
-    println!("\n === FIM DO DEBUG ===");
-    Ok(None)
+// Consulta pelo índice e, se preciso, no overflow, com cada passo no rastro
+pub fn consultar_com_indice_e_overflow_debug(
+    caminho_principal: &str,
+    caminho_overflow: &str,
+    indice: &IndiceParcial,
+    chave: i64,
+) -> Resultado<RastroConsulta<Produto>> {
+    arquivo::rastrear_consulta(caminho_principal, Some(caminho_overflow), indice, chave)
 }
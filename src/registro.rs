@@ -0,0 +1,214 @@
+use crate::cabecalho::TipoRegistro;
+use crate::crc::crc32;
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::indice_secundario::{TipoValor, ValorSecundario};
+use crate::textos::Textos;
+
+// Chave gravada no lugar da original quando um registro é removido logicamente
+pub const CHAVE_REMOVIDA: i64 = -1;
+
+// Todo registro termina com o CRC32 (u32) dos bytes anteriores
+pub const TAMANHO_CRC: usize = 4;
+
+// Layout em que o registro já foi gravado, lido só pela migração (migracao.rs):
+// tamanho total (com o CRC) e como decodificar os campos (sem o CRC) nele
+pub struct LayoutAnterior<R> {
+    pub tamanho_registro: usize,
+    pub decodificar: fn(&[u8], &mut Textos) -> Result<R, String>,
+}
+
+// Registro de tamanho fixo, ordenado por uma chave i64, que pode ser guardado
+// em um ArquivoSequencial (arquivo principal ordenado + área de overflow)
+pub trait Registro: Sized + Clone + Default + std::fmt::Debug + 'static {
+    const TIPO: TipoRegistro;
+    // Tamanho total no arquivo, incluindo o CRC do final
+    const TAMANHO_REGISTRO: usize;
+    // Posição (em bytes) da chave dentro do registro
+    const OFFSET_CHAVE: usize = 0;
+    const LAYOUT: LayoutRegistro = LayoutRegistro {
+        tipo: Self::TIPO,
+        tamanho_registro: Self::TAMANHO_REGISTRO,
+        offset_chave: Self::OFFSET_CHAVE,
+    };
+    // Campos (fora a chave) que podem ter índice secundário, com o tipo do valor
+    const CAMPOS_SECUNDARIOS: &'static [(&'static str, TipoValor)] = &[];
+    // Campos de texto com a largura de cada um no registro (textos.rs)
+    const CAMPOS_TEXTO: &'static [(&'static str, usize)] = &[];
+    // Layouts antigos, do mais antigo para o mais novo; o primeiro é o dos
+    // arquivos anteriores à versão 2 do formato (cabecalho.rs)
+    const LAYOUTS_ANTERIORES: &'static [LayoutAnterior<Self>] = &[];
+
+    fn chave(&self) -> i64;
+    // Campos do registro, sem o CRC (TAMANHO_REGISTRO - TAMANHO_CRC bytes).
+    // Os textos passam por `textos`, que pode acrescentá-los ao heap do arquivo.
+    fn codificar(&self, textos: &mut Textos) -> Resultado<Vec<u8>>;
+    // Inverso de codificar; o erro diz o que há de errado nos bytes
+    fn decodificar(bytes: &[u8], textos: &mut Textos) -> Result<Self, String>;
+
+    fn to_bytes(&self, textos: &mut Textos) -> Resultado<Vec<u8>> {
+        let mut bytes = self.codificar(textos)?;
+        let crc = crc32(&bytes);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        Ok(bytes)
+    }
+
+    // Confere o CRC antes de decodificar
+    fn from_bytes(bytes: &[u8], textos: &mut Textos) -> Result<Self, String> {
+        conferir_crc(&bytes[..Self::TAMANHO_REGISTRO])?;
+        Self::decodificar(&bytes[..Self::TAMANHO_REGISTRO - TAMANHO_CRC], textos)
+    }
+
+    // Mesmo que from_bytes, com a posição do registro no arquivo no erro
+    fn ler_em(bytes: &[u8], posicao: u64, textos: &mut Textos) -> Resultado<Self> {
+        Self::from_bytes(bytes, textos).map_err(|motivo| Erro::RegistroCorrompido { posicao, motivo })
+    }
+
+    // Lê só a chave, sem decodificar o registro inteiro
+    fn chave_de_bytes(bytes: &[u8]) -> i64 {
+        Self::LAYOUT.chave(bytes)
+    }
+
+    fn removido(&self) -> bool {
+        self.chave() == CHAVE_REMOVIDA
+    }
+
+    // Valor de um dos CAMPOS_SECUNDARIOS neste registro
+    fn valor_secundario(&self, _campo: &str) -> Option<ValorSecundario> {
+        None
+    }
+
+    // Um dos CAMPOS_TEXTO, para ajustar à política de tamanho
+    fn texto_mut(&mut self, _campo: &str) -> Option<&mut String> {
+        None
+    }
+}
+
+pub fn conferir_crc(registro: &[u8]) -> Result<(), String> {
+    let (dados, crc) = registro.split_at(registro.len() - TAMANHO_CRC);
+    let gravado = u32::from_le_bytes(crc.try_into().unwrap());
+    let calculado = crc32(dados);
+    if gravado != calculado {
+        return Err(format!("CRC não confere (gravado {:08x}, calculado {:08x})", gravado, calculado));
+    }
+    Ok(())
+}
+
+// Bytes do registro removido logicamente: chave trocada por CHAVE_REMOVIDA e CRC refeito
+pub fn bytes_removido(layout: LayoutRegistro, registro: &[u8]) -> Vec<u8> {
+    let mut bytes = registro[..layout.tamanho_registro].to_vec();
+    bytes[layout.offset_chave..layout.offset_chave + 8].copy_from_slice(&CHAVE_REMOVIDA.to_le_bytes());
+    let fim = bytes.len() - TAMANHO_CRC;
+    let crc = crc32(&bytes[..fim]);
+    bytes[fim..].copy_from_slice(&crc.to_le_bytes());
+    bytes
+}
+
+// Campo de texto de tamanho fixo, completado com espaços
+pub fn texto_fixo(bytes: &[u8], campo: &str) -> Result<String, String> {
+    std::str::from_utf8(bytes)
+        .map(|t| t.trim().to_string())
+        .map_err(|_| format!("{} não é UTF-8 válido", campo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::{gravar_ordenados, ArquivoSequencial, Insercao};
+    use crate::indice::construir_indice_parcial;
+    use crate::pedido::Pedido;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+    use crate::textos::FormatoTextos;
+
+    fn produto(product_id: i64) -> Produto {
+        Produto {
+            product_id,
+            category_alias: "jewelry.ring".to_string(),
+            price: Preco::em_centavos(product_id * 3 + 1),
+            material: "gold".to_string(),
+            stone: "diamond".to_string(),
+        }
+    }
+
+    fn pedido(order_id: i64) -> Pedido {
+        Pedido {
+            order_id,
+            user_id: order_id % 7,
+            event_time: "2018-12-01 11:40:29-03:00".parse().unwrap(),
+            product_id: order_id / 2,
+            price: Preco::em_centavos(order_id * 5),
+        }
+    }
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_registro_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(format!("{}.dat", nome)).to_string_lossy().to_string()
+    }
+
+    // Registro -> bytes -> registro devolve os mesmos campos; um byte trocado
+    // falha no CRC e a remoção lógica continua com o CRC válido
+    fn ida_e_volta<R: Registro>(registro: &R) {
+        let mut textos = Textos::novo(&caminho_temporario("ida_e_volta"), R::LAYOUT, FormatoTextos::Fixos);
+        let bytes = registro.to_bytes(&mut textos).unwrap();
+        assert_eq!(bytes.len(), R::TAMANHO_REGISTRO);
+        assert_eq!(R::chave_de_bytes(&bytes), registro.chave());
+        let lido = R::from_bytes(&bytes, &mut textos).unwrap();
+        assert_eq!(format!("{:?}", lido), format!("{:?}", registro));
+
+        let mut corrompido = bytes.clone();
+        corrompido[R::OFFSET_CHAVE + 8] ^= 0xff;
+        assert!(R::from_bytes(&corrompido, &mut textos).unwrap_err().contains("CRC"));
+
+        let removido = R::from_bytes(&bytes_removido(R::LAYOUT, &bytes), &mut textos).unwrap();
+        assert!(removido.removido() && !registro.removido());
+    }
+
+    #[test]
+    fn codificacao_de_produto_e_pedido() {
+        ida_e_volta(&produto(5013));
+        ida_e_volta(&Produto::default());
+        ida_e_volta(&pedido(1007));
+        ida_e_volta(&Pedido { order_id: i64::MAX, price: Preco::em_centavos(-250), ..pedido(1) });
+    }
+
+    // Busca, consulta pelo índice, inserção no overflow e reconstrução pelo
+    // mesmo código genérico para as duas entidades
+    fn operacoes_genericas<R: Registro>(nome: &str, novo: fn(i64) -> R) {
+        let principal = caminho_temporario(nome);
+        let overflow = format!("{}.overflow", principal);
+        let mut registros: Vec<R> = (0..40).rev().map(|i| novo(i * 10)).collect();
+        gravar_ordenados(&mut registros, &principal).unwrap();
+        let mut indice = construir_indice_parcial(&principal, 4, R::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<R>::novo(&principal, &overflow);
+        for chave in [0, 10, 200, 390] {
+            assert_eq!(format!("{:?}", arquivo.buscar(chave).unwrap().unwrap()), format!("{:?}", novo(chave)));
+            assert_eq!(arquivo.consultar(&indice, chave).unwrap().unwrap().chave(), chave);
+        }
+        assert!(arquivo.buscar(5).unwrap().is_none());
+
+        for chave in [5, 395, -10] {
+            assert_eq!(arquivo.inserir(&novo(chave), &mut indice).unwrap(), Insercao::Inserido);
+        }
+        assert_eq!(arquivo.num_registros_overflow().unwrap(), 3);
+        assert_eq!(format!("{:?}", arquivo.consultar(&indice, 5).unwrap().unwrap()), format!("{:?}", novo(5)));
+
+        assert_eq!(arquivo.reconstruir(&mut indice).unwrap().gravados, 43);
+        assert_eq!(arquivo.num_registros_overflow().unwrap(), 0);
+        let chaves: Vec<i64> = crate::arquivo::ler_validos::<R>(&principal).unwrap().iter().map(R::chave).collect();
+        assert_eq!(chaves.first().copied(), Some(-10));
+        assert!(chaves.windows(2).all(|par| par[0] < par[1]));
+        for chave in [-10, 5, 200, 395] {
+            assert_eq!(arquivo.consultar(&indice, chave).unwrap().unwrap().chave(), chave);
+        }
+        std::fs::remove_dir_all(std::path::Path::new(&principal).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn operacoes_com_produto_e_pedido() {
+        operacoes_genericas("produtos", produto);
+        operacoes_genericas("pedidos", pedido);
+    }
+}
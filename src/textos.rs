@@ -0,0 +1,503 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cabecalho::{self, Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::crc::crc32;
+use crate::erro::{Erro, Resultado};
+use crate::indice::LayoutRegistro;
+use crate::manifesto::{caminho_temporario, SUFIXO_TEMPORARIO};
+use crate::registro::{texto_fixo, Registro};
+
+// Campos de texto dos registros. O registro continua com tamanho fixo (a chave
+// fica sempre no mesmo lugar, para a busca binária), e cada campo de texto
+// ocupa a mesma largura de antes; o que muda é como o texto é guardado nela,
+// conforme o formato marcado no cabeçalho do arquivo de dados:
+//
+//   Fixos      o texto completado com espaços até a largura (formato original:
+//              texto maior que a largura é recusado e os espaços das pontas
+//              somem na leitura)
+//   Variaveis  [tamanho u8][bytes] quando o texto cabe em largura - 1 bytes;
+//              senão [MARCA_HEAP][posição u64][tamanho u32], apontando para o
+//              heap de textos do arquivo (produtos.dat.textos). Nada é cortado
+//              nem aparado.
+//
+// O heap tem o cabeçalho comum seguido dos textos, cada um com o CRC32 dos
+// seus bytes logo depois. Só recebe acréscimos: o texto que um registro deixa
+// de usar fica lá até a próxima reconstrução, que grava um heap novo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatoTextos {
+    #[default]
+    Fixos,
+    Variaveis,
+}
+
+impl FormatoTextos {
+    pub fn from_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "fixos" => Some(FormatoTextos::Fixos),
+            "variaveis" => Some(FormatoTextos::Variaveis),
+            _ => None,
+        }
+    }
+
+    // Maior texto aceito num campo de `largura` bytes
+    pub fn limite(&self, largura: usize) -> usize {
+        match self {
+            FormatoTextos::Fixos => largura,
+            FormatoTextos::Variaveis => TAMANHO_MAXIMO_TEXTO,
+        }
+    }
+}
+
+// Primeiro byte de um campo cujo texto está no heap
+pub const MARCA_HEAP: u8 = 0xFF;
+// Limite por texto no formato variável; mais que isso é erro de entrada, não nome de produto
+pub const TAMANHO_MAXIMO_TEXTO: usize = 4096;
+const TAMANHO_REFERENCIA: usize = 1 + 8 + 4;
+
+// O temporário de um arquivo de dados (produtos.dat.novo) usa o temporário do
+// heap (produtos.dat.textos.novo), para os dois serem trocados juntos pelo manifesto
+pub fn caminho_textos(caminho_dados: &str) -> String {
+    match caminho_dados.strip_suffix(SUFIXO_TEMPORARIO) {
+        Some(definitivo) => caminho_temporario(&caminho_textos(definitivo)),
+        None => format!("{}.textos", caminho_dados),
+    }
+}
+
+// Codifica e decodifica os campos de texto dos registros de um arquivo de dados
+#[derive(Debug)]
+pub struct Textos {
+    formato: FormatoTextos,
+    caminho: String,
+    layout: LayoutRegistro,
+    leitura: Option<File>,
+    escrita: Option<File>,
+    // Textos acrescentados desde a última sincronização
+    pendente: bool,
+}
+
+impl Textos {
+    pub fn novo(caminho_dados: &str, layout: LayoutRegistro, formato: FormatoTextos) -> Self {
+        Textos {
+            formato,
+            caminho: caminho_textos(caminho_dados),
+            layout,
+            leitura: None,
+            escrita: None,
+            pendente: false,
+        }
+    }
+
+    // Para um arquivo de dados que está sendo criado: o heap começa vazio. É
+    // criado também no formato fixo, para a troca pelo manifesto substituir o
+    // heap de uma geração anterior.
+    pub fn criar(caminho_dados: &str, layout: LayoutRegistro, formato: FormatoTextos) -> Resultado<Self> {
+        let textos = Textos::novo(caminho_dados, layout, formato);
+        let mut arquivo = File::create(&textos.caminho)?;
+        Cabecalho::novo(TipoArquivo::Textos, layout, 0, 0).escrever(&mut arquivo)?;
+        arquivo.sync_all()?;
+        Ok(textos)
+    }
+
+    // Textos de um arquivo de dados existente, no formato do cabeçalho dele
+    pub fn do_arquivo(caminho_dados: &str, layout: LayoutRegistro, cabecalho: &Cabecalho) -> Self {
+        Textos::novo(caminho_dados, layout, cabecalho.formato_textos)
+    }
+
+    pub fn formato(&self) -> FormatoTextos {
+        self.formato
+    }
+
+    pub fn caminho(&self) -> &str {
+        &self.caminho
+    }
+
+    // Acrescenta o campo de `largura` bytes a `saida`. Texto maior que o
+    // formato comporta é erro: nada é cortado aqui.
+    pub fn codificar(&mut self, campo: &str, texto: &str, largura: usize, saida: &mut Vec<u8>) -> Resultado<()> {
+        let limite = self.formato.limite(largura);
+        if texto.len() > limite {
+            return Err(texto_longo(campo, texto, limite));
+        }
+        if self.formato == FormatoTextos::Fixos {
+            saida.extend_from_slice(texto.as_bytes());
+            saida.resize(saida.len() + largura - texto.len(), b' ');
+            return Ok(());
+        }
+        let inicio = saida.len();
+        if texto.len() < largura {
+            saida.push(texto.len() as u8);
+            saida.extend_from_slice(texto.as_bytes());
+        } else {
+            let posicao = self.gravar(texto.as_bytes())?;
+            saida.push(MARCA_HEAP);
+            saida.extend_from_slice(&posicao.to_le_bytes());
+            saida.extend_from_slice(&(texto.len() as u32).to_le_bytes());
+        }
+        saida.resize(inicio + largura, 0);
+        Ok(())
+    }
+
+    pub fn decodificar(&mut self, bytes: &[u8], campo: &str) -> Result<String, String> {
+        if self.formato == FormatoTextos::Fixos {
+            return texto_fixo(bytes, campo);
+        }
+        let tamanho = bytes[0] as usize;
+        if bytes[0] == MARCA_HEAP && bytes.len() >= TAMANHO_REFERENCIA {
+            let posicao = u64::from_le_bytes(bytes[1..9].try_into().unwrap());
+            let tamanho = u32::from_le_bytes(bytes[9..13].try_into().unwrap());
+            let lidos = self.ler(posicao, tamanho).map_err(|e| format!("{}: {}", campo, e))?;
+            return String::from_utf8(lidos).map_err(|_| format!("{} não é UTF-8 válido", campo));
+        }
+        if tamanho >= bytes.len() {
+            return Err(format!("{} com tamanho {} maior que o campo", campo, tamanho));
+        }
+        std::str::from_utf8(&bytes[1..1 + tamanho])
+            .map(str::to_string)
+            .map_err(|_| format!("{} não é UTF-8 válido", campo))
+    }
+
+    // Garante no disco os textos acrescentados, antes de gravar os registros que apontam para eles
+    pub fn sincronizar(&mut self) -> Resultado<()> {
+        if self.pendente
+            && let Some(arquivo) = self.escrita.as_mut()
+        {
+            arquivo.sync_data()?;
+        }
+        self.pendente = false;
+        Ok(())
+    }
+
+    fn gravar(&mut self, bytes: &[u8]) -> Resultado<u64> {
+        if self.escrita.is_none() {
+            let mut arquivo = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.caminho)?;
+            if arquivo.metadata()?.len() == 0 {
+                Cabecalho::novo(TipoArquivo::Textos, self.layout, 0, 0).escrever(&mut arquivo)?;
+            } else {
+                Cabecalho::ler(&mut arquivo)?.validar(TipoArquivo::Textos, self.layout)?;
+            }
+            self.escrita = Some(arquivo);
+        }
+        let Some(arquivo) = self.escrita.as_mut() else {
+            unreachable!("heap aberto logo acima");
+        };
+        let posicao = arquivo.seek(SeekFrom::End(0))?;
+        let mut texto = bytes.to_vec();
+        texto.extend_from_slice(&crc32(bytes).to_le_bytes());
+        arquivo.write_all(&texto)?;
+        self.pendente = true;
+        Ok(posicao)
+    }
+
+    fn ler(&mut self, posicao: u64, tamanho: u32) -> Result<Vec<u8>, String> {
+        if self.leitura.is_none() {
+            if !Path::new(&self.caminho).exists() {
+                return Err(format!("heap de textos {} não existe", self.caminho));
+            }
+            let mut arquivo = File::open(&self.caminho).map_err(|e| e.to_string())?;
+            Cabecalho::ler(&mut arquivo)
+                .and_then(|c| c.validar(TipoArquivo::Textos, self.layout))
+                .map_err(|e| format!("{}: {}", self.caminho, e))?;
+            self.leitura = Some(arquivo);
+        }
+        let Some(arquivo) = self.leitura.as_mut() else {
+            unreachable!("heap aberto logo acima");
+        };
+        let fim = posicao + tamanho as u64 + 4;
+        if posicao < TAMANHO_CABECALHO as u64 || fim > arquivo.metadata().map_err(|e| e.to_string())?.len() {
+            return Err(format!("texto na posição {} fora do heap {}", posicao, self.caminho));
+        }
+        let mut bytes = vec![0u8; tamanho as usize + 4];
+        arquivo.seek(SeekFrom::Start(posicao)).map_err(|e| e.to_string())?;
+        arquivo.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+        let crc = u32::from_le_bytes(bytes[tamanho as usize..].try_into().unwrap());
+        bytes.truncate(tamanho as usize);
+        if crc32(&bytes) != crc {
+            return Err(format!("CRC do texto na posição {} do heap não confere", posicao));
+        }
+        Ok(bytes)
+    }
+}
+
+// Textos de um arquivo de dados pelo cabeçalho dele; arquivo inexistente ou
+// vazio ainda não tem formato e fica com `padrao`
+pub fn textos_do_arquivo(caminho_dados: &str, layout: LayoutRegistro, padrao: FormatoTextos) -> Resultado<Textos> {
+    if std::fs::metadata(caminho_dados).map(|m| m.len() == 0).unwrap_or(true) {
+        return Ok(Textos::novo(caminho_dados, layout, padrao));
+    }
+    let cabecalho = Cabecalho::ler(&mut File::open(caminho_dados)?)?;
+    cabecalho.validar(TipoArquivo::Dados, layout)?;
+    Ok(Textos::do_arquivo(caminho_dados, layout, &cabecalho))
+}
+
+// Cria (ou trunca) um arquivo de dados vazio no formato de textos pedido, com o heap vazio
+pub fn criar_arquivo_dados(caminho: &str, layout: LayoutRegistro, formato: FormatoTextos) -> Resultado<(File, Textos)> {
+    let arquivo = cabecalho::criar_arquivo_dados(caminho, layout, formato)?;
+    Ok((arquivo, Textos::criar(caminho, layout, formato)?))
+}
+
+// Texto que não cabe no formato do arquivo
+pub fn texto_longo(campo: &str, texto: &str, limite: usize) -> Erro {
+    Erro::RegistroInvalido(format!("{} com {} bytes excede o limite de {}", campo, texto.len(), limite))
+}
+
+// O que fazer com um texto de entrada (CSV, linha de comando, menu) maior que o
+// campo comporta no formato do arquivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoliticaTexto {
+    // Recusa o registro (a linha do CSV vai para os rejeitados)
+    #[default]
+    Rejeitar,
+    // Corta no último caractere que cabe; só entra na contagem do relatório
+    Truncar,
+    // Corta como Truncar e mostra cada corte
+    Avisar,
+}
+
+impl PoliticaTexto {
+    pub fn from_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "rejeitar" => Some(PoliticaTexto::Rejeitar),
+            "truncar" => Some(PoliticaTexto::Truncar),
+            "avisar" => Some(PoliticaTexto::Avisar),
+            _ => None,
+        }
+    }
+}
+
+// Política de cada campo de texto; os não listados ficam com a padrão. Na configuração:
+//
+//   [produtos.politica_textos]
+//   padrao = "rejeitar"
+//   category_alias = "avisar"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PoliticasTexto {
+    pub padrao: PoliticaTexto,
+    #[serde(flatten)]
+    pub campos: BTreeMap<String, PoliticaTexto>,
+}
+
+impl PoliticasTexto {
+    pub fn do_campo(&self, campo: &str) -> PoliticaTexto {
+        self.campos.get(campo).copied().unwrap_or(self.padrao)
+    }
+}
+
+// Um texto de entrada que foi cortado para caber no campo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncamento {
+    pub campo: String,
+    pub original: String,
+    pub bytes_gravados: usize,
+    // Campo com PoliticaTexto::Avisar
+    pub avisar: bool,
+}
+
+impl fmt::Display for Truncamento {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} cortado de {} para {} bytes: {:?}",
+            self.campo,
+            self.original.len(),
+            self.bytes_gravados,
+            &self.original[..self.bytes_gravados]
+        )
+    }
+}
+
+// Maior prefixo de `texto` com até `limite` bytes que termina numa fronteira de
+// caractere (nunca deixa um caractere de vários bytes pela metade)
+pub fn truncar_utf8(texto: &str, limite: usize) -> &str {
+    if texto.len() <= limite {
+        return texto;
+    }
+    let mut fim = limite;
+    while !texto.is_char_boundary(fim) {
+        fim -= 1;
+    }
+    &texto[..fim]
+}
+
+// Como os textos que chegam são ajustados aos campos de um arquivo: o formato
+// dá o limite de cada campo e a política diz o que fazer com o que passa dele
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegrasTextos {
+    pub formato: FormatoTextos,
+    pub politicas: PoliticasTexto,
+}
+
+impl RegrasTextos {
+    // Ajusta o texto ao campo de `largura` bytes do registro. Retorna o corte
+    // feito, se houve; o erro é o motivo da recusa.
+    pub fn ajustar(&self, campo: &str, texto: &mut String, largura: usize) -> Result<Option<Truncamento>, String> {
+        let limite = self.formato.limite(largura);
+        if texto.len() <= limite {
+            return Ok(None);
+        }
+        let politica = self.politicas.do_campo(campo);
+        if politica == PoliticaTexto::Rejeitar {
+            return Err(format!("{} com {} bytes excede o limite de {}", campo, texto.len(), limite));
+        }
+        let bytes_gravados = truncar_utf8(texto, limite).len();
+        let original = std::mem::take(texto);
+        texto.push_str(&original[..bytes_gravados]);
+        Ok(Some(Truncamento {
+            campo: campo.to_string(),
+            original,
+            bytes_gravados,
+            avisar: politica == PoliticaTexto::Avisar,
+        }))
+    }
+
+    // Ajusta todos os CAMPOS_TEXTO do registro
+    pub fn ajustar_registro<R: Registro>(&self, registro: &mut R) -> Result<Vec<Truncamento>, String> {
+        let mut truncamentos = Vec::new();
+        for (campo, largura) in R::CAMPOS_TEXTO {
+            if let Some(texto) = registro.texto_mut(campo)
+                && let Some(truncamento) = self.ajustar(campo, texto, *largura)?
+            {
+                truncamentos.push(truncamento);
+            }
+        }
+        Ok(truncamentos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::{gravar_ordenados_com, ler_validos, ArquivoSequencial};
+    use crate::indice::construir_indice_parcial;
+    use crate::ordenacao_externa::OrdenadorExterno;
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+    use crate::registro::Registro;
+
+    fn produto(product_id: i64, category_alias: &str, material: &str, stone: &str) -> Produto {
+        Produto {
+            product_id,
+            category_alias: category_alias.to_string(),
+            price: Preco::em_centavos(100),
+            material: material.to_string(),
+            stone: stone.to_string(),
+        }
+    }
+
+    fn conferir(lido: &Produto, esperado: &Produto) {
+        assert_eq!(lido.product_id, esperado.product_id);
+        assert_eq!(lido.category_alias, esperado.category_alias);
+        assert_eq!(lido.material, esperado.material);
+        assert_eq!(lido.stone, esperado.stone);
+    }
+
+    // Acentos, textos maiores que o campo, com caractere de vários bytes na
+    // fronteira da largura e espaços nas pontas voltam exatamente como foram
+    // gravados, no principal e no overflow
+    #[test]
+    fn textos_variaveis_voltam_sem_perda() {
+        let dir = std::env::temp_dir().join(format!("aed2_textos_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("produtos.dat").to_string_lossy().to_string();
+        let overflow = dir.join("produtos_overflow.dat").to_string_lossy().to_string();
+        let originais = vec![
+            produto(1, "joalheria.anel de noivado clássico com brilhantes", "ouro 18k", "diamante lapidação brilhante"),
+            produto(2, "  brincos  ", "prata ", " pérola"),
+            produto(3, &"ã".repeat(15), &format!("{}é", "x".repeat(18)), "💎"),
+            produto(4, "", "", ""),
+        ];
+        let mut produtos = originais.clone();
+        gravar_ordenados_com(&mut produtos, &principal, FormatoTextos::Variaveis).unwrap();
+        let lidos: Vec<Produto> = ler_validos(&principal).unwrap();
+        for (lido, esperado) in lidos.iter().zip(&originais) {
+            conferir(lido, esperado);
+        }
+        assert!(std::fs::metadata(caminho_textos(&principal)).unwrap().len() > TAMANHO_CABECALHO as u64);
+
+        let mut indice = construir_indice_parcial(&principal, 2, Produto::LAYOUT).unwrap();
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow).com_formato_textos(FormatoTextos::Variaveis);
+        let novo = produto(10, "relógios/pulso • edição limitada «ouro rosé»", "aço inoxidável", "safira ");
+        arquivo.inserir(&novo, &mut indice).unwrap();
+        conferir(&arquivo.consultar(&indice, 10).unwrap().unwrap(), &novo);
+        let alterado = arquivo
+            .atualizar(&indice, 1, |p| {
+                p.stone = "água-marinha e turmalina paraíba".to_string();
+                Ok(())
+            })
+            .unwrap();
+        conferir(&arquivo.consultar(&indice, 1).unwrap().unwrap(), &alterado);
+
+        arquivo.reconstruir(&mut indice).unwrap();
+        let lidos: Vec<Produto> = ler_validos(&principal).unwrap();
+        assert_eq!(lidos.len(), 5);
+        conferir(&lidos[0], &alterado);
+        conferir(&lidos[4], &novo);
+
+        // Ordenação externa em runs: os textos vão para o heap do destino antes da intercalação
+        let externo = dir.join("externo.dat").to_string_lossy().to_string();
+        let mut ordenador = OrdenadorExterno::<Produto>::novo(&externo, 2 * Produto::TAMANHO_REGISTRO)
+            .com_formato_textos(FormatoTextos::Variaveis);
+        for produto in lidos.iter().rev() {
+            ordenador.adicionar(produto.clone()).unwrap();
+        }
+        assert_eq!(ordenador.finalizar().unwrap(), 5);
+        let ordenados: Vec<Produto> = ler_validos(&externo).unwrap();
+        for (lido, esperado) in ordenados.iter().zip(&lidos) {
+            conferir(lido, esperado);
+        }
+    }
+
+    // Cortes só em fronteira de caractere, conforme a política de cada campo
+    #[test]
+    fn politicas_cortam_sem_quebrar_caracteres() {
+        assert_eq!(truncar_utf8("anéis", 3), "an");
+        assert_eq!(truncar_utf8("💎💎", 5), "💎");
+        assert_eq!(truncar_utf8("ouro", 10), "ouro");
+
+        let mut politicas = PoliticasTexto::default();
+        politicas.campos.insert("category_alias".to_string(), PoliticaTexto::Avisar);
+        politicas.campos.insert("material".to_string(), PoliticaTexto::Truncar);
+        let regras = RegrasTextos { formato: FormatoTextos::Fixos, politicas };
+        let mut p = produto(1, &format!("{}ção", "x".repeat(28)), &"ã".repeat(11), "rubi");
+        let truncamentos = regras.ajustar_registro(&mut p).unwrap();
+        assert_eq!(p.category_alias, format!("{}ç", "x".repeat(28)));
+        assert_eq!(p.material, "ã".repeat(10));
+        assert_eq!(truncamentos.len(), 2);
+        assert!(truncamentos[0].avisar && !truncamentos[1].avisar);
+        assert_eq!(truncamentos[1].original, "ã".repeat(11));
+        assert_eq!(p.to_bytes(&mut Textos::novo("inexistente.dat", Produto::LAYOUT, FormatoTextos::Fixos)).unwrap().len(), Produto::TAMANHO_REGISTRO);
+
+        // Com a política padrão (rejeitar) o registro é recusado; no formato variável cabe inteiro
+        let mut p = produto(2, "a", "b", &"c".repeat(21));
+        assert!(RegrasTextos::default().ajustar_registro(&mut p).unwrap_err().contains("stone"));
+        let variaveis = RegrasTextos { formato: FormatoTextos::Variaveis, ..RegrasTextos::default() };
+        assert!(variaveis.ajustar_registro(&mut p).unwrap().is_empty());
+    }
+
+    // Um campo curto ocupa a mesma largura nos dois formatos e os registros
+    // antigos (sem a marca no cabeçalho) continuam legíveis
+    #[test]
+    fn formato_fixo_continua_legivel() {
+        let mut bytes = Vec::new();
+        let mut fixos = Textos::novo("inexistente.dat", Produto::LAYOUT, FormatoTextos::Fixos);
+        fixos.codificar("material", "gold", 20, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(fixos.decodificar(&bytes, "material").unwrap(), "gold");
+
+        let mut variaveis = Textos::novo("inexistente.dat", Produto::LAYOUT, FormatoTextos::Variaveis);
+        let mut bytes = Vec::new();
+        variaveis.codificar("material", " gold ", 20, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(variaveis.decodificar(&bytes, "material").unwrap(), " gold ");
+        let registro = produto(5, "a", "b", "c").to_bytes(&mut variaveis).unwrap();
+        assert_eq!(registro.len(), Produto::TAMANHO_REGISTRO);
+    }
+}
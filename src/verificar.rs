@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::arquivo::{percorrer_brutos, ArquivoSequencial};
+use crate::cabecalho::{Cabecalho, TipoArquivo, TAMANHO_CABECALHO};
+use crate::erro::Resultado;
+use crate::indice::IndiceParcial;
+use crate::registro::{Registro, CHAVE_REMOVIDA};
+use crate::textos::Textos;
+
+// Quantos problemas são listados ao mostrar o relatório; todos ficam no vetor
+pub const MAX_PROBLEMAS_NA_TELA: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problema {
+    // Cabeçalho ilegível ou de outro tipo/layout; o arquivo não foi verificado
+    Cabecalho { arquivo: String, motivo: String },
+    // CRC não confere ou campos ilegíveis
+    Corrompido { arquivo: String, posicao: u64, motivo: String },
+    // Contador do cabeçalho diferente do número de registros completos, ou bytes soltos no fim
+    Contagem { arquivo: String, no_cabecalho: u64, encontrados: u64, bytes_sobrando: u64 },
+    // Chave menor que a anterior no arquivo principal
+    ForaDeOrdem { arquivo: String, posicao: u64, chave: i64, anterior: i64 },
+    Duplicada { chave: i64, arquivo: String, posicao: u64, arquivo_original: String, posicao_original: u64 },
+    // Entrada do índice fora de ordem, desalinhada, fora do arquivo ou com a chave errada
+    Indice { entrada: usize, motivo: String },
+}
+
+impl fmt::Display for Problema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problema::Cabecalho { arquivo, motivo } => write!(f, "{}: {}", arquivo, motivo),
+            Problema::Corrompido { arquivo, posicao, motivo } => {
+                write!(f, "{} posição {}: registro corrompido ({})", arquivo, posicao, motivo)
+            }
+            Problema::Contagem { arquivo, no_cabecalho, encontrados, bytes_sobrando } => write!(
+                f,
+                "{}: cabeçalho indica {} registros, arquivo tem {} completos e {} bytes sobrando",
+                arquivo, no_cabecalho, encontrados, bytes_sobrando
+            ),
+            Problema::ForaDeOrdem { arquivo, posicao, chave, anterior } => {
+                write!(f, "{} posição {}: chave {} depois de {} (fora de ordem)", arquivo, posicao, chave, anterior)
+            }
+            Problema::Duplicada { chave, arquivo, posicao, arquivo_original, posicao_original } => write!(
+                f,
+                "{} posição {}: chave {} duplicada (já em {} posição {})",
+                arquivo, posicao, chave, arquivo_original, posicao_original
+            ),
+            Problema::Indice { entrada, motivo } => write!(f, "índice, entrada {}: {}", entrada, motivo),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelatorioVerificacao {
+    pub registros_lidos: u64,
+    pub entradas_indice: u64,
+    pub problemas: Vec<Problema>,
+}
+
+impl RelatorioVerificacao {
+    pub fn sem_problemas(&self) -> bool {
+        self.problemas.is_empty()
+    }
+}
+
+impl fmt::Display for RelatorioVerificacao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Registros lidos: {}", self.registros_lidos)?;
+        writeln!(f, "Entradas do índice conferidas: {}", self.entradas_indice)?;
+        write!(f, "Problemas encontrados: {}", self.problemas.len())?;
+        for problema in self.problemas.iter().take(MAX_PROBLEMAS_NA_TELA) {
+            write!(f, "\n   {}", problema)?;
+        }
+        if self.problemas.len() > MAX_PROBLEMAS_NA_TELA {
+            write!(f, "\n   ... e mais {}", self.problemas.len() - MAX_PROBLEMAS_NA_TELA)?;
+        }
+        Ok(())
+    }
+}
+
+// Confere os arquivos de uma entidade sem alterar nada: CRC de cada registro,
+// contador do cabeçalho, ordem do arquivo principal, chaves duplicadas (no
+// principal, no overflow e entre os dois) e, se o arquivo tiver caminho_indice
+// e ele existir, cada entrada do índice contra o registro para onde aponta.
+// Só retorna erro se não conseguir ler os arquivos.
+pub fn verificar<R: Registro>(arquivo: &ArquivoSequencial<R>) -> Resultado<RelatorioVerificacao> {
+    let mut relatorio = RelatorioVerificacao::default();
+
+    // O overflow é pequeno (no máximo LIMITE_OVERFLOW do principal) e cabe num mapa;
+    // o principal é percorrido uma vez só, sem guardar as chaves
+    let mut no_overflow: HashMap<i64, u64> = HashMap::new();
+    let overflow = arquivo.caminho_overflow.as_str();
+    verificar_arquivo::<R>(overflow, &mut relatorio, |relatorio, posicao, chave| {
+        match no_overflow.get(&chave) {
+            Some(&original) => relatorio.problemas.push(Problema::Duplicada {
+                chave,
+                arquivo: overflow.to_string(),
+                posicao,
+                arquivo_original: overflow.to_string(),
+                posicao_original: original,
+            }),
+            None => {
+                no_overflow.insert(chave, posicao);
+            }
+        }
+    })?;
+
+    let principal = arquivo.caminho_principal.as_str();
+    let mut anterior: Option<(i64, u64)> = None;
+    verificar_arquivo::<R>(principal, &mut relatorio, |relatorio, posicao, chave| {
+        match anterior {
+            Some((chave_anterior, _)) if chave < chave_anterior => relatorio.problemas.push(Problema::ForaDeOrdem {
+                arquivo: principal.to_string(),
+                posicao,
+                chave,
+                anterior: chave_anterior,
+            }),
+            Some((chave_anterior, posicao_anterior)) if chave == chave_anterior => {
+                relatorio.problemas.push(Problema::Duplicada {
+                    chave,
+                    arquivo: principal.to_string(),
+                    posicao,
+                    arquivo_original: principal.to_string(),
+                    posicao_original: posicao_anterior,
+                })
+            }
+            _ => {}
+        }
+        if let Some(&posicao_overflow) = no_overflow.get(&chave) {
+            relatorio.problemas.push(Problema::Duplicada {
+                chave,
+                arquivo: overflow.to_string(),
+                posicao: posicao_overflow,
+                arquivo_original: principal.to_string(),
+                posicao_original: posicao,
+            });
+        }
+        anterior = Some((chave, posicao));
+    })?;
+
+    if let Some(caminho_indice) = &arquivo.caminho_indice
+        && Path::new(caminho_indice).exists()
+    {
+        verificar_indice::<R>(caminho_indice, principal, &mut relatorio)?;
+    }
+    Ok(relatorio)
+}
+
+// Confere cabeçalho, contagem e CRC de um arquivo de dados, chamando `chave_valida`
+// para cada registro legível e não removido, na ordem do arquivo
+fn verificar_arquivo<R: Registro>(
+    caminho: &str,
+    relatorio: &mut RelatorioVerificacao,
+    mut chave_valida: impl FnMut(&mut RelatorioVerificacao, u64, i64),
+) -> Resultado<()> {
+    if std::fs::metadata(caminho).map(|m| m.len() == 0).unwrap_or(true) {
+        return Ok(());
+    }
+    let mut arquivo = File::open(caminho)?;
+    let cabecalho = match Cabecalho::ler(&mut arquivo).and_then(|c| c.validar(TipoArquivo::Dados, R::LAYOUT).map(|_| c)) {
+        Ok(cabecalho) => cabecalho,
+        Err(e) => {
+            relatorio.problemas.push(Problema::Cabecalho { arquivo: caminho.to_string(), motivo: e.to_string() });
+            return Ok(());
+        }
+    };
+
+    let mut textos = Textos::do_arquivo(caminho, R::LAYOUT, &cabecalho);
+    let mut encontrados = 0;
+    let bytes_sobrando = percorrer_brutos::<R>(caminho, |posicao, bytes| {
+        encontrados += 1;
+        relatorio.registros_lidos += 1;
+        match R::from_bytes(bytes, &mut textos) {
+            Ok(registro) if !registro.removido() => chave_valida(relatorio, posicao, registro.chave()),
+            Ok(_) => {}
+            Err(motivo) => relatorio.problemas.push(Problema::Corrompido {
+                arquivo: caminho.to_string(),
+                posicao,
+                motivo,
+            }),
+        }
+        Ok(())
+    })?;
+    if encontrados != cabecalho.num_registros || bytes_sobrando > 0 {
+        relatorio.problemas.push(Problema::Contagem {
+            arquivo: caminho.to_string(),
+            no_cabecalho: cabecalho.num_registros,
+            encontrados,
+            bytes_sobrando,
+        });
+    }
+    Ok(())
+}
+
+fn verificar_indice<R: Registro>(caminho_indice: &str, principal: &str, relatorio: &mut RelatorioVerificacao) -> Resultado<()> {
+    let indice = match IndiceParcial::carregar_binario(caminho_indice, R::LAYOUT) {
+        Ok(indice) => indice,
+        Err(e) => {
+            relatorio.problemas.push(Problema::Cabecalho { arquivo: caminho_indice.to_string(), motivo: e.to_string() });
+            return Ok(());
+        }
+    };
+    let tamanho = R::TAMANHO_REGISTRO as u64;
+    let fim_dados = match std::fs::metadata(principal) {
+        Ok(m) => m.len(),
+        Err(_) => TAMANHO_CABECALHO as u64,
+    };
+    let mut dados = File::open(principal).ok();
+    let mut buffer = vec![0u8; R::TAMANHO_REGISTRO];
+    let mut anterior: Option<i64> = None;
+
+    for (i, entrada) in indice.entradas.iter().enumerate() {
+        relatorio.entradas_indice += 1;
+        let mut problema = |motivo: String| relatorio.problemas.push(Problema::Indice { entrada: i, motivo });
+        if let Some(chave_anterior) = anterior
+            && entrada.chave < chave_anterior
+        {
+            problema(format!("chave {} depois de {} (fora de ordem)", entrada.chave, chave_anterior));
+        }
+        anterior = Some(entrada.chave);
+
+        if entrada.posicao < TAMANHO_CABECALHO as u64 || !(entrada.posicao - TAMANHO_CABECALHO as u64).is_multiple_of(tamanho) {
+            problema(format!("posição {} não é o início de um registro", entrada.posicao));
+            continue;
+        }
+        if entrada.posicao + tamanho > fim_dados {
+            problema(format!("posição {} além do fim de {}", entrada.posicao, principal));
+            continue;
+        }
+        let Some(dados) = dados.as_mut() else { continue };
+        dados.seek(SeekFrom::Start(entrada.posicao))?;
+        dados.read_exact(&mut buffer)?;
+        // Registro removido depois da construção do índice continua valendo como marco
+        let chave = R::chave_de_bytes(&buffer);
+        if chave != entrada.chave && chave != CHAVE_REMOVIDA {
+            problema(format!("aponta para a posição {}, que tem a chave {} em vez de {}", entrada.posicao, chave, entrada.chave));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arquivo::{caminho_quarentena, gravar_ordenados, ler_validos};
+    use crate::preco::Preco;
+    use crate::produto::Produto;
+
+    fn caminho_temporario(nome: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("aed2_verificar_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(nome).to_string_lossy().to_string()
+    }
+
+    // Um byte trocado no meio do arquivo é apontado pela verificação, e o reparo
+    // tira só aquele registro, deixando os arquivos sem problemas
+    #[test]
+    fn reparo_move_registro_corrompido_para_quarentena() {
+        let principal = caminho_temporario("produtos.dat");
+        let overflow = caminho_temporario("produtos_overflow.dat");
+        let indice_path = caminho_temporario("indice_produtos.bin");
+        let mut produtos: Vec<Produto> = (1..=10)
+            .map(|i| Produto {
+                product_id: i,
+                category_alias: "jewelry.ring".to_string(),
+                price: Preco::em_centavos(i * 100),
+                material: "gold".to_string(),
+                stone: String::new(),
+            })
+            .collect();
+        gravar_ordenados(&mut produtos, &principal).unwrap();
+        let arquivo = ArquivoSequencial::<Produto>::novo(&principal, &overflow).com_indice(&indice_path);
+        assert!(verificar(&arquivo).unwrap().sem_problemas());
+
+        let mut bytes = std::fs::read(&principal).unwrap();
+        let posicao = TAMANHO_CABECALHO + 3 * Produto::TAMANHO_REGISTRO;
+        bytes[posicao + 20] ^= 0xFF;
+        std::fs::write(&principal, bytes).unwrap();
+        let relatorio = verificar(&arquivo).unwrap();
+        assert_eq!(relatorio.problemas.len(), 1);
+        assert!(matches!(relatorio.problemas[0], Problema::Corrompido { posicao: p, .. } if p == posicao as u64));
+
+        let mut indice = IndiceParcial::novo(3, Produto::LAYOUT);
+        let relatorio = arquivo.reparar(&mut indice).unwrap();
+        assert_eq!((relatorio.gravados, relatorio.quarentenados), (9, 1));
+        assert!(verificar(&arquivo).unwrap().sem_problemas());
+        let chaves: Vec<i64> = ler_validos::<Produto>(&principal).unwrap().iter().map(|p| p.product_id).collect();
+        assert_eq!(chaves, vec![1, 2, 3, 5, 6, 7, 8, 9, 10]);
+        let quarentena = std::fs::metadata(caminho_quarentena(&principal)).unwrap().len();
+        assert_eq!(quarentena, Produto::TAMANHO_REGISTRO as u64);
+    }
+}
@@ -0,0 +1,310 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::crc::crc32;
+use crate::erro::{Erro, Resultado};
+use crate::manifesto::Manifesto;
+
+// Log de escrita antecipada (redo físico) de uma entidade: produtos.dat.wal.
+//
+// Garantias:
+//   - toda inserção e remoção é gravada no log e sincronizada com o disco
+//     (fsync) antes de tocar nos arquivos de dados; quando a operação retorna
+//     com sucesso ela sobrevive a uma queda do processo ou da máquina;
+//   - uma operação é aplicada por inteiro ou não é aplicada: o registro novo e
+//     o contador do cabeçalho do overflow vão na mesma entrada do log;
+//   - na inicialização as entradas do log são reaplicadas (reescrever os mesmos
+//     bytes na mesma posição é idempotente), os arquivos de dados são
+//     sincronizados e o log é esvaziado (checkpoint);
+//   - uma entrada cortada no fim do log (queda durante a gravação) é
+//     descartada: a operação correspondente nunca foi confirmada;
+//   - a reconstrução e a importação reescrevem os arquivos inteiros e
+//     também esvaziam o log. O log guarda a geração do manifesto em que foi
+//     aberto e é ignorado se ela não for a atual.
+//
+// Formato: cabeçalho de 16 bytes ("AWAL", versão u16, 2 bytes livres, geração
+// u64) seguido de entradas [tamanho u32][crc32 u32][conteúdo]. O conteúdo é o
+// número de escritas (u16) e, para cada uma, [arquivo u8][posição u64]
+// [tamanho u32][bytes].
+const MAGIC_WAL: [u8; 4] = *b"AWAL";
+const VERSAO_WAL: u16 = 1;
+const TAMANHO_CABECALHO_WAL: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArquivoAlvo {
+    Principal = 0,
+    Overflow = 1,
+}
+
+// Bytes a gravar numa posição absoluta de um dos arquivos da entidade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escrita {
+    pub alvo: ArquivoAlvo,
+    pub posicao: u64,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Wal {
+    pub caminho: String,
+    caminho_principal: String,
+    caminho_overflow: String,
+}
+
+pub fn caminho_wal(caminho_principal: &str) -> String {
+    format!("{}.wal", caminho_principal)
+}
+
+fn codificar(escritas: &[Escrita]) -> Vec<u8> {
+    let mut conteudo = Vec::new();
+    conteudo.extend_from_slice(&(escritas.len() as u16).to_le_bytes());
+    for escrita in escritas {
+        conteudo.push(escrita.alvo as u8);
+        conteudo.extend_from_slice(&escrita.posicao.to_le_bytes());
+        conteudo.extend_from_slice(&(escrita.bytes.len() as u32).to_le_bytes());
+        conteudo.extend_from_slice(&escrita.bytes);
+    }
+    let mut entrada = Vec::with_capacity(8 + conteudo.len());
+    entrada.extend_from_slice(&(conteudo.len() as u32).to_le_bytes());
+    entrada.extend_from_slice(&crc32(&conteudo).to_le_bytes());
+    entrada.extend_from_slice(&conteudo);
+    entrada
+}
+
+// None se o conteúdo não for uma lista de escritas bem formada
+fn decodificar(conteudo: &[u8]) -> Option<Vec<Escrita>> {
+    let n = u16::from_le_bytes(conteudo.get(0..2)?.try_into().ok()?) as usize;
+    let mut pos = 2;
+    let mut escritas = Vec::with_capacity(n);
+    for _ in 0..n {
+        let alvo = match *conteudo.get(pos)? {
+            0 => ArquivoAlvo::Principal,
+            1 => ArquivoAlvo::Overflow,
+            _ => return None,
+        };
+        let posicao = u64::from_le_bytes(conteudo.get(pos + 1..pos + 9)?.try_into().ok()?);
+        let tamanho = u32::from_le_bytes(conteudo.get(pos + 9..pos + 13)?.try_into().ok()?) as usize;
+        let bytes = conteudo.get(pos + 13..pos + 13 + tamanho)?.to_vec();
+        pos += 13 + tamanho;
+        escritas.push(Escrita { alvo, posicao, bytes });
+    }
+    (pos == conteudo.len()).then_some(escritas)
+}
+
+impl Wal {
+    pub fn novo(caminho_principal: &str, caminho_overflow: &str) -> Self {
+        Wal {
+            caminho: caminho_wal(caminho_principal),
+            caminho_principal: caminho_principal.to_string(),
+            caminho_overflow: caminho_overflow.to_string(),
+        }
+    }
+
+    fn caminho_alvo(&self, alvo: ArquivoAlvo) -> &str {
+        match alvo {
+            ArquivoAlvo::Principal => &self.caminho_principal,
+            ArquivoAlvo::Overflow => &self.caminho_overflow,
+        }
+    }
+
+    // Grava a operação no log, sincroniza e só então aplica nos arquivos de dados
+    pub fn executar(&self, escritas: &[Escrita]) -> Resultado<()> {
+        let mut log = self.abrir_para_acrescentar()?;
+        log.write_all(&codificar(escritas))?;
+        log.sync_data()?;
+        self.aplicar(escritas)
+    }
+
+    fn aplicar(&self, escritas: &[Escrita]) -> Resultado<()> {
+        for escrita in escritas {
+            let mut arquivo = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(self.caminho_alvo(escrita.alvo))?;
+            arquivo.seek(SeekFrom::Start(escrita.posicao))?;
+            arquivo.write_all(&escrita.bytes)?;
+        }
+        Ok(())
+    }
+
+    // Abre o log no fim; se ele não existir ou for de outra geração, recomeça vazio
+    fn abrir_para_acrescentar(&self) -> Resultado<File> {
+        let geracao = Manifesto::ler(&self.caminho_principal)?.geracao;
+        let mut log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.caminho)?;
+        if ler_geracao(&mut log)? != Some(geracao) {
+            log.set_len(0)?;
+            log.seek(SeekFrom::Start(0))?;
+            let mut cabecalho = Vec::with_capacity(TAMANHO_CABECALHO_WAL as usize);
+            cabecalho.extend_from_slice(&MAGIC_WAL);
+            cabecalho.extend_from_slice(&VERSAO_WAL.to_le_bytes());
+            cabecalho.extend_from_slice(&[0, 0]);
+            cabecalho.extend_from_slice(&geracao.to_le_bytes());
+            log.write_all(&cabecalho)?;
+            log.sync_data()?;
+        }
+        log.seek(SeekFrom::End(0))?;
+        Ok(log)
+    }
+
+    // Reaplica as operações do log e faz o checkpoint. Retorna quantas foram reaplicadas.
+    pub fn reaplicar(&self) -> Resultado<usize> {
+        if !Path::new(&self.caminho).exists() {
+            return Ok(0);
+        }
+        let mut log = File::open(&self.caminho)?;
+        let geracao = Manifesto::ler(&self.caminho_principal)?.geracao;
+        // Log de uma geração anterior já está incorporado aos arquivos reconstruídos
+        if ler_geracao(&mut log)? != Some(geracao) {
+            self.descartar()?;
+            return Ok(0);
+        }
+        let mut resto = Vec::new();
+        log.read_to_end(&mut resto)?;
+
+        let mut operacoes = 0;
+        let mut pos = 0;
+        while pos + 8 <= resto.len() {
+            let tamanho = u32::from_le_bytes(resto[pos..pos + 4].try_into().unwrap()) as usize;
+            let crc = u32::from_le_bytes(resto[pos + 4..pos + 8].try_into().unwrap());
+            let Some(conteudo) = resto.get(pos + 8..pos + 8 + tamanho) else { break };
+            if crc32(conteudo) != crc {
+                break;
+            }
+            let escritas = decodificar(conteudo).ok_or_else(|| {
+                Erro::RegistroCorrompido {
+                    posicao: TAMANHO_CABECALHO_WAL + pos as u64,
+                    motivo: format!("entrada do log {} com CRC válido mas ilegível", self.caminho),
+                }
+            })?;
+            self.aplicar(&escritas)?;
+            operacoes += 1;
+            pos += 8 + tamanho;
+        }
+        self.checkpoint()?;
+        Ok(operacoes)
+    }
+
+    // Sincroniza os arquivos de dados e esvazia o log
+    pub fn checkpoint(&self) -> Resultado<()> {
+        for caminho in [&self.caminho_principal, &self.caminho_overflow] {
+            if Path::new(caminho).exists() {
+                OpenOptions::new().write(true).open(caminho)?.sync_all()?;
+            }
+        }
+        self.descartar()
+    }
+
+    // Apaga o log sem reaplicar nada (os arquivos foram reescritos por inteiro)
+    pub fn descartar(&self) -> Resultado<()> {
+        if Path::new(&self.caminho).exists() {
+            std::fs::remove_file(&self.caminho)?;
+        }
+        Ok(())
+    }
+}
+
+// Geração gravada no cabeçalho do log; None se o log estiver vazio ou ilegível
+fn ler_geracao(log: &mut File) -> Resultado<Option<u64>> {
+    let mut cabecalho = [0u8; TAMANHO_CABECALHO_WAL as usize];
+    log.seek(SeekFrom::Start(0))?;
+    if log.read_exact(&mut cabecalho).is_err() {
+        return Ok(None);
+    }
+    if cabecalho[0..4] != MAGIC_WAL || u16::from_le_bytes([cabecalho[4], cabecalho[5]]) != VERSAO_WAL {
+        return Ok(None);
+    }
+    Ok(Some(u64::from_le_bytes(cabecalho[8..16].try_into().unwrap())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifesto;
+
+    // Principal e overflow de 8 bytes cada, num diretório só do teste
+    fn preparar(nome: &str) -> Wal {
+        let dir = std::env::temp_dir().join(format!("aed2_wal_{}_{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let principal = dir.join("a.dat").to_string_lossy().to_string();
+        let overflow = dir.join("a_overflow.dat").to_string_lossy().to_string();
+        std::fs::write(&principal, b"pppppppp").unwrap();
+        std::fs::write(&overflow, b"oooooooo").unwrap();
+        Wal::novo(&principal, &overflow)
+    }
+
+    fn escrita(alvo: ArquivoAlvo, posicao: u64, bytes: &[u8]) -> Escrita {
+        Escrita { alvo, posicao, bytes: bytes.to_vec() }
+    }
+
+    // Como uma queda logo depois do fsync do log: a entrada está no log, mas
+    // os arquivos de dados não foram tocados
+    fn so_no_log(wal: &Wal, escritas: &[Escrita]) {
+        let mut log = wal.abrir_para_acrescentar().unwrap();
+        log.write_all(&codificar(escritas)).unwrap();
+        log.sync_data().unwrap();
+    }
+
+    fn conteudo(wal: &Wal) -> (String, String) {
+        let ler = |alvo| String::from_utf8(std::fs::read(wal.caminho_alvo(alvo)).unwrap()).unwrap();
+        (ler(ArquivoAlvo::Principal), ler(ArquivoAlvo::Overflow))
+    }
+
+    #[test]
+    fn operacoes_sem_checkpoint_sao_reaplicadas() {
+        let wal = preparar("reaplica");
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Principal, 0, b"AA"), escrita(ArquivoAlvo::Overflow, 6, b"BB")]);
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Overflow, 8, b"CC")]);
+        assert_eq!(conteudo(&wal), ("pppppppp".to_string(), "oooooooo".to_string()));
+
+        let reaberto = Wal::novo(&wal.caminho_principal, &wal.caminho_overflow);
+        assert_eq!(reaberto.reaplicar().unwrap(), 2);
+        assert_eq!(conteudo(&wal), ("AApppppp".to_string(), "ooooooBBCC".to_string()));
+        assert!(!Path::new(&wal.caminho).exists());
+    }
+
+    #[test]
+    fn entrada_cortada_no_fim_e_descartada() {
+        let wal = preparar("cortada");
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Principal, 0, b"AA")]);
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Principal, 2, b"BB")]);
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Principal, 4, b"CCCC")]);
+        let tamanho = std::fs::metadata(&wal.caminho).unwrap().len();
+        OpenOptions::new().write(true).open(&wal.caminho).unwrap().set_len(tamanho - 3).unwrap();
+
+        assert_eq!(wal.reaplicar().unwrap(), 2);
+        assert_eq!(conteudo(&wal).0, "AABBpppp");
+    }
+
+    #[test]
+    fn log_de_outra_geracao_e_ignorado() {
+        let wal = preparar("geracao");
+        so_no_log(&wal, &[escrita(ArquivoAlvo::Principal, 0, b"AA")]);
+        // Uma reconstrução concluída depois do log passa o manifesto para a geração 1
+        manifesto::trocar(&wal.caminho_principal, &[], || Ok(())).unwrap();
+        assert_eq!(Manifesto::ler(&wal.caminho_principal).unwrap().geracao, 1);
+
+        assert_eq!(wal.reaplicar().unwrap(), 0);
+        assert_eq!(conteudo(&wal).0, "pppppppp");
+        assert!(!Path::new(&wal.caminho).exists());
+    }
+
+    #[test]
+    fn checkpoint_esvazia_o_log() {
+        let wal = preparar("checkpoint");
+        wal.executar(&[escrita(ArquivoAlvo::Overflow, 0, b"XY")]).unwrap();
+        assert!(std::fs::metadata(&wal.caminho).unwrap().len() > TAMANHO_CABECALHO_WAL);
+
+        wal.checkpoint().unwrap();
+        assert!(!Path::new(&wal.caminho).exists());
+        assert_eq!(wal.reaplicar().unwrap(), 0);
+        assert_eq!(conteudo(&wal).1, "XYoooooo");
+    }
+}